@@ -0,0 +1,51 @@
+//! Benchmark of extracting fields from a large (~4KB) smartplug response.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::{json, Value};
+use tplink_hs110::ExtractHierarchical;
+
+/// Builds a `get_sysinfo`-like response padded with extra fields up to roughly 4KB.
+fn large_response() -> Value {
+    let mut sysinfo = json!({
+        "active_mode": "schedule",
+        "alias": "Bathroom",
+        "dev_name": "Wi-Fi Smart Plug With Energy Monitoring",
+        "deviceId": "700644160CBBBB3AC78D5DFB15345D6C191F906B",
+        "err_code": 0,
+        "feature": "TIM:ENE",
+        "hw_ver": "1.0",
+        "mac": "70:4F:57:58:C6:FA",
+        "model": "HS110(EU)",
+        "relay_state": 1,
+        "rssi": -69,
+        "sw_ver": "1.2.6 Build 200727 Rel.120821",
+    });
+    for index in 0..140 {
+        sysinfo[format!("extra_field_{index:03}")] = json!(format!("{index:08x}"));
+    }
+
+    let response = json!({"system": {"get_sysinfo": sysinfo}});
+    assert!(response.to_string().len() >= 4096);
+    response
+}
+
+fn extract_hierarchical(c: &mut Criterion) {
+    let response = large_response();
+
+    c.bench_function("extract nested field", |b| {
+        b.iter(|| {
+            black_box(&response)
+                .extract_hierarchical(&["system", "get_sysinfo", "alias"])
+                .unwrap()
+        });
+    });
+    c.bench_function("extract nested object", |b| {
+        b.iter(|| {
+            black_box(&response)
+                .extract_hierarchical(&["system", "get_sysinfo"])
+                .unwrap()
+        });
+    });
+}
+
+criterion_group!(benches, extract_hierarchical);
+criterion_main!(benches);
@@ -0,0 +1,438 @@
+//! Using smartplugs from async code (requires `tokio` feature).
+//!
+//! Cloud management requests are performed over [`tokio::net::TcpStream`] by the runtime the
+//! calling task runs on (it needs IO and time drivers enabled). The rest of requests are still
+//! performed by blocking [`HS110`] methods, which are run on a blocking thread pool of a given
+//! runtime, so they don't stall async tasks. This allows migrating to async code gradually.
+use crate::{
+    cloud_server_params, error::TpLinkHs110Error, extract_checked, protocol, wrap_command,
+    CloudInfo, EmeterReading, ExtractHierarchical, LedState, PowerState, HS110,
+};
+use serde_json::{json, Value};
+use socket2::SockRef;
+use std::{
+    future::Future,
+    io,
+    sync::{Arc, PoisonError},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpSocket, TcpStream},
+    runtime::Runtime,
+};
+
+impl HS110 {
+    /// Converts an instance into an async one running blocking requests on a given `runtime`
+    /// (see the [module documentation](self)). All the settings (address, timeouts, etc.) are
+    /// preserved, see [`AsyncHS110::into_sync`].
+    pub fn into_async(self, runtime: Arc<Runtime>) -> AsyncHS110 {
+        AsyncHS110 {
+            inner: self,
+            runtime,
+        }
+    }
+}
+
+/// HS110 smartplug usable from async code, see [`HS110::into_async`].
+#[derive(Debug, Clone)]
+pub struct AsyncHS110 {
+    /// Blocking instance performing requests.
+    inner: HS110,
+
+    /// Runtime which blocking requests are run on (async requests are run by a calling task).
+    runtime: Arc<Runtime>,
+}
+
+impl AsyncHS110 {
+    /// Converts an instance back into a blocking one (preserving all the settings).
+    pub fn into_sync(self) -> HS110 {
+        self.inner
+    }
+
+    /// Runs any blocking [`HS110`] method on the runtime's blocking thread pool, e.g.
+    /// `smartplug.run_blocking(|smartplug| smartplug.set_alias("Kitchen")).await`.
+    pub async fn run_blocking<F, T>(&self, f: F) -> Result<T, TpLinkHs110Error>
+    where
+        F: FnOnce(&HS110) -> Result<T, TpLinkHs110Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        self.runtime
+            .spawn_blocking(move || f(&inner))
+            .await
+            .map_err(|err| std::io::Error::other(err.to_string()))?
+    }
+
+    /// Attempts to send a provided request to a smartplug, receive a response and represent it as
+    /// a plain text string, like blocking requests do (respecting timeouts, a local address and
+    /// socket options set for a blocking instance). A connection established with
+    /// [`HS110::connect_only`] is not used.
+    async fn request(&self, request: &str) -> Result<String, TpLinkHs110Error> {
+        self.exchange(request).await.inspect_err(|err| {
+            *self
+                .inner
+                .last_error
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(err.clone());
+        })
+    }
+
+    /// Helper function which performs a single request-response exchange with a smartplug over
+    /// a new connection.
+    async fn exchange(&self, request: &str) -> Result<String, TpLinkHs110Error> {
+        let timeout = self.inner.timeout;
+        let mut stream = self.connect().await?;
+        with_timeout(timeout, stream.write_all(&protocol::encrypt(request))).await?;
+
+        // A length header is read first and then exactly as many bytes as it claims, a shortage
+        // is reported by `decrypt`.
+        let mut received = Vec::new();
+        with_timeout(
+            timeout,
+            (&mut stream)
+                .take(protocol::HEADER_LEN as u64)
+                .read_to_end(&mut received),
+        )
+        .await?;
+        if let Ok(header) = <[u8; protocol::HEADER_LEN]>::try_from(received.as_slice()) {
+            with_timeout(
+                timeout,
+                (&mut stream)
+                    .take(u64::from(u32::from_be_bytes(header)))
+                    .read_to_end(&mut received),
+            )
+            .await?;
+        }
+
+        self.inner.decode_response(&received)
+    }
+
+    /// Helper function which establishes a connection to a smartplug, see [`HS110::connect`].
+    async fn connect(&self) -> Result<TcpStream, TpLinkHs110Error> {
+        let socket_addr = self.inner.socket_addr;
+        let socket = match self.inner.bound_socket()? {
+            Some(socket) => {
+                socket.set_nonblocking(true)?;
+                TcpSocket::from_std_stream(socket.into())
+            }
+            None if socket_addr.is_ipv4() => TcpSocket::new_v4()?,
+            None => TcpSocket::new_v6()?,
+        };
+        let stream = with_timeout(
+            self.inner.connect_timeout.or(self.inner.timeout),
+            socket.connect(socket_addr),
+        )
+        .await?;
+        self.inner.configure_stream(&SockRef::from(&stream))?;
+
+        Ok(stream)
+    }
+
+    /// Helper function which sends a `{module: {command: params}}` request, see
+    /// [`HS110::command`].
+    async fn command(
+        &self,
+        module: &'static str,
+        command: &'static str,
+        params: Value,
+    ) -> Result<Value, TpLinkHs110Error> {
+        let request = wrap_command(module, command, params).to_string();
+        extract_checked(
+            &serde_json::from_str::<Value>(&self.request(&request).await?)?,
+            module,
+            command,
+        )
+    }
+
+    /// Attempts to get system information, see [`HS110::info`].
+    pub async fn info(&self) -> Result<Value, TpLinkHs110Error> {
+        self.run_blocking(HS110::info).await
+    }
+
+    /// Attempts to get current power relay state, see [`HS110::power_state`].
+    pub async fn power_state(&self) -> Result<PowerState, TpLinkHs110Error> {
+        self.run_blocking(HS110::power_state).await
+    }
+
+    /// Attempts to switch power relay on or switch it off, see [`HS110::set_power_state`].
+    pub async fn set_power_state(&self, state: PowerState) -> Result<(), TpLinkHs110Error> {
+        self.run_blocking(move |smartplug| smartplug.set_power_state(state))
+            .await
+    }
+
+    /// Attempts to get current LED state, see [`HS110::led_state`].
+    pub async fn led_state(&self) -> Result<LedState, TpLinkHs110Error> {
+        self.run_blocking(HS110::led_state).await
+    }
+
+    /// Attempts to switch LED to a specified state, see [`HS110::set_led_state`].
+    pub async fn set_led_state(&self, led_state: LedState) -> Result<(), TpLinkHs110Error> {
+        self.run_blocking(move |smartplug| smartplug.set_led_state(led_state))
+            .await
+    }
+
+    /// Attempts to get typed values from energy meter, see [`HS110::emeter_reading`].
+    pub async fn emeter_reading(&self) -> Result<EmeterReading, TpLinkHs110Error> {
+        self.run_blocking(HS110::emeter_reading).await
+    }
+
+    /// Attempts to get an information about smartplug connection to TP-Link cloud as a raw JSON,
+    /// see [`HS110::cloudinfo`].
+    #[deprecated(since = "0.3.0", note = "use `cloud_info_typed()` instead")]
+    pub async fn cloudinfo(&self) -> Result<Value, TpLinkHs110Error> {
+        let request = json!({"cnCloud": {"get_info": {}}}).to_string();
+        serde_json::from_str::<Value>(&self.request(&request).await?)?
+            .extract_hierarchical(&["cnCloud", "get_info"])
+    }
+
+    /// Attempts to get an information about smartplug connection to TP-Link cloud, see
+    /// [`HS110::cloud_info_typed`].
+    pub async fn cloud_info_typed(&self) -> Result<CloudInfo, TpLinkHs110Error> {
+        Ok(serde_json::from_value(
+            self.command("cnCloud", "get_info", json!({})).await?,
+        )?)
+    }
+
+    /// Attempts to check whether a smartplug is connected to TP-Link cloud, see
+    /// [`HS110::is_cloud_connected`].
+    pub async fn is_cloud_connected(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed().await?.cld_connection)
+    }
+
+    /// Attempts to check whether a smartplug is bound to a TP-Link cloud account, see
+    /// [`HS110::is_cloud_bound`].
+    pub async fn is_cloud_bound(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed().await?.binded)
+    }
+
+    /// Attempts to get a hostname of a cloud server, see [`HS110::cloud_server`].
+    pub async fn cloud_server(&self) -> Result<String, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed().await?.server)
+    }
+
+    /// Attempts to set a hostname of a cloud server, see [`HS110::set_cloud_server`].
+    pub async fn set_cloud_server(&self, server: &str) -> Result<(), TpLinkHs110Error> {
+        self.command("cnCloud", "set_server_url", cloud_server_params(server)?)
+            .await
+            .map(|_| ())
+    }
+
+    /// Attempts to unbind a smartplug from a TP-Link cloud account, see [`HS110::unbind_cloud`].
+    pub async fn unbind_cloud(&self) -> Result<(), TpLinkHs110Error> {
+        self.command("cnCloud", "unbind", json!({}))
+            .await
+            .map(|_| ())
+    }
+
+    /// Attempts to check whether a smartplug is prevented from connecting to TP-Link cloud, see
+    /// [`HS110::is_cloud_stop_connect`].
+    pub async fn is_cloud_stop_connect(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed().await?.stop_connect)
+    }
+
+    /// Attempts to prevent (or allow again) a smartplug from connecting to TP-Link cloud, see
+    /// [`HS110::set_cloud_stop_connect`].
+    pub async fn set_cloud_stop_connect(&self, stop: bool) -> Result<(), TpLinkHs110Error> {
+        self.command(
+            "cnCloud",
+            "stop_connect",
+            json!({"stopConnect": u8::from(stop)}),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+/// Helper function which limits a duration of an IO operation (if `timeout` is set), expiration
+/// is reported as [`io::ErrorKind::TimedOut`] like blocking sockets do.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    operation: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    match timeout {
+        None => operation.await,
+        Some(duration) => tokio::time::timeout(duration, operation)
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSmartplug;
+    use once_cell::sync::Lazy;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn async_round_trip() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "alias": "Bathroom", "relay_state": 1, "err_code": 0
+        }}})]);
+        let runtime = Arc::new(
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap(),
+        );
+
+        let smartplug = mock
+            .hs110()
+            .with_connect_timeout(Duration::from_secs(2))
+            .into_async(runtime.clone());
+        let (info, power_state) = runtime.block_on(async {
+            (
+                smartplug.info().await.unwrap(),
+                smartplug.power_state().await.unwrap(),
+            )
+        });
+        assert_eq!(info["system"]["get_sysinfo"]["alias"], "Bathroom");
+        assert_eq!(power_state, PowerState::On);
+
+        let smartplug = smartplug.into_sync();
+        assert_eq!(smartplug.socket_addr(), mock.hs110().socket_addr());
+        assert_eq!(
+            format!("{smartplug:?}"),
+            format!(
+                "{:?}",
+                mock.hs110().with_connect_timeout(Duration::from_secs(2))
+            )
+        );
+        assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn async_cloud() {
+        /// Results of async methods are sent between threads.
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CloudInfo>();
+
+        let (smartplug, requests) = start_mock(|request| {
+            if request.pointer("/cnCloud/get_info").is_some() {
+                json!({"cnCloud": {"get_info": {
+                    "binded": 1, "cld_connection": 0, "fwDlPage": "", "fwNotifyType": 0,
+                    "illegalType": 0, "server": "n-devs.tplinkcloud.com", "stopConnect": 1,
+                    "tcspInfo": "", "tcspStatus": 1, "username": "username@example.com",
+                    "err_code": 0
+                }}})
+            } else {
+                let command = request["cnCloud"]
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .next()
+                    .unwrap();
+                json!({"cnCloud": {command: {"err_code": 0}}})
+            }
+        })
+        .await;
+
+        let info = smartplug.cloud_info_typed().await.unwrap();
+        assert!(info.stop_connect);
+        #[allow(deprecated)]
+        let cloudinfo = smartplug.cloudinfo().await.unwrap();
+        assert_eq!(cloudinfo["server"], "n-devs.tplinkcloud.com");
+        assert!(smartplug.is_cloud_stop_connect().await.unwrap());
+        assert!(smartplug.is_cloud_bound().await.unwrap());
+        assert!(!smartplug.is_cloud_connected().await.unwrap());
+        assert_eq!(smartplug.cloud_server().await.unwrap(), info.server);
+        smartplug
+            .set_cloud_server("cloud.example.com")
+            .await
+            .unwrap();
+        assert!(matches!(
+            smartplug.set_cloud_server("").await,
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        smartplug.set_cloud_stop_connect(false).await.unwrap();
+        smartplug.unbind_cloud().await.unwrap();
+        assert_eq!(
+            requests.lock().unwrap()[6..],
+            [
+                json!({"cnCloud": {"set_server_url": {"server": "cloud.example.com"}}}),
+                json!({"cnCloud": {"stop_connect": {"stopConnect": 0}}}),
+                json!({"cnCloud": {"unbind": {}}}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn async_cloud_errors() {
+        let (smartplug, _) = start_mock(
+            |_| json!({"cnCloud": {"unbind": {"err_code": -7, "err_msg": "not bound"}}}),
+        )
+        .await;
+        assert!(matches!(
+            smartplug.unbind_cloud().await,
+            Err(TpLinkHs110Error::SmartplugErrCode(-7))
+        ));
+
+        // A smartplug which accepts a connection, but never responds.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let smartplug = HS110::new(&listener.local_addr().unwrap().to_string())
+            .unwrap()
+            .with_timeout(Duration::from_millis(100))
+            .into_async(BLOCKING_RUNTIME.clone());
+        match smartplug.is_cloud_connected().await {
+            Err(TpLinkHs110Error::IO(err)) => assert_eq!(err.kind(), io::ErrorKind::TimedOut),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert!(matches!(
+            smartplug.into_sync().last_error(),
+            Some(TpLinkHs110Error::IO(_))
+        ));
+    }
+
+    /// Runtime for blocking requests of smartplugs created within async tests (it is never dropped,
+    /// as a runtime can't be dropped within an async context).
+    static BLOCKING_RUNTIME: Lazy<Arc<Runtime>> = Lazy::new(|| {
+        Arc::new(
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap(),
+        )
+    });
+
+    /// Starts a fake smartplug on a local port which answers every request (one per connection,
+    /// like smartplugs do) with `respond`, returns an instance connecting to it and a list of
+    /// received requests.
+    async fn start_mock<F>(respond: F) -> (AsyncHS110, Arc<Mutex<Vec<Value>>>)
+    where
+        F: Fn(&Value) -> Value + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let smartplug = HS110::new(&listener.local_addr().unwrap().to_string())
+            .unwrap()
+            .with_timeout(Duration::from_secs(2))
+            .into_async(BLOCKING_RUNTIME.clone());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; protocol::HEADER_LEN];
+                stream.read_exact(&mut request).await.unwrap();
+                let payload_len = u32::from_be_bytes(request[..].try_into().unwrap());
+                request.resize(protocol::HEADER_LEN + payload_len as usize, 0);
+                stream
+                    .read_exact(&mut request[protocol::HEADER_LEN..])
+                    .await
+                    .unwrap();
+
+                let request =
+                    serde_json::from_str::<Value>(&protocol::decrypt(&request).unwrap()).unwrap();
+                let response = respond(&request);
+                received.lock().unwrap().push(request);
+                stream
+                    .write_all(&protocol::encrypt(response.to_string()))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        (smartplug, requests)
+    }
+}
@@ -0,0 +1,241 @@
+//! Tracking energy consumption against a budget (e.g. a monthly one for cost monitoring).
+use crate::{error::TpLinkHs110Error, HS110};
+use std::fmt;
+
+impl HS110 {
+    /// Creates an energy budget tracker for a smartplug, see [`EnergyBudgetTracker::new`].
+    pub fn energy_budget_tracker(&self, budget_wh: f64) -> EnergyBudgetTracker {
+        EnergyBudgetTracker::new(self.clone(), budget_wh)
+    }
+}
+
+/// Tracks energy consumed since the tracker creation against a budget.
+pub struct EnergyBudgetTracker {
+    /// Smartplug which energy meter is tracked.
+    device: HS110,
+
+    /// Energy budget in Wh.
+    budget_wh: f64,
+
+    /// Total consumed energy (in kWh) reported by the energy meter by the most recent reading,
+    /// `None` until it is successfully read.
+    last_total_kwh: Option<f64>,
+
+    /// Energy consumed since tracking started in Wh (accumulated over energy meter resets).
+    consumed_wh: f64,
+
+    /// Optional callback called once the budget is exhausted.
+    on_budget_exhausted: Option<Box<dyn Fn() + Send>>,
+
+    /// Whether the budget has been found exhausted by the most recent update.
+    exhausted: bool,
+}
+
+impl EnergyBudgetTracker {
+    /// Creates a tracker of energy consumed by `device` starting from now, the total consumed
+    /// energy reported by the energy meter is captured as a baseline. If it can't be read, a
+    /// warning is logged and the baseline is captured by the first successful
+    /// [`EnergyBudgetTracker::update`] instead.
+    pub fn new(device: HS110, budget_wh: f64) -> Self {
+        let last_total_kwh = device
+            .emeter_reading()
+            .map(|reading| reading.total_kwh)
+            .map_err(|err| log::warn!("failed to capture energy budget baseline: {err}"))
+            .ok();
+
+        Self {
+            device,
+            budget_wh,
+            last_total_kwh,
+            consumed_wh: 0.0,
+            on_budget_exhausted: None,
+            exhausted: false,
+        }
+    }
+
+    /// Sets a callback which is called once an update finds the budget exhausted (it isn't called
+    /// again by subsequent updates).
+    #[must_use]
+    pub fn on_budget_exhausted<F>(mut self, callback: F) -> Self
+    where
+        F: Fn() + Send + 'static,
+    {
+        self.on_budget_exhausted = Some(Box::new(callback));
+        self
+    }
+
+    /// Attempts to read the energy meter and find out how much of the budget is consumed. The
+    /// energy meter counter going backwards (e.g. once it is reset) isn't treated as negative
+    /// consumption: energy consumed before the reset is kept and the counter value is counted as
+    /// consumed since the reset.
+    pub fn update(&mut self) -> Result<EnergyBudget, TpLinkHs110Error> {
+        let total_kwh = self.device.emeter_reading()?.total_kwh;
+        match self.last_total_kwh {
+            Some(last_total_kwh) if last_total_kwh <= total_kwh => {
+                self.consumed_wh += (total_kwh - last_total_kwh) * 1000.0;
+            }
+            Some(_) => self.consumed_wh += total_kwh * 1000.0,
+            None => {}
+        }
+        self.last_total_kwh = Some(total_kwh);
+
+        let consumed_wh = self.consumed_wh;
+        let budget = EnergyBudget {
+            budget_wh: self.budget_wh,
+            consumed_wh,
+            remaining_wh: self.budget_wh - consumed_wh,
+            pct_consumed: if self.budget_wh > 0.0 {
+                consumed_wh / self.budget_wh * 100.0
+            } else {
+                100.0
+            },
+        };
+
+        let exhausted = budget.is_exhausted();
+        if exhausted && !self.exhausted {
+            if let Some(on_budget_exhausted) = &self.on_budget_exhausted {
+                on_budget_exhausted();
+            }
+        }
+        self.exhausted = exhausted;
+
+        Ok(budget)
+    }
+
+    /// Whether the most recent [`EnergyBudgetTracker::update`] has found the budget exhausted.
+    pub fn is_budget_exhausted(&self) -> bool {
+        self.exhausted
+    }
+}
+
+impl fmt::Debug for EnergyBudgetTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnergyBudgetTracker")
+            .field("device", &self.device)
+            .field("budget_wh", &self.budget_wh)
+            .field("last_total_kwh", &self.last_total_kwh)
+            .field("consumed_wh", &self.consumed_wh)
+            .field("on_budget_exhausted", &self.on_budget_exhausted.is_some())
+            .field("exhausted", &self.exhausted)
+            .finish()
+    }
+}
+
+/// Energy budget consumption, see [`EnergyBudgetTracker::update`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyBudget {
+    /// Energy budget in Wh.
+    pub budget_wh: f64,
+
+    /// Energy consumed since tracking started in Wh.
+    pub consumed_wh: f64,
+
+    /// Energy left in Wh (negative once the budget is overspent).
+    pub remaining_wh: f64,
+
+    /// Consumed part of the budget in percent (could exceed 100).
+    pub pct_consumed: f64,
+}
+
+impl EnergyBudget {
+    /// Whether the budget is exhausted, i.e. nothing remains.
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining_wh <= 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSmartplug;
+    use serde_json::json;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn progression() {
+        let reading = |total_wh: u32| {
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230000, "current_ma": 500, "power_mw": 115000,
+                "total_wh": total_wh, "err_code": 0
+            }}})
+        };
+        let mock = MockSmartplug::with_responses(
+            [10_000, 10_250, 10_500, 11_000, 11_250]
+                .into_iter()
+                .map(reading)
+                .collect(),
+        );
+
+        let exhausted = Arc::new(AtomicU32::new(0));
+        let counter = exhausted.clone();
+        let mut tracker = mock
+            .hs110()
+            .energy_budget_tracker(1000.0)
+            .on_budget_exhausted(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+
+        let budget = tracker.update().unwrap();
+        assert_eq!(
+            budget,
+            EnergyBudget {
+                budget_wh: 1000.0,
+                consumed_wh: 250.0,
+                remaining_wh: 750.0,
+                pct_consumed: 25.0
+            }
+        );
+        assert!(!tracker.is_budget_exhausted());
+
+        assert_eq!(tracker.update().unwrap().pct_consumed, 50.0);
+        assert!(!tracker.is_budget_exhausted());
+        assert_eq!(exhausted.load(Ordering::SeqCst), 0);
+
+        assert_eq!(tracker.update().unwrap().remaining_wh, 0.0);
+        assert!(tracker.is_budget_exhausted());
+        let budget = tracker.update().unwrap();
+        assert_eq!(budget.remaining_wh, -250.0);
+        assert_eq!(budget.pct_consumed, 125.0);
+        assert_eq!(exhausted.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn counter_reset() {
+        let reading = |total_wh: u32| {
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230000, "current_ma": 500, "power_mw": 115000,
+                "total_wh": total_wh, "err_code": 0
+            }}})
+        };
+        let mock = MockSmartplug::with_responses(
+            [10_000, 10_500, 200, 400, 600]
+                .into_iter()
+                .map(reading)
+                .collect(),
+        );
+
+        let exhausted = Arc::new(AtomicU32::new(0));
+        let counter = exhausted.clone();
+        let mut tracker = mock
+            .hs110()
+            .energy_budget_tracker(1000.0)
+            .on_budget_exhausted(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+
+        assert_eq!(tracker.update().unwrap().consumed_wh, 500.0);
+        // Energy consumed before the reset is kept.
+        assert_eq!(tracker.update().unwrap().consumed_wh, 700.0);
+        assert_eq!(tracker.update().unwrap().consumed_wh, 900.0);
+        assert!(!tracker.is_budget_exhausted());
+
+        let budget = tracker.update().unwrap();
+        assert_eq!(budget.consumed_wh, 1100.0);
+        assert_eq!(budget.remaining_wh, -100.0);
+        assert!(tracker.is_budget_exhausted());
+        assert_eq!(exhausted.load(Ordering::SeqCst), 1);
+    }
+}
@@ -14,8 +14,8 @@ pub enum TpLinkHs110Error {
     /// Mismatch between actual response payload length and payload length specified in the response
     /// header.
     #[error(
-        "encrypted response payload length ({payload_len_actual}) differs from payload length \
-        specified in the header ({payload_len_from_header})"
+        "encrypted response payload length mismatch: got {payload_len_actual} bytes, expected \
+        {payload_len_from_header} bytes"
     )]
     EncryptedPayloadLengthMismatch {
         payload_len_actual: usize,
@@ -38,10 +38,13 @@ pub enum TpLinkHs110Error {
     SerdeJson(#[from] serde_json::Error),
 
     /// Given key is not available in the response.
-    #[error("key {key:?} is not available in the response: {response:#?}")]
+    #[error(
+        "key {key:?} is not available in the response (available keys: {})",
+        available_keys(.response)
+    )]
     KeyIsNotAvailable {
         response: serde_json::Value,
-        key: &'static str,
+        key: String,
     },
 
     /// JSON value represented in unexpected form.
@@ -59,4 +62,633 @@ pub enum TpLinkHs110Error {
     /// Smartplug host address is not provided.
     #[error("smartplug host address is not provided")]
     HostIsNotProvided,
+
+    /// Response checksum doesn't match the checksum calculated over the response.
+    #[error(
+        "response checksum mismatch (reported: {reported:#010x}, calculated: {calculated:#010x})"
+    )]
+    ChecksumMismatch { reported: u32, calculated: u32 },
+
+    /// Remote side doesn't look like a smartplug (e.g. a wrong address is used).
+    #[error("remote device doesn't look like a smartplug")]
+    NotASmartPlug,
+
+    /// Provided parameter value is not acceptable.
+    #[error("invalid parameter: {0}")]
+    InvalidParameter(String),
+
+    /// Energy meter readings haven't stabilized within a given time.
+    #[error("energy meter readings haven't stabilized within {0:?}")]
+    StabilizationTimeout(std::time::Duration),
+
+    /// Smartplug hasn't become reachable within a given time.
+    #[error("smartplug hasn't become reachable within {0:?}")]
+    WaitTimeout(std::time::Duration),
+
+    /// DHCP leases file is malformed.
+    #[error("failed to parse DHCP leases: {0}")]
+    DhcpParseError(String),
+
+    /// Requested time of a scheduled action has already passed.
+    #[error("scheduled time is in the past")]
+    ScheduleTimeInPast,
+
+    /// Smartplug hasn't changed its power relay state as requested.
+    #[error("smartplug power relay state hasn't changed (still {0})")]
+    StateNotChanged(crate::PowerState),
+
+    /// Smartplug power relay state differs from an expected one.
+    #[error("unexpected power relay state: expected {expected}, actual {actual}")]
+    UnexpectedPowerState {
+        expected: crate::PowerState,
+        actual: crate::PowerState,
+    },
+
+    /// Smartplug LED indicator state differs from an expected one.
+    #[error("unexpected LED state: expected {expected}, actual {actual}")]
+    UnexpectedLedState {
+        expected: crate::LedState,
+        actual: crate::LedState,
+    },
+
+    /// Too few samples have been collected to compute a result.
+    #[error("insufficient samples: got {got}, at least {required} required")]
+    InsufficientSamples { got: u32, required: u32 },
+
+    /// Smartplug doesn't support a requested feature (e.g. due to its hardware/firmware version).
+    #[error("feature is not supported by the smartplug")]
+    FeatureNotSupported,
+
+    /// A whole-device energy meter command has been sent to a multi-outlet power strip, whose
+    /// energy meters are per outlet (see [`crate::HS110::child_emeter`]).
+    #[error("smartplug is a power strip, energy meters should be read per outlet (child_emeter)")]
+    PowerStripEmeter,
+
+    /// Some parts of a snapshot haven't been restored (the rest has been applied), errors are
+    /// given by snapshot field names.
+    #[error("failed to restore {}", list_fields(.0))]
+    PartialRestore(Vec<(String, TpLinkHs110Error)>),
+
+    /// Several errors occurred during a batch operation (e.g. over multiple smartplugs).
+    #[error("{} errors occurred: {}", .0.len(), list_errors(.0))]
+    Multiple(Vec<TpLinkHs110Error>),
+}
+
+impl Clone for TpLinkHs110Error {
+    /// Clones an error. Wrapped [`std::io::Error`] and [`serde_json::Error`] aren't cloneable, so
+    /// their clones preserve only an error kind (for IO errors) and a description.
+    fn clone(&self) -> Self {
+        match self {
+            Self::ShortEncryptedResponse(len) => Self::ShortEncryptedResponse(*len),
+            Self::IO(err) => Self::IO(std::io::Error::new(err.kind(), err.to_string())),
+            Self::EncryptedPayloadLengthMismatch {
+                payload_len_actual,
+                payload_len_from_header,
+            } => Self::EncryptedPayloadLengthMismatch {
+                payload_len_actual: *payload_len_actual,
+                payload_len_from_header: *payload_len_from_header,
+            },
+            Self::TryFromSliceError(err) => Self::TryFromSliceError(*err),
+            Self::AddrParse(err) => Self::AddrParse(err.clone()),
+            Self::SerdeJson(err) => Self::SerdeJson(serde::de::Error::custom(err)),
+            Self::KeyIsNotAvailable { response, key } => Self::KeyIsNotAvailable {
+                response: response.clone(),
+                key: key.clone(),
+            },
+            Self::UnexpectedValueRepresentation => Self::UnexpectedValueRepresentation,
+            Self::SmartplugErrCode(err_code) => Self::SmartplugErrCode(*err_code),
+            Self::PortIsNotProvided => Self::PortIsNotProvided,
+            Self::HostIsNotProvided => Self::HostIsNotProvided,
+            Self::ChecksumMismatch {
+                reported,
+                calculated,
+            } => Self::ChecksumMismatch {
+                reported: *reported,
+                calculated: *calculated,
+            },
+            Self::NotASmartPlug => Self::NotASmartPlug,
+            Self::InvalidParameter(message) => Self::InvalidParameter(message.clone()),
+            Self::StabilizationTimeout(timeout) => Self::StabilizationTimeout(*timeout),
+            Self::WaitTimeout(timeout) => Self::WaitTimeout(*timeout),
+            Self::DhcpParseError(message) => Self::DhcpParseError(message.clone()),
+            Self::ScheduleTimeInPast => Self::ScheduleTimeInPast,
+            Self::StateNotChanged(state) => Self::StateNotChanged(*state),
+            Self::UnexpectedPowerState { expected, actual } => Self::UnexpectedPowerState {
+                expected: *expected,
+                actual: *actual,
+            },
+            Self::UnexpectedLedState { expected, actual } => Self::UnexpectedLedState {
+                expected: *expected,
+                actual: *actual,
+            },
+            Self::InsufficientSamples { got, required } => Self::InsufficientSamples {
+                got: *got,
+                required: *required,
+            },
+            Self::FeatureNotSupported => Self::FeatureNotSupported,
+            Self::PowerStripEmeter => Self::PowerStripEmeter,
+            Self::PartialRestore(errors) => Self::PartialRestore(errors.clone()),
+            Self::Multiple(errors) => Self::Multiple(errors.clone()),
+        }
+    }
+}
+
+impl TpLinkHs110Error {
+    /// Whether an error is caused by a (likely temporary) network issue, e.g. a connection
+    /// dropped in the middle of a response, i.e. whether it makes sense to retry an operation.
+    /// Errors reported by a smartplug, invalid parameters and unexpected responses aren't
+    /// transient. [`TpLinkHs110Error::Multiple`] and [`TpLinkHs110Error::PartialRestore`] are
+    /// transient only if all of their sub-errors are.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::IO(_)
+            | Self::ShortEncryptedResponse(_)
+            | Self::EncryptedPayloadLengthMismatch { .. }
+            | Self::StabilizationTimeout(_)
+            | Self::WaitTimeout(_) => true,
+            Self::TryFromSliceError(_)
+            | Self::AddrParse(_)
+            | Self::SerdeJson(_)
+            | Self::KeyIsNotAvailable { .. }
+            | Self::UnexpectedValueRepresentation
+            | Self::SmartplugErrCode(_)
+            | Self::PortIsNotProvided
+            | Self::HostIsNotProvided
+            | Self::ChecksumMismatch { .. }
+            | Self::NotASmartPlug
+            | Self::InvalidParameter(_)
+            | Self::DhcpParseError(_)
+            | Self::ScheduleTimeInPast
+            | Self::StateNotChanged(_)
+            | Self::UnexpectedPowerState { .. }
+            | Self::UnexpectedLedState { .. }
+            | Self::InsufficientSamples { .. }
+            | Self::FeatureNotSupported
+            | Self::PowerStripEmeter => false,
+            Self::Multiple(errors) => errors.iter().all(Self::is_transient),
+            Self::PartialRestore(errors) => errors.iter().all(|(_, error)| error.is_transient()),
+        }
+    }
+
+    /// Whether a smartplug has reported an error ([`TpLinkHs110Error::SmartplugErrCode`]).
+    pub fn is_device_error(&self) -> bool {
+        matches!(self, Self::SmartplugErrCode(_))
+    }
+
+    /// Whether an error is caused by a failure to parse something (a response, an address,
+    /// a DHCP leases file, etc.).
+    pub fn is_parse_error(&self) -> bool {
+        matches!(
+            self,
+            Self::SerdeJson(_)
+                | Self::UnexpectedValueRepresentation
+                | Self::TryFromSliceError(_)
+                | Self::AddrParse(_)
+                | Self::DhcpParseError(_)
+        )
+    }
+
+    /// Iterates over sub-errors of [`TpLinkHs110Error::Multiple`], any other error yields itself.
+    pub fn iter(&self) -> std::slice::Iter<'_, TpLinkHs110Error> {
+        match self {
+            Self::Multiple(errors) => errors.iter(),
+            error => std::slice::from_ref(error).iter(),
+        }
+    }
+
+    /// Collects results of a batch operation: returns all the values if every operation has
+    /// succeeded, otherwise returns all the errors as [`TpLinkHs110Error::Multiple`].
+    pub fn collect_all<T, I>(results: I) -> Result<Vec<T>, Self>
+    where
+        I: IntoIterator<Item = Result<T, Self>>,
+    {
+        let (values, errors) =
+            results
+                .into_iter()
+                .fold((vec![], vec![]), |(mut values, mut errors), result| {
+                    match result {
+                        Ok(value) => values.push(value),
+                        Err(error) => errors.push(error),
+                    }
+                    (values, errors)
+                });
+
+        if errors.is_empty() {
+            Ok(values)
+        } else {
+            Err(Self::Multiple(errors))
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a TpLinkHs110Error {
+    type Item = &'a TpLinkHs110Error;
+    type IntoIter = std::slice::Iter<'a, TpLinkHs110Error>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Lists errors on a single line, e.g. `1) first error; 2) second error`.
+fn list_errors(errors: &[TpLinkHs110Error]) -> String {
+    errors
+        .iter()
+        .enumerate()
+        .map(|(index, error)| format!("{}) {error}", index + 1))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Lists errors of snapshot fields on a single line, e.g. `alias (first error), location (second
+/// error)`.
+fn list_fields(errors: &[(String, TpLinkHs110Error)]) -> String {
+    errors
+        .iter()
+        .map(|(field, error)| format!("{field} ({error})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Lists top-level keys of a JSON object (to keep error messages short instead of dumping
+/// a whole response).
+fn available_keys(response: &serde_json::Value) -> String {
+    match response.as_object() {
+        Some(object) if !object.is_empty() => object
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", "),
+        Some(_) => "none".to_string(),
+        None => "not an object".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn display() {
+        for (error, expected) in [
+            (
+                TpLinkHs110Error::ShortEncryptedResponse(3),
+                "encrypted response is too short (length: 3)",
+            ),
+            (
+                std::io::Error::other("connection reset").into(),
+                "IO: connection reset",
+            ),
+            (
+                TpLinkHs110Error::EncryptedPayloadLengthMismatch {
+                    payload_len_actual: 10,
+                    payload_len_from_header: 20,
+                },
+                "encrypted response payload length mismatch: got 10 bytes, expected 20 bytes",
+            ),
+            (
+                <[u8; 4]>::try_from(&[0u8][..]).unwrap_err().into(),
+                "failed to construct array from slice: could not convert slice to array",
+            ),
+            (
+                "::g".parse::<std::net::IpAddr>().unwrap_err().into(),
+                "failed to parse an IP address: invalid IP address syntax",
+            ),
+            (
+                serde_json::from_str::<serde_json::Value>("{")
+                    .unwrap_err()
+                    .into(),
+                "serde json: EOF while parsing an object at line 1 column 1",
+            ),
+            (
+                TpLinkHs110Error::KeyIsNotAvailable {
+                    response: json!({"system": {"get_sysinfo": {}}, "emeter": {}}),
+                    key: "cnCloud".to_string(),
+                },
+                r#"key "cnCloud" is not available in the response (available keys: emeter, system)"#,
+            ),
+            (
+                TpLinkHs110Error::KeyIsNotAvailable {
+                    response: json!({}),
+                    key: "alias".to_string(),
+                },
+                r#"key "alias" is not available in the response (available keys: none)"#,
+            ),
+            (
+                TpLinkHs110Error::KeyIsNotAvailable {
+                    response: json!([1, 2]),
+                    key: "alias".to_string(),
+                },
+                r#"key "alias" is not available in the response (available keys: not an object)"#,
+            ),
+            (
+                TpLinkHs110Error::UnexpectedValueRepresentation,
+                "JSON value represented in unexpected form",
+            ),
+            (
+                TpLinkHs110Error::SmartplugErrCode(-1),
+                "smartplug reported the command has failed (err_code = -1)",
+            ),
+            (
+                TpLinkHs110Error::PortIsNotProvided,
+                "smartplug network port is not provided",
+            ),
+            (
+                TpLinkHs110Error::HostIsNotProvided,
+                "smartplug host address is not provided",
+            ),
+            (
+                TpLinkHs110Error::ChecksumMismatch {
+                    reported: 0xdead,
+                    calculated: 0xbeef,
+                },
+                "response checksum mismatch (reported: 0x0000dead, calculated: 0x0000beef)",
+            ),
+            (
+                TpLinkHs110Error::NotASmartPlug,
+                "remote device doesn't look like a smartplug",
+            ),
+            (
+                TpLinkHs110Error::InvalidParameter("delay is too long".to_string()),
+                "invalid parameter: delay is too long",
+            ),
+            (
+                TpLinkHs110Error::StabilizationTimeout(Duration::from_secs(5)),
+                "energy meter readings haven't stabilized within 5s",
+            ),
+            (
+                TpLinkHs110Error::WaitTimeout(Duration::from_millis(1500)),
+                "smartplug hasn't become reachable within 1.5s",
+            ),
+            (
+                TpLinkHs110Error::DhcpParseError("line 2: missing IP address".to_string()),
+                "failed to parse DHCP leases: line 2: missing IP address",
+            ),
+            (
+                TpLinkHs110Error::ScheduleTimeInPast,
+                "scheduled time is in the past",
+            ),
+            (
+                TpLinkHs110Error::StateNotChanged(crate::PowerState::Off),
+                "smartplug power relay state hasn't changed (still OFF)",
+            ),
+            (
+                TpLinkHs110Error::UnexpectedPowerState {
+                    expected: crate::PowerState::On,
+                    actual: crate::PowerState::Off,
+                },
+                "unexpected power relay state: expected ON, actual OFF",
+            ),
+            (
+                TpLinkHs110Error::UnexpectedLedState {
+                    expected: crate::LedState::Off,
+                    actual: crate::LedState::On,
+                },
+                "unexpected LED state: expected OFF, actual ON",
+            ),
+            (
+                TpLinkHs110Error::InsufficientSamples {
+                    got: 2,
+                    required: 3,
+                },
+                "insufficient samples: got 2, at least 3 required",
+            ),
+            (
+                TpLinkHs110Error::FeatureNotSupported,
+                "feature is not supported by the smartplug",
+            ),
+            (
+                TpLinkHs110Error::PowerStripEmeter,
+                "smartplug is a power strip, energy meters should be read per outlet \
+                (child_emeter)",
+            ),
+            (
+                TpLinkHs110Error::PartialRestore(vec![
+                    ("alias".to_string(), TpLinkHs110Error::SmartplugErrCode(-3)),
+                    (
+                        "location".to_string(),
+                        TpLinkHs110Error::FeatureNotSupported,
+                    ),
+                ]),
+                "failed to restore alias (smartplug reported the command has failed \
+                (err_code = -3)), location (feature is not supported by the smartplug)",
+            ),
+            (
+                TpLinkHs110Error::Multiple(vec![
+                    TpLinkHs110Error::NotASmartPlug,
+                    TpLinkHs110Error::SmartplugErrCode(-3),
+                ]),
+                "2 errors occurred: 1) remote device doesn't look like a smartplug; \
+                2) smartplug reported the command has failed (err_code = -3)",
+            ),
+        ] {
+            assert_eq!(error.to_string(), expected);
+            assert!(!error.to_string().contains('\n'));
+        }
+    }
+
+    #[test]
+    fn multiple() {
+        assert_eq!(
+            TpLinkHs110Error::collect_all(vec![Ok(1), Ok(2)]).unwrap(),
+            [1, 2]
+        );
+
+        let error = TpLinkHs110Error::collect_all(vec![
+            Ok(1),
+            Err(TpLinkHs110Error::NotASmartPlug),
+            Err(TpLinkHs110Error::SmartplugErrCode(-1)),
+        ])
+        .unwrap_err();
+        assert!(matches!(
+            error.iter().collect::<Vec<_>>()[..],
+            [
+                TpLinkHs110Error::NotASmartPlug,
+                TpLinkHs110Error::SmartplugErrCode(-1)
+            ]
+        ));
+
+        assert_eq!(TpLinkHs110Error::NotASmartPlug.iter().count(), 1);
+    }
+
+    #[test]
+    fn clone() {
+        let error: TpLinkHs110Error =
+            std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out").into();
+        assert!(matches!(
+            error.clone(),
+            TpLinkHs110Error::IO(err) if err.kind() == std::io::ErrorKind::TimedOut
+        ));
+        assert_eq!(error.clone().to_string(), error.to_string());
+
+        let error: TpLinkHs110Error = serde_json::from_str::<serde_json::Value>("{")
+            .unwrap_err()
+            .into();
+        assert_eq!(error.clone().to_string(), error.to_string());
+    }
+
+    #[test]
+    fn classification() {
+        let io: TpLinkHs110Error =
+            std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused").into();
+        let json: TpLinkHs110Error = serde_json::from_str::<serde_json::Value>("{")
+            .unwrap_err()
+            .into();
+        // (error, is_transient, is_device_error, is_parse_error)
+        for (error, transient, device, parse) in [
+            (
+                TpLinkHs110Error::ShortEncryptedResponse(0),
+                true,
+                false,
+                false,
+            ),
+            (io.clone(), true, false, false),
+            (
+                TpLinkHs110Error::EncryptedPayloadLengthMismatch {
+                    payload_len_actual: 10,
+                    payload_len_from_header: 20,
+                },
+                true,
+                false,
+                false,
+            ),
+            (
+                <[u8; 4]>::try_from(&[0u8][..]).unwrap_err().into(),
+                false,
+                false,
+                true,
+            ),
+            (
+                "::g".parse::<std::net::IpAddr>().unwrap_err().into(),
+                false,
+                false,
+                true,
+            ),
+            (json.clone(), false, false, true),
+            (
+                TpLinkHs110Error::KeyIsNotAvailable {
+                    response: json!({}),
+                    key: "alias".to_string(),
+                },
+                false,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::UnexpectedValueRepresentation,
+                false,
+                false,
+                true,
+            ),
+            (TpLinkHs110Error::SmartplugErrCode(-1), false, true, false),
+            (TpLinkHs110Error::PortIsNotProvided, false, false, false),
+            (TpLinkHs110Error::HostIsNotProvided, false, false, false),
+            (
+                TpLinkHs110Error::ChecksumMismatch {
+                    reported: 1,
+                    calculated: 2,
+                },
+                false,
+                false,
+                false,
+            ),
+            (TpLinkHs110Error::NotASmartPlug, false, false, false),
+            (
+                TpLinkHs110Error::InvalidParameter(String::new()),
+                false,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::StabilizationTimeout(Duration::from_secs(5)),
+                true,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::WaitTimeout(Duration::from_secs(5)),
+                true,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::DhcpParseError(String::new()),
+                false,
+                false,
+                true,
+            ),
+            (TpLinkHs110Error::ScheduleTimeInPast, false, false, false),
+            (
+                TpLinkHs110Error::StateNotChanged(crate::PowerState::On),
+                false,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::UnexpectedPowerState {
+                    expected: crate::PowerState::On,
+                    actual: crate::PowerState::Off,
+                },
+                false,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::UnexpectedLedState {
+                    expected: crate::LedState::On,
+                    actual: crate::LedState::Off,
+                },
+                false,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::InsufficientSamples {
+                    got: 0,
+                    required: 3,
+                },
+                false,
+                false,
+                false,
+            ),
+            (TpLinkHs110Error::FeatureNotSupported, false, false, false),
+            (TpLinkHs110Error::PowerStripEmeter, false, false, false),
+            (
+                TpLinkHs110Error::Multiple(vec![
+                    io.clone(),
+                    TpLinkHs110Error::WaitTimeout(Duration::from_secs(1)),
+                ]),
+                true,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::Multiple(vec![io.clone(), json.clone()]),
+                false,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::PartialRestore(vec![("alias".to_string(), io.clone())]),
+                true,
+                false,
+                false,
+            ),
+            (
+                TpLinkHs110Error::PartialRestore(vec![
+                    ("alias".to_string(), io),
+                    ("schedule_rules".to_string(), json),
+                ]),
+                false,
+                false,
+                false,
+            ),
+        ] {
+            assert_eq!(error.is_transient(), transient, "{error}");
+            assert_eq!(error.is_device_error(), device, "{error}");
+            assert_eq!(error.is_parse_error(), parse, "{error}");
+        }
+    }
 }
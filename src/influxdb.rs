@@ -0,0 +1,132 @@
+//! Representing smartplug readings in InfluxDB line protocol (requires `influxdb` feature).
+use crate::{error::TpLinkHs110Error, ExtractHierarchical, HS110};
+use std::{
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+impl HS110 {
+    /// Attempts to read the energy meter and system information and represent them as a line of
+    /// InfluxDB line protocol. Given `tags` along with the smartplug alias are used as tags, the
+    /// current time is used as a timestamp.
+    ///
+    /// Resulting line looks similar to this:
+    /// ```text
+    /// plug,location=cabinet,alias=Bathroom voltage_v=228.603726,current_a=0.027824,power_w=0.770242,total_kwh=625.833,relay_state=1i,rssi=-64i 1713968042123456789
+    /// ```
+    pub fn influxdb_line_protocol(
+        &self,
+        measurement: &str,
+        tags: &[(&str, &str)],
+    ) -> Result<String, TpLinkHs110Error> {
+        let emeter = self.emeter()?;
+        let emeter_field = |field| {
+            emeter
+                .extract_hierarchical(&[field])?
+                .as_f64()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+        };
+        let sysinfo = self
+            .info()?
+            .extract_hierarchical(&["system", "get_sysinfo"])?;
+        let sysinfo_field = |field| {
+            sysinfo
+                .extract_hierarchical(&[field])?
+                .as_i64()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+        };
+        let alias = sysinfo
+            .extract_hierarchical(&["alias"])?
+            .as_str()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            .to_string();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        let mut tags_text = String::new();
+        for (key, value) in tags.iter().copied().chain([("alias", alias.as_str())]) {
+            let _ = write!(
+                tags_text,
+                ",{}={}",
+                escape(key, ",= "),
+                escape(value, ",= ")
+            );
+        }
+
+        Ok(format!(
+            "{}{tags_text} voltage_v={},current_a={},power_w={},total_kwh={},relay_state={}i,rssi={}i {timestamp}",
+            escape(measurement, ", "),
+            emeter_field("voltage")?,
+            emeter_field("current")?,
+            emeter_field("power")?,
+            emeter_field("total")?,
+            sysinfo_field("relay_state")?,
+            sysinfo_field("rssi")?,
+        ))
+    }
+}
+
+/// Escapes given special characters with a backslash according to InfluxDB line protocol.
+fn escape(value: &str, special: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| {
+            if special.contains(c) {
+                vec!['\\', c]
+            } else {
+                vec![c]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::MockSmartplug;
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn line_protocol() {
+        let mock = MockSmartplug::start(|request| match request.get("emeter") {
+            Some(_) => json!({"emeter": {"get_realtime": {
+                "current": 0.5, "power": 115.25, "total": 12.5, "voltage": 230.5, "err_code": 0
+            }}}),
+            None => json!({"system": {"get_sysinfo": {
+                "alias": "Living room", "relay_state": 1, "rssi": -64, "err_code": 0
+            }}}),
+        });
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let line = mock
+            .hs110()
+            .influxdb_line_protocol("plug", &[("location", "cabinet,2")])
+            .unwrap();
+        let after = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+
+        // Series part may contain escaped spaces, while fields and timestamp may not:
+        let (rest, timestamp) = line.rsplit_once(' ').unwrap();
+        let (series, fields) = rest.rsplit_once(' ').unwrap();
+        assert_eq!(series, r"plug,location=cabinet\,2,alias=Living\ room");
+        assert_eq!(
+            fields.split(',').collect::<Vec<_>>(),
+            [
+                "voltage_v=230.5",
+                "current_a=0.5",
+                "power_w=115.25",
+                "total_kwh=12.5",
+                "relay_state=1i",
+                "rssi=-64i",
+            ]
+        );
+        assert!((before..=after).contains(&timestamp.parse::<u128>().unwrap()));
+    }
+}
@@ -1,49 +1,515 @@
 //! A library to control TP-Link HS110 (and HS100) SmartPlugs over Wi-Fi.
 use error::TpLinkHs110Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{json, Value};
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
 use std::{
+    collections::{hash_map::RandomState, HashMap, VecDeque},
+    convert::Infallible,
     fmt::Display,
+    fs,
+    hash::{BuildHasher, Hash, Hasher},
     io::{Read, Write},
-    mem::size_of,
-    net::{self, SocketAddr},
+    net::{self, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     ops::Not,
-    time::Duration,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+pub mod energy_budget;
 pub mod error;
+#[cfg(feature = "influxdb")]
+pub mod influxdb;
+#[cfg(test)]
+mod mock;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod power_log;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+pub mod protocol;
+pub mod registry;
+#[cfg(feature = "chrono")]
+pub mod timezone;
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
 
 const NET_BUFFER_SIZE: usize = 8192;
 
+/// Acceptable difference between a smartplug clock and UTC shifted by an offset of a smartplug
+/// time zone, see [`ConnectivityTest::ntp_ok`].
+pub const NTP_SYNC_TOLERANCE: Duration = Duration::from_secs(60);
+
+/// Number of the last loopback test durations kept, see [`HS110::perform_loopback_test`].
+pub const LOOPBACK_HISTORY_LEN: usize = 10;
+
+/// Known `(get, set)` command names of overload protection (they vary between firmware versions),
+/// see [`HS110::set_auto_off_on_overload`].
+const OVERLOAD_PROTECTION_COMMANDS: [(&str, &str); 2] = [
+    ("get_over_limit", "set_over_limit"),
+    ("get_overload_protection", "set_overload_protection"),
+];
+
+/// Longest smartplug name (in bytes) accepted by firmware, see [`HS110::set_alias`].
+pub const MAX_NAME_LEN: usize = 31;
+
+/// Longest device type name (in bytes) accepted by firmware, see [`HS110::set_dev_name`]. It is
+/// longer than [`MAX_NAME_LEN`], as factory names like "Wi-Fi Smart Plug With Energy Monitoring"
+/// are.
+pub const MAX_DEV_NAME_LEN: usize = 63;
+
+/// Highest LED brightness (in percent).
+const MAX_LED_BRIGHTNESS: u8 = 100;
+
+/// Error code reported by smartplugs for unsupported modules ("module not support").
+const MODULE_NOT_SUPPORTED: i64 = -1;
+
+/// Error code reported by smartplugs for unsupported commands/modules ("member not support").
+const MEMBER_NOT_SUPPORTED: i64 = -2;
+
+/// Error code reported by smartplugs for unacceptable command parameters ("invalid argument").
+const INVALID_ARGUMENT: i64 = -3;
+
+/// `set_relay_state` state value which makes some firmware versions invert the relay state.
+const ATOMIC_TOGGLE_STATE: u8 = 2;
+
+/// `enable_type` of schedule rules switching power relay.
+const RELAY_RULE_TYPE: u64 = 0;
+
+/// `enable_type` of schedule rules switching LED indicator.
+const LED_RULE_TYPE: u64 = 1;
+
+/// Interval between attempts to reach a smartplug in [`HS110::wait_for_reachable`].
+const REACHABILITY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Minimal interval (in seconds) between cloud server polls.
+const MIN_CLOUD_POLL_INTERVAL: u32 = 60;
+
+/// Port smartplugs listen on.
+const DEFAULT_PORT: u16 = 9999;
+
+/// Longest delay which [`HS110::schedule_state_change`] schedules with a countdown rule.
+pub const COUNTDOWN_RULE_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Default interval between energy meter readings in [`HS110::measure_power_stability`], see
+/// [`HS110::with_power_sampling_interval`].
+pub const POWER_SAMPLING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default power stability threshold (coefficient of variation in percents), see
+/// [`HS110::with_stability_threshold`].
+pub const DEFAULT_STABILITY_THRESHOLD_PCT: f64 = 5.0;
+
+/// Number of energy meter readings power stability is checked over in
+/// [`HS110::wait_for_stable_power`].
+const STABILITY_WAIT_SAMPLES: u32 = 4;
+
+/// Version of a format produced by [`HS110::export_json`].
+const EXPORT_VERSION: u64 = 1;
+
 /// HS110 smartplug.
-#[derive(Debug)]
+///
+/// Two instances are equal (and hash equally) when they point to the same network address,
+/// regardless of their timeouts and other settings. Instances are safe to use as `HashMap` keys
+/// despite `clippy::mutable_key_type` warning (caused by a shared [`HS110::last_error`]).
+#[derive(Clone)]
 pub struct HS110 {
     /// Smartplug network address.
     socket_addr: SocketAddr,
 
     /// Optional timeout for network communication.
     timeout: Option<Duration>,
+
+    /// Optional timeout for establishing a connection (`timeout` is used if not set).
+    connect_timeout: Option<Duration>,
+
+    /// Whether to verify response checksums.
+    verify: bool,
+
+    /// Optional local address to connect from.
+    bind_addr: Option<SocketAddr>,
+
+    /// Optional inclusive range of local ports to connect from.
+    source_port_range: Option<(u16, u16)>,
+
+    /// The most recent communication error (shared between clones).
+    last_error: Arc<Mutex<Option<TpLinkHs110Error>>>,
+
+    /// Optional minimal interval between requests, see [`HS110::rate_limited`].
+    min_request_interval: Option<Duration>,
+
+    /// Time of the most recent request (shared between clones).
+    last_request: Arc<Mutex<Option<Instant>>>,
+
+    /// Whether Nagle's algorithm is disabled (`TCP_NODELAY`).
+    tcp_nodelay: bool,
+
+    /// Optional TCP keepalive idle time.
+    tcp_keepalive: Option<Duration>,
+
+    /// Power stability threshold in percents, see [`HS110::with_stability_threshold`].
+    stability_threshold_pct: f64,
+
+    /// Interval between energy meter readings, see [`HS110::with_power_sampling_interval`].
+    power_sampling_interval: Duration,
+
+    /// Pre-established connection, see [`HS110::connect_only`] (shared between clones).
+    stream: Arc<Mutex<Option<net::TcpStream>>>,
+
+    /// Durations of the last loopback tests, see [`HS110::perform_loopback_test`] (shared between
+    /// clones).
+    loopback_latencies: Arc<Mutex<VecDeque<Duration>>>,
+
+    /// Whether a smartplug supports atomic toggling (unknown until tried), see
+    /// [`HS110::atomic_toggle`] (shared between clones).
+    atomic_toggle_supported: Arc<Mutex<Option<bool>>>,
 }
 
 impl HS110 {
-    /// Attempts to create a new HS110 instance using given network address.
+    /// Attempts to create a new HS110 instance using given network address. Default port (9999)
+    /// is used if the address doesn't specify a port. IPv6 addresses could be given either as is
+    /// (e.g. `::1`) or in brackets (e.g. `[::1]` or `[::1]:9999`).
     pub fn new(addr: &str) -> Result<Self, TpLinkHs110Error> {
-        let socket_addr = match addr.find(':') {
-            Some(_) => addr.parse(),
-            None => (addr.to_string() + ":9999").parse(),
-        }?;
+        let socket_addr = match addr.parse::<SocketAddr>() {
+            Ok(socket_addr) => socket_addr,
+            Err(_) => SocketAddr::new(
+                addr.strip_prefix('[')
+                    .and_then(|addr| addr.strip_suffix(']'))
+                    .unwrap_or(addr)
+                    .parse::<IpAddr>()?,
+                DEFAULT_PORT,
+            ),
+        };
 
         Ok(Self {
             socket_addr,
             timeout: None,
+            connect_timeout: None,
+            verify: false,
+            bind_addr: None,
+            source_port_range: None,
+            last_error: Arc::default(),
+            min_request_interval: None,
+            last_request: Arc::default(),
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+            stability_threshold_pct: DEFAULT_STABILITY_THRESHOLD_PCT,
+            power_sampling_interval: POWER_SAMPLING_INTERVAL,
+            stream: Arc::default(),
+            loopback_latencies: Arc::default(),
+            atomic_toggle_supported: Arc::default(),
+        })
+    }
+
+    /// Attempts to create a new HS110 instance from a given configuration (see [`HS110::new`] for
+    /// supported address formats).
+    pub fn from_config(config: &HS110Config) -> Result<Self, TpLinkHs110Error> {
+        let smartplug = Self::new(&config.addr)?;
+
+        Ok(match config.timeout_secs {
+            Some(timeout_secs) => smartplug.with_timeout(Duration::from_secs(timeout_secs)),
+            None => smartplug,
+        })
+    }
+
+    /// Attempts to discover smartplugs in a local network: a system information request is
+    /// broadcast over UDP and responses are collected until `timeout` expires. Devices which
+    /// don't look like smartplugs are skipped.
+    pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredDevice>, TpLinkHs110Error> {
+        Self::discover_at(
+            SocketAddr::new(Ipv4Addr::BROADCAST.into(), DEFAULT_PORT),
+            timeout,
+        )
+    }
+
+    /// Same as [`HS110::discover`], but the request is sent to a given (e.g. subnet broadcast)
+    /// address.
+    fn discover_at(
+        target: SocketAddr,
+        timeout: Duration,
+    ) -> Result<Vec<DiscoveredDevice>, TpLinkHs110Error> {
+        let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))?;
+        socket.set_broadcast(true)?;
+        socket.send_to(
+            &protocol::encrypt_datagram(json!({"system": {"get_sysinfo": {}}}).to_string()),
+            target,
+        )?;
+
+        let deadline = Instant::now() + timeout;
+        let mut devices = Vec::<DiscoveredDevice>::new();
+        let mut rx_buf = [0u8; NET_BUFFER_SIZE];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(remaining))?;
+
+            let (nread, source) = match socket.recv_from(&mut rx_buf) {
+                Ok(received) => received,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break
+                }
+                Err(err) => Err(err)?,
+            };
+            let Some(device) =
+                serde_json::from_str::<Value>(&protocol::decrypt_datagram(&rx_buf[..nread]))
+                    .ok()
+                    .and_then(|response| DiscoveredDevice::from_sysinfo(source.ip(), &response))
+            else {
+                log::debug!("skipping a non-smartplug response from {source}");
+                continue;
+            };
+            if devices.iter().all(|known| known.addr != device.addr) {
+                devices.push(device);
+            }
+        }
+
+        Ok(devices)
+    }
+
+    /// Attempts to create HS110 instances for smartplugs listed in a dnsmasq DHCP leases file
+    /// (which consists of `timestamp mac ip hostname clientid` lines). Only entries with a
+    /// hostname containing `hs110` or `hs100` (case-insensitive) are taken, see
+    /// [`HS110::from_dhcp_lease_file_with_prefix`] to use another filter.
+    pub fn from_dhcp_lease_file(path: &Path) -> Result<Vec<Self>, TpLinkHs110Error> {
+        Self::from_dhcp_leases(&fs::read_to_string(path)?, |hostname| {
+            let hostname = hostname.to_lowercase();
+            hostname.contains("hs110") || hostname.contains("hs100")
+        })
+    }
+
+    /// Same as [`HS110::from_dhcp_lease_file`], but takes entries with a hostname starting with
+    /// `hostname_prefix` (case-insensitive).
+    pub fn from_dhcp_lease_file_with_prefix(
+        path: &Path,
+        hostname_prefix: &str,
+    ) -> Result<Vec<Self>, TpLinkHs110Error> {
+        let hostname_prefix = hostname_prefix.to_lowercase();
+        Self::from_dhcp_leases(&fs::read_to_string(path)?, |hostname| {
+            hostname.to_lowercase().starts_with(&hostname_prefix)
         })
     }
 
+    /// Creates HS110 instances for entries of dnsmasq DHCP leases which hostname `matches`.
+    fn from_dhcp_leases<F>(leases: &str, matches: F) -> Result<Vec<Self>, TpLinkHs110Error>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut smartplugs = vec![];
+        for (index, line) in leases.lines().enumerate() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            // Empty lines and a server DUID line (in case of DHCPv6) aren't leases.
+            if matches!(fields[..], [] | ["duid", ..]) {
+                continue;
+            }
+
+            let parse_error = |reason: String| {
+                TpLinkHs110Error::DhcpParseError(format!("line {}: {reason}", index + 1))
+            };
+            let [timestamp, _, ip, hostname, ..] = fields[..] else {
+                Err(parse_error(format!(
+                    "expected at least 4 fields, got {}",
+                    fields.len()
+                )))?
+            };
+            timestamp
+                .parse::<u64>()
+                .map_err(|_| parse_error(format!("invalid timestamp {timestamp:?}")))?;
+            let ip = ip
+                .parse::<IpAddr>()
+                .map_err(|_| parse_error(format!("invalid IP address {ip:?}")))?;
+
+            if matches(hostname) {
+                smartplugs.push(Self::new(&SocketAddr::new(ip, DEFAULT_PORT).to_string())?);
+            }
+        }
+
+        Ok(smartplugs)
+    }
+
     /// Sets a timeout for network communication with a smartplug.
+    #[must_use]
     pub fn with_timeout(mut self, duration: Duration) -> Self {
         self.timeout = Some(duration);
         self
     }
 
+    /// Sets a separate timeout for establishing a connection with a smartplug. If not set, the
+    /// timeout specified with [`HS110::with_timeout`] is used.
+    #[must_use]
+    pub fn with_connect_timeout(mut self, duration: Duration) -> Self {
+        self.connect_timeout = Some(duration);
+        self
+    }
+
+    /// Enables or disables verification of response checksums (disabled by default).
+    ///
+    /// Smartplug firmware known so far doesn't include checksums into responses, in which case a
+    /// warning is logged and a response is accepted. If a response contains a `checksum` field,
+    /// it is expected to be a CRC-32 of the response JSON (without the `checksum` field) in a
+    /// compact form with keys sorted.
+    #[must_use]
+    pub fn with_response_verification(mut self, enabled: bool) -> Self {
+        self.verify = enabled;
+        self
+    }
+
+    /// Enables or disables `TCP_NODELAY` socket option, i.e. disables or enables Nagle's
+    /// algorithm which may delay small packets by tens of milliseconds (enabled by default, so
+    /// Nagle's algorithm is off).
+    #[must_use]
+    pub fn with_tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Enables TCP keepalive probes which are sent once a connection is idle for a given
+    /// `interval` (disabled by default). Useful only for long-lived connections, e.g. slow
+    /// requests over unreliable networks.
+    #[must_use]
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Returns the most recent error which occurred while communicating with a smartplug (errors
+    /// reported by a smartplug itself are not tracked). Useful for long-running scripts which
+    /// keep going on errors and need a summary later. Clones of an instance share the last error.
+    pub fn last_error(&self) -> Option<TpLinkHs110Error> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Forgets the most recent error, see [`HS110::last_error`].
+    pub fn clear_last_error(&self) {
+        *self
+            .last_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Sets a local address to connect to a smartplug from, i.e. a network interface to be used
+    /// on a multi-homed host (port `0` picks an arbitrary port).
+    #[must_use]
+    pub fn with_local_bind_addr(mut self, addr: SocketAddr) -> Self {
+        self.bind_addr = Some(addr);
+        self
+    }
+
+    /// Sets an inclusive range of local ports to connect to a smartplug from (e.g. for strict
+    /// firewalls or port forwarding), a random free port of the range is picked for each
+    /// connection. Could be combined with [`HS110::with_local_bind_addr`], whose port is ignored
+    /// then. Ports of recently closed connections stay busy for a while (`TIME_WAIT`), so
+    /// a range should be wide enough for a request rate.
+    ///
+    /// Returns [`TpLinkHs110Error::InvalidParameter`] if `start` is greater than `end` or either
+    /// of them is `0`.
+    pub fn with_source_port_range(
+        mut self,
+        start: u16,
+        end: u16,
+    ) -> Result<Self, TpLinkHs110Error> {
+        if start == 0 || end == 0 || start > end {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "invalid source port range {start}-{end}"
+            )))?;
+        }
+        self.source_port_range = Some((start, end));
+
+        Ok(self)
+    }
+
+    /// Sets a threshold of a coefficient of variation of power readings (in percents) below which
+    /// power draw is considered stable by [`HS110::is_power_stable`] (defaults to
+    /// [`DEFAULT_STABILITY_THRESHOLD_PCT`]).
+    ///
+    /// Returns [`TpLinkHs110Error::InvalidParameter`] if `pct` isn't a non-negative finite number.
+    pub fn with_stability_threshold(mut self, pct: f64) -> Result<Self, TpLinkHs110Error> {
+        if !(pct.is_finite() && pct >= 0.0) {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "stability threshold should be a non-negative finite number, got {pct}"
+            )))?;
+        }
+        self.stability_threshold_pct = pct;
+
+        Ok(self)
+    }
+
+    /// Sets an interval between energy meter readings taken by
+    /// [`HS110::measure_power_stability`] (defaults to [`POWER_SAMPLING_INTERVAL`]).
+    ///
+    /// Returns [`TpLinkHs110Error::InvalidParameter`] if `interval` is zero.
+    pub fn with_power_sampling_interval(
+        mut self,
+        interval: Duration,
+    ) -> Result<Self, TpLinkHs110Error> {
+        if interval.is_zero() {
+            Err(TpLinkHs110Error::InvalidParameter(
+                "power sampling interval should be positive".to_string(),
+            ))?;
+        }
+        self.power_sampling_interval = interval;
+
+        Ok(self)
+    }
+
+    /// Wraps an instance into a rate limiter which makes sure no more than `requests_per_second`
+    /// requests are sent to a smartplug (some smartplugs get confused when hammered with rapid
+    /// requests). Requests are delayed as needed, clones of a rate limited instance share a limit.
+    ///
+    /// Returns [`TpLinkHs110Error::InvalidParameter`] if `requests_per_second` isn't a positive
+    /// finite number.
+    pub fn rate_limited(
+        self,
+        requests_per_second: f64,
+    ) -> Result<RateLimitedHS110, TpLinkHs110Error> {
+        if !(requests_per_second.is_finite() && requests_per_second > 0.0) {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "requests per second should be a positive finite number, got {requests_per_second}"
+            )))?;
+        }
+
+        Ok(RateLimitedHS110(Self {
+            min_request_interval: Some(Duration::from_secs_f64(1.0 / requests_per_second)),
+            last_request: Arc::default(),
+            ..self
+        }))
+    }
+
+    /// Returns smartplug network address.
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// Returns smartplug IP address.
+    pub fn ip_addr(&self) -> IpAddr {
+        self.socket_addr.ip()
+    }
+
+    /// Returns smartplug IPv4 address (`None` if the smartplug is addressed using IPv6).
+    pub fn ip_v4(&self) -> Option<Ipv4Addr> {
+        match self.socket_addr.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        }
+    }
+
+    /// Returns smartplug TCP port number.
+    pub fn port(&self) -> u16 {
+        self.socket_addr.port()
+    }
+
     /// "Encrypts" a given string (which is usually a command represented as a JSON).
     ///
     /// This way of encryption/scrambling is necessary before sending a command to a smartplug.
@@ -51,45 +517,134 @@ impl HS110 {
     where
         S: AsRef<str>,
     {
-        let mut key = 171;
-
-        (payload.as_ref().len() as u32)
-            .to_be_bytes()
-            .into_iter()
-            .chain(payload.as_ref().as_bytes().iter().map(|v| {
-                key ^= v;
-                key
-            }))
-            .collect()
+        protocol::encrypt(payload)
     }
 
     /// Attempts to decrypt/unscramble data received from a smartplug.
     fn decrypt(payload: &[u8]) -> Result<String, TpLinkHs110Error> {
-        const HEADER_LEN: usize = size_of::<u32>();
-        if payload.len() < HEADER_LEN {
-            Err(TpLinkHs110Error::ShortEncryptedResponse(payload.len()))?
+        protocol::decrypt(payload)
+    }
+
+    /// Helper function which establishes a connection to a smartplug (from a local address set
+    /// with [`HS110::with_local_bind_addr`], if any).
+    fn connect(&self) -> Result<net::TcpStream, TpLinkHs110Error> {
+        let stream = self.open_stream()?;
+        self.configure_stream(&SockRef::from(&stream))?;
+
+        Ok(stream)
+    }
+
+    /// Helper function which applies socket options (see [`HS110::with_tcp_nodelay`] and
+    /// [`HS110::with_tcp_keepalive`]) to an established connection.
+    fn configure_stream(&self, socket: &SockRef<'_>) -> std::io::Result<()> {
+        socket.set_nodelay(self.tcp_nodelay)?;
+        if let Some(keepalive) = self.tcp_keepalive {
+            socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(keepalive))?;
         }
 
-        let payload_len_from_header = u32::from_be_bytes(payload[..HEADER_LEN].try_into()?);
-        let payload_len_actual = payload.len() - HEADER_LEN;
-        if payload_len_actual != payload_len_from_header as usize {
-            Err(TpLinkHs110Error::EncryptedPayloadLengthMismatch {
-                payload_len_actual,
-                payload_len_from_header,
-            })?;
+        Ok(())
+    }
+
+    /// Attempts to establish a connection with a smartplug in advance without sending anything,
+    /// so that subsequent requests skip the connection phase (e.g. in scripts with strict timing
+    /// requirements). The connection is kept open and reused by subsequent requests (clones share
+    /// it) until [`HS110::disconnect`] is called. If the connection turns out to be closed by
+    /// a smartplug before a request reaches it, a new one is established for the request.
+    ///
+    /// Note that HS110 firmware closes a connection after each response, so usually only the
+    /// first request benefits from a pre-established connection.
+    pub fn connect_only(&self) -> Result<(), TpLinkHs110Error> {
+        let stream = self.connect()?;
+        *self.stream.lock().unwrap_or_else(PoisonError::into_inner) = Some(stream);
+
+        Ok(())
+    }
+
+    /// Attempts to close a connection established with [`HS110::connect_only`] (if any).
+    pub fn disconnect(&self) -> Result<(), TpLinkHs110Error> {
+        let stored = self
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(stream) = stored {
+            match stream.shutdown(net::Shutdown::Both) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotConnected => Err(err)?,
+                _ => {}
+            }
         }
 
-        let mut key = 171;
-        let decrypted: String = payload[HEADER_LEN..]
-            .iter()
-            .map(|byte| {
-                let plain_char = (key ^ byte) as char;
-                key = *byte;
-                plain_char
-            })
-            .collect();
+        Ok(())
+    }
+
+    /// Helper function which establishes a TCP connection (from a local address and a port of
+    /// a source port range if they are set), see [`HS110::connect`].
+    fn open_stream(&self) -> Result<net::TcpStream, TpLinkHs110Error> {
+        let connect_timeout = self.connect_timeout.or(self.timeout);
+        let Some(socket) = self.bound_socket()? else {
+            return Ok(match connect_timeout {
+                None => net::TcpStream::connect(self.socket_addr)?,
+                Some(duration) => net::TcpStream::connect_timeout(&self.socket_addr, duration)?,
+            });
+        };
+
+        match connect_timeout {
+            None => socket.connect(&self.socket_addr.into())?,
+            Some(duration) => socket.connect_timeout(&self.socket_addr.into(), duration)?,
+        }
 
-        Ok(decrypted)
+        Ok(socket.into())
+    }
+
+    /// Helper function which creates a socket bound to a local address and a port of a source
+    /// port range, if any of them is set (otherwise nothing is returned).
+    fn bound_socket(&self) -> Result<Option<Socket>, TpLinkHs110Error> {
+        if self.bind_addr.is_none() && self.source_port_range.is_none() {
+            return Ok(None);
+        }
+
+        let socket = Socket::new(
+            Domain::for_address(self.socket_addr),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        match (self.source_port_range, self.bind_addr) {
+            (Some(range), _) => self.bind_in_range(&socket, range)?,
+            (None, Some(bind_addr)) => socket.bind(&bind_addr.into())?,
+            (None, None) => unreachable!("unbound sockets are not created above"),
+        }
+
+        Ok(Some(socket))
+    }
+
+    /// Helper function which binds a socket to a free port of a given range, starting from
+    /// a random one, see [`HS110::with_source_port_range`].
+    fn bind_in_range(
+        &self,
+        socket: &Socket,
+        (start, end): (u16, u16),
+    ) -> Result<(), TpLinkHs110Error> {
+        let ip = self.bind_addr.map_or(
+            match self.socket_addr {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            },
+            |bind_addr| bind_addr.ip(),
+        );
+        let ports = u64::from(end - start) + 1;
+        let first = RandomState::new().build_hasher().finish() % ports;
+
+        let mut last_err = None;
+        for offset in 0..ports {
+            let port = start + ((first + offset) % ports) as u16;
+            match socket.bind(&SocketAddr::new(ip, port).into()) {
+                Ok(()) => return Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => last_err = Some(err),
+                Err(err) => Err(err)?,
+            }
+        }
+
+        Err(last_err.expect("a range has at least one port"))?
     }
 
     /// Attempts to send a provided request to a smartplug, receive a response and represent it as
@@ -98,30 +653,178 @@ impl HS110 {
     where
         S: AsRef<str>,
     {
-        let mut stream = match self.timeout {
-            None => net::TcpStream::connect(self.socket_addr)?,
-            Some(duration) => {
-                let stream = net::TcpStream::connect_timeout(&self.socket_addr, duration)?;
-                stream.set_read_timeout(self.timeout)?;
-                stream.set_write_timeout(self.timeout)?;
-                stream
+        self.throttled(|smartplug| smartplug.exchange(request))
+    }
+
+    /// Attempts to send a provided request to a smartplug without waiting for a response, see
+    /// [`HS110::request`].
+    fn request_without_response<S>(&self, request: S) -> Result<(), TpLinkHs110Error>
+    where
+        S: AsRef<str>,
+    {
+        self.throttled(|smartplug| {
+            // A response would arrive over a pre-established connection, so it can't be reused
+            // by subsequent requests.
+            let stored = smartplug
+                .stream
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            if let Some(mut stream) = stored {
+                match smartplug.send_over(&mut stream, request.as_ref()) {
+                    Ok(()) => return Ok(()),
+                    Err(err) => {
+                        log::debug!("pre-established connection failed ({err}), reconnecting");
+                    }
+                }
             }
-        };
+
+            Ok(smartplug.send_over(&mut smartplug.connect()?, request.as_ref())?)
+        })
+    }
+
+    /// Helper function which performs a communication with a smartplug respecting a request rate
+    /// limit (see [`HS110::rate_limited`]) and remembers an error (see [`HS110::last_error`]).
+    fn throttled<T, F>(&self, communicate: F) -> Result<T, TpLinkHs110Error>
+    where
+        F: FnOnce(&Self) -> Result<T, TpLinkHs110Error>,
+    {
+        if let Some(interval) = self.min_request_interval {
+            // The lock is held while sleeping, so concurrent requests are throttled as well.
+            let mut last_request = self
+                .last_request
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if let Some(last_request) = *last_request {
+                std::thread::sleep(interval.saturating_sub(last_request.elapsed()));
+            }
+            *last_request = Some(Instant::now());
+        }
+
+        communicate(self).inspect_err(|err| {
+            *self
+                .last_error
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(err.clone());
+        })
+    }
+
+    /// Helper function which performs a single request-response exchange with a smartplug, see
+    /// [`HS110::request`].
+    fn exchange<S>(&self, request: S) -> Result<String, TpLinkHs110Error>
+    where
+        S: AsRef<str>,
+    {
+        let stored = self
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(mut stream) = stored {
+            // A smartplug might have closed an idle connection meanwhile, then either sending
+            // fails or the connection is closed before any response arrives. A request is sent
+            // again over a new connection only in these cases, as otherwise a smartplug might
+            // have executed it already.
+            match self.send_over(&mut stream, request.as_ref()) {
+                Ok(()) => {
+                    let received = Self::receive_over(&mut stream)?;
+                    if !received.is_empty() {
+                        let response = self.decode_response(&received)?;
+                        *self.stream.lock().unwrap_or_else(PoisonError::into_inner) = Some(stream);
+                        return Ok(response);
+                    }
+                    log::debug!("pre-established connection is closed, reconnecting");
+                }
+                Err(err) => {
+                    log::debug!("pre-established connection failed ({err}), reconnecting");
+                }
+            }
+        }
+
+        self.exchange_over(&mut self.connect()?, request.as_ref())
+    }
+
+    /// Helper function which performs a request-response exchange over a given connection.
+    fn exchange_over(
+        &self,
+        stream: &mut net::TcpStream,
+        request: &str,
+    ) -> Result<String, TpLinkHs110Error> {
+        self.send_over(stream, request)?;
+        self.decode_response(&Self::receive_over(stream)?)
+    }
+
+    /// Helper function which sends an encrypted request over a given connection.
+    fn send_over(&self, stream: &mut net::TcpStream, request: &str) -> std::io::Result<()> {
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
 
         stream.write_all(&Self::encrypt(request))?;
-        stream.flush()?;
+        stream.flush()
+    }
 
-        let mut received = vec![];
-        let mut rx_buf = [0u8; NET_BUFFER_SIZE];
-        loop {
-            let nread = stream.read(&mut rx_buf)?;
-            received.extend_from_slice(&rx_buf[..nread]);
-            if nread < NET_BUFFER_SIZE {
-                break;
+    /// Helper function which receives an encrypted response over a given connection. Nothing is
+    /// returned if the connection is closed (or reset) before any response bytes arrive.
+    fn receive_over(stream: &mut net::TcpStream) -> std::io::Result<Vec<u8>> {
+        // A response may arrive in any number of segments, so a length header is read first and
+        // then exactly as many bytes as it claims. If a smartplug closes the connection earlier,
+        // the shortage is reported by `decrypt`.
+        let mut received = Vec::with_capacity(NET_BUFFER_SIZE);
+        match Read::take(&mut *stream, protocol::HEADER_LEN as u64).read_to_end(&mut received) {
+            Err(err)
+                if received.is_empty()
+                    && matches!(
+                        err.kind(),
+                        std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted
+                    ) =>
+            {
+                return Ok(received);
             }
+            result => result?,
+        };
+        if let Ok(header) = <[u8; protocol::HEADER_LEN]>::try_from(received.as_slice()) {
+            Read::take(&mut *stream, u64::from(u32::from_be_bytes(header)))
+                .read_to_end(&mut received)?;
+        }
+
+        Ok(received)
+    }
+
+    /// Helper function which decrypts a received response (and verifies its checksum if
+    /// enabled).
+    fn decode_response(&self, received: &[u8]) -> Result<String, TpLinkHs110Error> {
+        let response = Self::decrypt(received)?;
+        if self.verify {
+            Self::verify_checksum(&response)?;
         }
 
-        Self::decrypt(&received)
+        Ok(response)
+    }
+
+    /// Verifies a checksum of a decrypted response (if the response contains one).
+    fn verify_checksum(response: &str) -> Result<(), TpLinkHs110Error> {
+        let mut response = serde_json::from_str::<Value>(response)?;
+        let Some(reported) = response
+            .as_object_mut()
+            .and_then(|object| object.remove("checksum"))
+        else {
+            log::warn!("response doesn't contain a checksum, skipping verification");
+            return Ok(());
+        };
+
+        let reported = reported
+            .as_u64()
+            .and_then(|checksum| u32::try_from(checksum).ok())
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?;
+        let calculated = crc32(response.to_string().as_bytes());
+        if reported == calculated {
+            Ok(())
+        } else {
+            Err(TpLinkHs110Error::ChecksumMismatch {
+                reported,
+                calculated,
+            })
+        }
     }
 
     /// Attempts to get a general info from/about a smartplug.
@@ -170,6 +873,59 @@ impl HS110 {
             .extract_hierarchical(&["system", "get_sysinfo", field])
     }
 
+    /// Attempts to get typed system information (the most useful part of [`HS110::info`]).
+    pub fn sysinfo(&self) -> Result<SysInfo, TpLinkHs110Error> {
+        Ok(serde_json::from_value(self.command(
+            "system",
+            "get_sysinfo",
+            json!({}),
+        )?)?)
+    }
+
+    /// Returns an endless iterator polling system information every `interval`, see
+    /// [`SysinfoStream`]. The first poll happens right away.
+    pub fn sysinfo_stream(&self, interval: Duration) -> SysinfoStream {
+        SysinfoStream {
+            smartplug: self.clone(),
+            interval,
+            next_poll: None,
+            power_state: None,
+            on_power_change: vec![],
+        }
+    }
+
+    /// Attempts to make sure the remote side is a smartplug and get its identity. Useful to
+    /// detect a wrong address before issuing any commands.
+    ///
+    /// Returns [`TpLinkHs110Error::NotASmartPlug`] if the response doesn't look like a response
+    /// of a smartplug.
+    pub fn verify_connectivity(&self) -> Result<DeviceIdentity, TpLinkHs110Error> {
+        let sysinfo = self
+            .info()
+            .and_then(|info| info.extract_hierarchical(&["system", "get_sysinfo"]))
+            .map_err(|err| match err {
+                TpLinkHs110Error::IO(_) => err,
+                _ => TpLinkHs110Error::NotASmartPlug,
+            })?;
+        let field = |field| {
+            sysinfo
+                .get(field)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or(TpLinkHs110Error::NotASmartPlug)
+        };
+
+        if field("type")? != "IOT.SMARTPLUGSWITCH" {
+            Err(TpLinkHs110Error::NotASmartPlug)?;
+        }
+
+        Ok(DeviceIdentity {
+            device_id: field("deviceId")?,
+            model: field("model")?,
+            alias: field("alias")?,
+        })
+    }
+
     /// Attempts to get current LED state (which could be ON or OFF).
     pub fn led_state(&self) -> Result<LedState, TpLinkHs110Error> {
         Ok((self
@@ -180,11 +936,28 @@ impl HS110 {
             .into())
     }
 
+    /// Attempts to make sure LED is in an `expected` state, returns
+    /// [`TpLinkHs110Error::UnexpectedLedState`] if it isn't.
+    pub fn assert_led_state(&self, expected: LedState) -> Result<(), TpLinkHs110Error> {
+        let actual = self.led_state()?;
+        if actual != expected {
+            Err(TpLinkHs110Error::UnexpectedLedState { expected, actual })?;
+        }
+
+        Ok(())
+    }
+
+    /// Attempts to get LED state as a number: `1` for ON, `0` for OFF (convenient for monitoring
+    /// systems).
+    pub fn led_state_as_u8(&self) -> Result<u8, TpLinkHs110Error> {
+        self.led_state().map(LedState::as_u8)
+    }
+
     /// Attempts to switch LED to a specified state (i.e. turn it ON or turn it OFF).
     pub fn set_led_state(&self, led_state: LedState) -> Result<(), TpLinkHs110Error> {
         match serde_json::from_str::<Value>(
             &self.request(
-                json!({"system": {"set_led_off": {"off": (led_state == LedState::Off) as u8 }}})
+                json!({"system": {"set_led_off": {"off": u8::from(led_state == LedState::Off) }}})
                     .to_string(),
             )?,
         )?
@@ -197,9 +970,50 @@ impl HS110 {
         }
     }
 
+    /// Attempts to get LED brightness in percent. Smartplugs which don't support dimming report
+    /// `100` for LED switched on and `0` for LED switched off.
+    pub fn get_led_brightness(&self) -> Result<u8, TpLinkHs110Error> {
+        match self.command("system", "get_led_brightness", json!({})) {
+            Ok(response) => response
+                .extract_hierarchical(&["brightness"])?
+                .as_u64()
+                .and_then(|brightness| u8::try_from(brightness).ok())
+                .filter(|brightness| *brightness <= MAX_LED_BRIGHTNESS)
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation),
+            Err(err) if is_unsupported_command(&err) => Ok(match self.led_state()? {
+                LedState::On => MAX_LED_BRIGHTNESS,
+                LedState::Off => 0,
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Attempts to set LED brightness in percent (`0..=100`).
+    ///
+    /// Smartplugs which don't support dimming (the most of them) could only switch LED on
+    /// (`100`) or off (`0`), see [`HS110::set_led_state`], other levels are reported as
+    /// [`TpLinkHs110Error::FeatureNotSupported`] for them.
+    pub fn set_led_brightness(&self, level: u8) -> Result<(), TpLinkHs110Error> {
+        if level > MAX_LED_BRIGHTNESS {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "LED brightness should be within 0..={MAX_LED_BRIGHTNESS}%, got {level}%"
+            )))?;
+        }
+
+        match self.command("system", "set_led_brightness", json!({"brightness": level})) {
+            Ok(_) => Ok(()),
+            Err(err) if is_unsupported_command(&err) => match level {
+                0 => self.set_led_state(LedState::Off),
+                MAX_LED_BRIGHTNESS => self.set_led_state(LedState::On),
+                _ => Err(TpLinkHs110Error::FeatureNotSupported),
+            },
+            Err(err) => Err(err),
+        }
+    }
+
     /// Attempts to obtain a smartplug name (alias). Name is given during smartplug initial setup,
     /// and it could be changed in companion app (Tapo or Kasa) on a mobile phone.
-    pub fn hostname(&self) -> Result<String, TpLinkHs110Error> {
+    pub fn get_alias(&self) -> Result<String, TpLinkHs110Error> {
         Ok(self
             .info_field_value("alias")?
             .as_str()
@@ -207,35 +1021,189 @@ impl HS110 {
             .to_string())
     }
 
-    /// Attempts to obtain hardware version (hardware revision) of a smartplug.
-    pub fn hw_version(&self) -> Result<HwVersion, TpLinkHs110Error> {
-        match self
-            .info_field_value("hw_ver")?
-            .as_str()
-            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
-        {
-            "1.0" => Ok(HwVersion::Version1),
-            "2.0" => Ok(HwVersion::Version2),
-            other => Ok(HwVersion::Unsupported(other.into())),
+    /// Attempts to get a number of times the power relay has been switched since factory reset
+    /// (reported as `sw_cnt` or `relay_cnt` field of system information by some firmware
+    /// versions). [`TpLinkHs110Error::FeatureNotSupported`] is returned if a smartplug doesn't
+    /// report it.
+    pub fn relay_switch_count(&self) -> Result<u64, TpLinkHs110Error> {
+        let sysinfo = self
+            .info()?
+            .extract_hierarchical(&["system", "get_sysinfo"])?;
+
+        sysinfo
+            .get("sw_cnt")
+            .or_else(|| sysinfo.get("relay_cnt"))
+            .ok_or(TpLinkHs110Error::FeatureNotSupported)?
+            .as_u64()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+    }
+
+    /// Attempts to estimate how much of the power relay life is left (in percent) given a number
+    /// of switching cycles the relay is rated for, see [`HS110::relay_switch_count`].
+    ///
+    /// Rated cycles vary by model and hardware version (100 000 is a typical figure), check
+    /// a datasheet of a particular smartplug.
+    pub fn relay_life_remaining_pct(&self, rated_cycles: u64) -> Result<f64, TpLinkHs110Error> {
+        if rated_cycles == 0 {
+            Err(TpLinkHs110Error::InvalidParameter(
+                "rated cycles should be positive".to_string(),
+            ))?;
         }
+        let used = self.relay_switch_count()?.min(rated_cycles);
+
+        Ok((rated_cycles - used) as f64 / rated_cycles as f64 * 100.0)
     }
 
-    /// Attempts to get current power relay state. It is either smartplug powers connected device
-    /// (ON) or not (OFF).
-    pub fn power_state(&self) -> Result<PowerState, TpLinkHs110Error> {
-        Ok((self
-            .info_field_value("relay_state")?
-            .as_u64()
-            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
-            == 1)
-            .into())
+    /// Attempts to set a smartplug name (alias), i.e. a name displayed in the app. Returns
+    /// [`TpLinkHs110Error::InvalidParameter`] if the name is empty, longer than
+    /// [`MAX_NAME_LEN`] bytes or contains control characters.
+    pub fn set_alias(&self, alias: &str) -> Result<(), TpLinkHs110Error> {
+        validate_name(alias, MAX_NAME_LEN)?;
+        self.command("system", "set_dev_alias", json!({"alias": alias}))
+            .map(|_| ())
+    }
+
+    /// Attempts to set a smartplug device type name (`dev_name` field of system information, e.g.
+    /// "Wi-Fi Smart Plug With Energy Monitoring"). Unlike an alias (see [`HS110::set_alias`]) it
+    /// describes a kind of a device rather than a particular one and is rarely changed. Returns
+    /// [`TpLinkHs110Error::InvalidParameter`] if the name is empty, longer than
+    /// [`MAX_DEV_NAME_LEN`] bytes or contains control characters.
+    pub fn set_dev_name(&self, name: &str) -> Result<(), TpLinkHs110Error> {
+        validate_name(name, MAX_DEV_NAME_LEN)?;
+        self.command("system", "set_dev_name", json!({"dev_name": name}))
+            .map(|_| ())
+    }
+
+    /// Attempts to obtain a smartplug name (alias), same as [`HS110::get_alias`].
+    #[deprecated(since = "0.3.0", note = "use get_alias() instead")]
+    pub fn hostname(&self) -> Result<String, TpLinkHs110Error> {
+        self.get_alias()
+    }
+
+    /// Attempts to get a smartplug MAC address (reported as `mac` or, by some hardware versions,
+    /// as `mic_mac` field of system information).
+    pub fn get_mac_address(&self) -> Result<MacAddress, TpLinkHs110Error> {
+        let sysinfo = self
+            .info()?
+            .extract_hierarchical(&["system", "get_sysinfo"])?;
+
+        match sysinfo.get("mac").or_else(|| sysinfo.get("mic_mac")) {
+            Some(mac) => MacAddress::try_from(
+                mac.as_str()
+                    .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?,
+            ),
+            None => Err(TpLinkHs110Error::KeyIsNotAvailable {
+                response: sysinfo,
+                key: "mac".to_string(),
+            }),
+        }
+    }
+
+    /// Attempts to get a set of icons available for a smartplug (icons are chosen in the Kasa
+    /// app and identified by a hash).
+    pub fn get_device_icons(&self) -> Result<Vec<DeviceIcon>, TpLinkHs110Error> {
+        Ok(serde_json::from_value(
+            self.command("system", "get_dev_icons", json!({}))?
+                .extract_hierarchical(&["icon_list"])?,
+        )?)
+    }
+
+    /// Attempts to set a smartplug icon given by a hash (32 hexadecimal digits), see
+    /// [`HS110::get_device_icons`].
+    pub fn set_device_icon(&self, icon_hash: &str) -> Result<(), TpLinkHs110Error> {
+        if icon_hash.len() != 32 || !icon_hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "icon hash should consist of 32 hexadecimal digits, got {icon_hash:?}"
+            )))?;
+        }
+
+        self.command("system", "set_dev_icon", json!({"hash": icon_hash}))
+            .map(|_| ())
+    }
+
+    /// Attempts to obtain hardware version (hardware revision) of a smartplug.
+    #[deprecated(since = "0.3.0", note = "use `hw_version_full()` instead")]
+    pub fn hw_version(&self) -> Result<HwVersion, TpLinkHs110Error> {
+        Ok(self
+            .info_field_value("hw_ver")?
+            .as_str()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            .into())
+    }
+
+    /// Attempts to obtain hardware version (hardware revision) of a smartplug along with its
+    /// hardware, firmware and OEM identifiers. Useful for support diagnostics.
+    pub fn hw_version_full(&self) -> Result<HwVersionInfo, TpLinkHs110Error> {
+        let sysinfo = self
+            .info()?
+            .extract_hierarchical(&["system", "get_sysinfo"])?;
+        let field = |field| -> Result<String, TpLinkHs110Error> {
+            Ok(sysinfo
+                .extract_hierarchical(&[field])?
+                .as_str()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+                .to_string())
+        };
+
+        Ok(HwVersionInfo {
+            hw_ver: field("hw_ver")?.as_str().into(),
+            hw_id: field("hwId")?,
+            fw_id: field("fwId")?,
+            oem_id: field("oemId")?,
+        })
+    }
+
+    /// Attempts to detect a smartplug model. Could be used to check whether a smartplug has an
+    /// energy meter before calling [`HS110::emeter`].
+    pub fn device_model(&self) -> Result<DeviceModel, TpLinkHs110Error> {
+        Ok(self
+            .info_field_value("model")?
+            .as_str()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            .parse()
+            .unwrap_or_else(|never: Infallible| match never {}))
+    }
+
+    /// Attempts to get current power relay state. It is either smartplug powers connected device
+    /// (ON) or not (OFF).
+    pub fn power_state(&self) -> Result<PowerState, TpLinkHs110Error> {
+        Ok((self
+            .info_field_value("relay_state")?
+            .as_u64()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            == 1)
+            .into())
+    }
+
+    /// Attempts to get a mode a smartplug is currently in, i.e. which kind of rules controls it.
+    pub fn active_mode(&self) -> Result<ActiveMode, TpLinkHs110Error> {
+        Ok(serde_json::from_value(
+            self.info_field_value("active_mode")?,
+        )?)
+    }
+
+    /// Attempts to make sure power relay is in an `expected` state (e.g. before increasing
+    /// a load), returns [`TpLinkHs110Error::UnexpectedPowerState`] if it isn't.
+    pub fn assert_power_state(&self, expected: PowerState) -> Result<(), TpLinkHs110Error> {
+        let actual = self.power_state()?;
+        if actual != expected {
+            Err(TpLinkHs110Error::UnexpectedPowerState { expected, actual })?;
+        }
+
+        Ok(())
+    }
+
+    /// Attempts to get power relay state as a number: `1` for ON, `0` for OFF (convenient for
+    /// monitoring systems).
+    pub fn power_state_as_u8(&self) -> Result<u8, TpLinkHs110Error> {
+        self.power_state().map(PowerState::as_u8)
     }
 
     /// Attempts to switch power relay on or switch it off.
     pub fn set_power_state(&self, state: PowerState) -> Result<(), TpLinkHs110Error> {
         match serde_json::from_str::<Value>(
             &self.request(
-                json!({"system": {"set_relay_state": {"state": (state == PowerState::On) as u8 }}})
+                json!({"system": {"set_relay_state": {"state": u8::from(state == PowerState::On) }}})
                     .to_string(),
             )?,
         )?
@@ -248,6 +1216,107 @@ impl HS110 {
         }
     }
 
+    /// Attempts to switch power relay to the opposite state, returns the new state.
+    pub fn toggle_power(&self) -> Result<PowerState, TpLinkHs110Error> {
+        let state = !self.power_state()?;
+        self.set_power_state(state)?;
+
+        Ok(state)
+    }
+
+    /// Attempts to switch power relay to the opposite state without a race with the physical
+    /// button or the app (unlike [`HS110::toggle_power`], which reads the state first). Some
+    /// firmware versions invert the relay state on their own given a special state value, other
+    /// ones reject it or treat it as an ordinary "on", so it falls back to
+    /// [`HS110::toggle_power`] then. Returns the new state.
+    ///
+    /// Until support is known (see [`HS110::supports_atomic_toggle`]), the state is read before
+    /// and after the atomic command to make sure the relay has actually been inverted. Support is
+    /// remembered only once the relay is seen switching off, as switching on doesn't tell an
+    /// inversion from an ordinary "on".
+    pub fn atomic_toggle(&self) -> Result<PowerState, TpLinkHs110Error> {
+        match self.supports_atomic_toggle() {
+            Some(true) => {
+                self.set_atomic_toggle_state()?;
+                return self.power_state();
+            }
+            Some(false) => return self.toggle_power(),
+            None => {}
+        }
+
+        let old_state = self.power_state()?;
+        if let Err(err) = self.set_atomic_toggle_state() {
+            // Firmware which doesn't know the state value considers it an invalid argument.
+            if !(is_unsupported_command(&err)
+                || matches!(err, TpLinkHs110Error::SmartplugErrCode(INVALID_ARGUMENT)))
+            {
+                return Err(err);
+            }
+            log::debug!("atomic toggle is not supported: {err}");
+            self.cache_atomic_toggle_support(false);
+            return self.toggle_power();
+        }
+
+        let new_state = self.power_state()?;
+        if new_state == old_state {
+            log::debug!("atomic toggle has left the relay {new_state}, it is not supported");
+            self.cache_atomic_toggle_support(false);
+            self.set_power_state(!old_state)?;
+            return Ok(!old_state);
+        }
+        if old_state == PowerState::On {
+            self.cache_atomic_toggle_support(true);
+        }
+
+        Ok(new_state)
+    }
+
+    /// Returns whether a smartplug is known to support atomic toggling, `None` until
+    /// [`HS110::atomic_toggle`] has found it out. A smartplug is never probed, as that would
+    /// switch the relay.
+    pub fn supports_atomic_toggle(&self) -> Option<bool> {
+        *self
+            .atomic_toggle_supported
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Helper function which sends a relay state which inverts the relay, see
+    /// [`HS110::atomic_toggle`].
+    fn set_atomic_toggle_state(&self) -> Result<(), TpLinkHs110Error> {
+        self.command(
+            "system",
+            "set_relay_state",
+            json!({"state": ATOMIC_TOGGLE_STATE}),
+        )
+        .map(drop)
+    }
+
+    /// Remembers whether atomic toggling is supported, see [`HS110::atomic_toggle`].
+    fn cache_atomic_toggle_support(&self, supported: bool) {
+        *self
+            .atomic_toggle_supported
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(supported);
+    }
+
+    /// Same as [`HS110::toggle_power`], but reads power relay state once again afterwards to make
+    /// sure it has actually changed. Returns `(old_state, new_state)`, or
+    /// [`TpLinkHs110Error::StateNotChanged`] if a smartplug has ignored the command.
+    pub fn toggle_power_with_confirmation(
+        &self,
+    ) -> Result<(PowerState, PowerState), TpLinkHs110Error> {
+        let old_state = self.power_state()?;
+        self.set_power_state(!old_state)?;
+
+        let new_state = self.power_state()?;
+        if new_state == old_state {
+            Err(TpLinkHs110Error::StateNotChanged(new_state))?;
+        }
+
+        Ok((old_state, new_state))
+    }
+
     /// Attempts to get an information about smartplug connection to TP-Link cloud.
     ///
     /// In case of success resulting JSON Value looks similar to this:
@@ -266,6 +1335,7 @@ impl HS110 {
     ///     "username": String("username@example.com"),
     /// }
     /// ```
+    #[deprecated(since = "0.3.0", note = "use `cloud_info_typed()` instead")]
     pub fn cloudinfo(&self) -> Result<Value, TpLinkHs110Error> {
         serde_json::from_str::<Value>(
             &self.request(json!({"cnCloud": {"get_info": {}}}).to_string())?,
@@ -273,6 +1343,88 @@ impl HS110 {
         .extract_hierarchical(&["cnCloud", "get_info"])
     }
 
+    /// Attempts to get an information about smartplug connection to TP-Link cloud.
+    pub fn cloud_info_typed(&self) -> Result<CloudInfo, TpLinkHs110Error> {
+        Ok(serde_json::from_value(self.command(
+            "cnCloud",
+            "get_info",
+            json!({}),
+        )?)?)
+    }
+
+    /// Attempts to get an interval (in seconds) between polls of TP-Link cloud server by
+    /// a smartplug, see [`HS110::set_cloud_poll_interval`].
+    pub fn get_cloud_poll_interval(&self) -> Result<u32, TpLinkHs110Error> {
+        self.command("cnCloud", "get_info", json!({}))?
+            .extract_hierarchical(&["poll_interval"])?
+            .as_u64()
+            .and_then(|seconds| u32::try_from(seconds).ok())
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+    }
+
+    /// Attempts to set an interval (in seconds, at least 60) between polls of TP-Link cloud
+    /// server by a smartplug. Longer intervals reduce network traffic (and battery usage of
+    /// portable devices), but increase a latency of commands issued through the cloud (e.g. from
+    /// the Kasa app outside of the local network) and of state updates seen there.
+    pub fn set_cloud_poll_interval(&self, seconds: u32) -> Result<(), TpLinkHs110Error> {
+        if seconds < MIN_CLOUD_POLL_INTERVAL {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "cloud poll interval should be at least {MIN_CLOUD_POLL_INTERVAL} seconds, got \
+                {seconds}"
+            )))?;
+        }
+
+        self.command(
+            "cnCloud",
+            "set_server_url",
+            json!({"poll_interval": seconds}),
+        )
+        .map(|_| ())
+    }
+
+    /// Attempts to check whether a smartplug is bound to a TP-Link cloud account.
+    pub fn is_cloud_bound(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed()?.binded)
+    }
+
+    /// Attempts to get a hostname of TP-Link cloud server a smartplug connects to.
+    pub fn cloud_server(&self) -> Result<String, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed()?.server)
+    }
+
+    /// Attempts to set a hostname of a cloud server a smartplug connects to (e.g. to point it to
+    /// a self-hosted server).
+    pub fn set_cloud_server(&self, server: &str) -> Result<(), TpLinkHs110Error> {
+        self.command("cnCloud", "set_server_url", cloud_server_params(server)?)
+            .map(|_| ())
+    }
+
+    /// Attempts to unbind a smartplug from a TP-Link cloud account.
+    pub fn unbind_cloud(&self) -> Result<(), TpLinkHs110Error> {
+        self.command("cnCloud", "unbind", json!({})).map(|_| ())
+    }
+
+    /// Attempts to find out whether a smartplug is prevented from connecting to TP-Link cloud,
+    /// see [`HS110::set_cloud_stop_connect`].
+    pub fn is_cloud_stop_connect(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed()?.stop_connect)
+    }
+
+    /// Attempts to prevent (or allow again) a smartplug from connecting to TP-Link cloud, i.e.
+    /// to disable any cloud communication for privacy reasons. The smartplug stays controllable
+    /// over the local network, but not through the cloud (e.g. from the Kasa app outside of the
+    /// local network).
+    ///
+    /// Note that this may prevent firmware updates, as they are delivered through the cloud.
+    pub fn set_cloud_stop_connect(&self, stop: bool) -> Result<(), TpLinkHs110Error> {
+        self.command(
+            "cnCloud",
+            "stop_connect",
+            json!({"stopConnect": u8::from(stop)}),
+        )
+        .map(|_| ())
+    }
+
     /// Attempts to get an information about Wi-Fi access points which smartplug observes in a
     /// radio spectrum.
     /// The `refresh` boolean specifies whether it is necessary to perform scan of Wi-Fi spectrum
@@ -300,16 +1452,49 @@ impl HS110 {
     /// ],
     /// ```
     pub fn ap_list(&self, refresh: bool) -> Result<Value, TpLinkHs110Error> {
-        serde_json::from_str::<Value>(
-            &self.request(
-                json!({"netif": {"get_scaninfo": {"refresh": refresh as u8}}}).to_string(),
-            )?,
-        )?
+        serde_json::from_str::<Value>(&self.request(
+            json!({"netif": {"get_scaninfo": {"refresh": u8::from(refresh)}}}).to_string(),
+        )?)?
         .extract_hierarchical(&["netif", "get_scaninfo", "ap_list"])
     }
 
+    /// Attempts to make a smartplug scan Wi-Fi spectrum without waiting for the scan to complete
+    /// (which takes several seconds): the request is sent and the connection is closed right
+    /// away. Results could be obtained later with [`HS110::get_cached_ap_list`].
+    pub fn trigger_wifi_scan(&self) -> Result<(), TpLinkHs110Error> {
+        self.request_without_response(
+            json!({"netif": {"get_scaninfo": {"refresh": 1}}}).to_string(),
+        )
+    }
+
+    /// Attempts to get Wi-Fi access points a smartplug has found during its last scan (without
+    /// scanning again), see [`HS110::trigger_wifi_scan`].
+    pub fn get_cached_ap_list(&self) -> Result<Vec<AccessPoint>, TpLinkHs110Error> {
+        self.scan_info(false)
+    }
+
+    /// Attempts to scan Wi-Fi spectrum and get found access points, waiting for the scan to
+    /// complete at most `timeout` (instead of a timeout set with [`HS110::with_timeout`]).
+    pub fn scan_and_wait(&self, timeout: Duration) -> Result<Vec<AccessPoint>, TpLinkHs110Error> {
+        self.clone().with_timeout(timeout).scan_info(true)
+    }
+
+    /// Helper function which attempts to get Wi-Fi access points (optionally scanning first).
+    fn scan_info(&self, refresh: bool) -> Result<Vec<AccessPoint>, TpLinkHs110Error> {
+        Ok(serde_json::from_value(
+            self.command(
+                "netif",
+                "get_scaninfo",
+                json!({"refresh": u8::from(refresh)}),
+            )?
+            .extract_hierarchical(&["ap_list"])?,
+        )?)
+    }
+
     /// Attempts to get values from smartplug's energy meter. Energy meter is present in HS110, and
-    /// absent in HS100.
+    /// absent in HS100. Multi-outlet power strips (e.g. HS300) have an energy meter per outlet,
+    /// [`TpLinkHs110Error::PowerStripEmeter`] is returned for them, use [`HS110::child_emeter`]
+    /// instead.
     ///
     /// In case of success resulting JSON looks like this:
     /// ```text
@@ -326,11 +1511,324 @@ impl HS110 {
     /// }
     /// ```
     pub fn emeter(&self) -> Result<Value, TpLinkHs110Error> {
-        let mut emeter = serde_json::from_str::<Value>(
-            &self.request(json!({"emeter":{"get_realtime":{}}}).to_string())?,
-        )?
-        .extract_hierarchical(&["emeter", "get_realtime"])?;
+        let mut emeter = self
+            .whole_device_emeter_request("get_realtime", json!({}))?
+            .extract_hierarchical(&["emeter", "get_realtime"])?;
+        Self::normalize_emeter(&mut emeter);
+
+        Ok(emeter)
+    }
+
+    /// Attempts to get all numeric fields of an energy meter response (both base-unit and
+    /// milli-unit variants, see [`HS110::emeter`]) regardless of their names, including
+    /// firmware-specific ones. `err_code` is not included.
+    pub fn get_emeter_fields(&self) -> Result<HashMap<String, f64>, TpLinkHs110Error> {
+        Ok(self
+            .emeter()?
+            .as_object()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            .iter()
+            .filter(|(field, _)| *field != "err_code")
+            .filter_map(|(field, value)| Some((field.clone(), value.as_f64()?)))
+            .collect())
+    }
 
+    /// Attempts to get all top-level system information fields (as returned by `get_sysinfo`
+    /// command). `err_code` is not included.
+    pub fn get_sysinfo_fields(&self) -> Result<HashMap<String, Value>, TpLinkHs110Error> {
+        match self.command("system", "get_sysinfo", json!({}))? {
+            Value::Object(mut fields) => {
+                fields.remove("err_code");
+                Ok(fields.into_iter().collect())
+            }
+            _ => Err(TpLinkHs110Error::UnexpectedValueRepresentation),
+        }
+    }
+
+    /// Attempts to get identifiers of child outlets of a multi-outlet power strip (e.g. HS300).
+    /// Returns an empty list for single-outlet smartplugs, see also [`HS110::get_children`].
+    pub fn get_child_ids(&self) -> Result<Vec<String>, TpLinkHs110Error> {
+        Ok(self
+            .get_children()?
+            .into_iter()
+            .flatten()
+            .map(|child| child.id)
+            .collect())
+    }
+
+    /// Attempts to get child outlets of a multi-outlet power strip (e.g. HS300), which could be
+    /// controlled individually. Returns `None` for single-outlet smartplugs.
+    pub fn get_children(&self) -> Result<Option<Vec<ChildPlug>>, TpLinkHs110Error> {
+        let sysinfo = self.command("system", "get_sysinfo", json!({}))?;
+        let Some(children) = sysinfo.get("children") else {
+            return Ok(None);
+        };
+
+        children
+            .as_array()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            .iter()
+            .map(|child| {
+                let field = |name| {
+                    child
+                        .extract_hierarchical(&[name])?
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+                };
+
+                Ok(ChildPlug {
+                    id: field("id")?,
+                    alias: field("alias")?,
+                    state: (child
+                        .extract_hierarchical(&["state"])?
+                        .as_u64()
+                        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+                        == 1)
+                        .into(),
+                    parent: self.clone(),
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+
+    /// Attempts to switch a given child outlet of a multi-outlet power strip to a specified
+    /// state, other outlets are left intact ([`HS110::set_power_state`] switches all of them).
+    pub fn set_child_power_state(
+        &self,
+        child_id: &str,
+        state: PowerState,
+    ) -> Result<(), TpLinkHs110Error> {
+        self.child_command(
+            child_id,
+            "system",
+            "set_relay_state",
+            json!({"state": u8::from(state == PowerState::On)}),
+        )
+        .map(|_| ())
+    }
+
+    /// Attempts to get values from an energy meter of a given child outlet of a multi-outlet
+    /// power strip. Resulting JSON has the same structure as the one of [`HS110::emeter`].
+    pub fn child_emeter(&self, child_id: &str) -> Result<Value, TpLinkHs110Error> {
+        let mut emeter = self.child_command(child_id, "emeter", "get_realtime", json!({}))?;
+        Self::normalize_emeter(&mut emeter);
+
+        Ok(emeter)
+    }
+
+    /// Attempts to get typed values from smartplug's energy meter, see [`HS110::emeter`].
+    pub fn emeter_reading(&self) -> Result<EmeterReading, TpLinkHs110Error> {
+        EmeterReading::try_from(&self.emeter()?)
+    }
+
+    /// Attempts to wait until power readings settle, i.e. vary by no more than `tolerance_pct`
+    /// percent (of an average power) over the last `window`, and returns readings averaged over
+    /// the window (total consumed energy is the latest one). Energy meter is polled 5 times per
+    /// `window`.
+    ///
+    /// Returns [`TpLinkHs110Error::StabilizationTimeout`] if readings haven't settled within
+    /// 5 windows.
+    pub fn await_stable_emeter(
+        &self,
+        window: Duration,
+        tolerance_pct: f64,
+    ) -> Result<EmeterReading, TpLinkHs110Error> {
+        let deadline = Instant::now() + window * 5;
+        let mut history = VecDeque::<(Instant, EmeterReading)>::new();
+
+        loop {
+            let now = Instant::now();
+            history.push_back((now, self.emeter_reading()?));
+            while history
+                .get(1)
+                .is_some_and(|(taken_at, _)| now.duration_since(*taken_at) >= window)
+            {
+                history.pop_front();
+            }
+
+            let (min, max, sum) = history.iter().fold(
+                (f64::INFINITY, f64::NEG_INFINITY, 0.0),
+                |(min, max, sum), (_, reading)| {
+                    (
+                        min.min(reading.power_w),
+                        max.max(reading.power_w),
+                        sum + reading.power_w,
+                    )
+                },
+            );
+            let average = sum / history.len() as f64;
+            let window_covered = now.duration_since(history[0].0) >= window;
+            if window_covered && max - min <= average.abs() * tolerance_pct / 100.0 {
+                let count = history.len() as f64;
+                let sum = |field: fn(&EmeterReading) -> f64| -> f64 {
+                    history.iter().map(|(_, reading)| field(reading)).sum()
+                };
+                return Ok(EmeterReading {
+                    voltage_v: sum(|reading| reading.voltage_v) / count,
+                    current_a: sum(|reading| reading.current_a) / count,
+                    power_w: average,
+                    total_kwh: history[history.len() - 1].1.total_kwh,
+                });
+            }
+
+            if now >= deadline {
+                Err(TpLinkHs110Error::StabilizationTimeout(window * 5))?;
+            }
+            std::thread::sleep(window / 5);
+        }
+    }
+
+    /// Attempts to measure energy (in Wh) consumed over a given `duration` as a difference of
+    /// total consumed energy readings taken before and after it. Blocks for the full `duration`,
+    /// see [`HS110::measure_energy_in_window_nb`] for a non-blocking alternative.
+    pub fn measure_energy_in_window(&self, duration: Duration) -> Result<f64, TpLinkHs110Error> {
+        let start_reading = self.emeter_reading()?.total_kwh;
+        std::thread::sleep(duration);
+
+        self.measure_energy_in_window_nb(start_reading)
+    }
+
+    /// Attempts to measure energy (in Wh) consumed since a previously captured total consumed
+    /// energy reading (`start_reading`, in kWh, i.e. [`EmeterReading::total_kwh`]). The result is
+    /// negative if the energy counter has been reset in between.
+    pub fn measure_energy_in_window_nb(&self, start_reading: f64) -> Result<f64, TpLinkHs110Error> {
+        Ok((self.emeter_reading()?.total_kwh - start_reading) * 1000.0)
+    }
+
+    /// Attempts to measure how stable a power draw is over a given `window`: energy meter is
+    /// read every [`POWER_SAMPLING_INTERVAL`] (see [`HS110::with_power_sampling_interval`], at
+    /// least once) and failed readings are skipped.
+    /// A high coefficient of variation indicates an intermittent load (like a motor starting and
+    /// stopping), a low one indicates a steady state.
+    ///
+    /// Returns [`TpLinkHs110Error::InsufficientSamples`] if fewer than 3 readings succeeded.
+    pub fn measure_power_stability(
+        &self,
+        window: Duration,
+    ) -> Result<PowerStability, TpLinkHs110Error> {
+        const MIN_SAMPLES: u32 = 3;
+
+        let attempts = (window.as_nanos() / self.power_sampling_interval.as_nanos()).max(1);
+        let mut powers = vec![];
+        for attempt in 0..attempts {
+            if let Ok(reading) = self.emeter_reading() {
+                powers.push(reading.power_w);
+            }
+            if attempt + 1 < attempts {
+                std::thread::sleep(self.power_sampling_interval);
+            }
+        }
+
+        let samples = powers.len() as u32;
+        if samples < MIN_SAMPLES {
+            Err(TpLinkHs110Error::InsufficientSamples {
+                got: samples,
+                required: MIN_SAMPLES,
+            })?;
+        }
+
+        let mean_w = powers.iter().sum::<f64>() / f64::from(samples);
+        let variance = powers
+            .iter()
+            .map(|power| (power - mean_w).powi(2))
+            .sum::<f64>()
+            / f64::from(samples);
+        let std_dev_w = variance.sqrt();
+
+        Ok(PowerStability {
+            mean_w,
+            std_dev_w,
+            cv_pct: if mean_w == 0.0 {
+                0.0
+            } else {
+                std_dev_w / mean_w * 100.0
+            },
+            samples,
+        })
+    }
+
+    /// Attempts to find out whether a power draw is stable over a given `window` (e.g. a device
+    /// has finished starting up), i.e. a coefficient of variation of power readings is below the
+    /// threshold (see [`HS110::with_stability_threshold`]). No load at all is stable too. See
+    /// [`HS110::measure_power_stability`] for how readings are taken.
+    pub fn is_power_stable(&self, window: Duration) -> Result<bool, TpLinkHs110Error> {
+        Ok(self.measure_power_stability(window)?.cv_pct < self.stability_threshold_pct)
+    }
+
+    /// Waits until a power draw becomes stable (see [`HS110::is_power_stable`]) checking it over
+    /// consecutive windows of 4 readings taken [`POWER_SAMPLING_INTERVAL`] apart (see
+    /// [`HS110::with_power_sampling_interval`]), e.g. before cutting power of an idle device.
+    /// Returns [`TpLinkHs110Error::WaitTimeout`] if it doesn't within `timeout` (a window which
+    /// has started before the timeout expired is completed). Any error of a stability check
+    /// (including a transient IO error) aborts the wait immediately and is returned as is.
+    pub fn wait_for_stable_power(&self, timeout: Duration) -> Result<(), TpLinkHs110Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.is_power_stable(self.power_sampling_interval * STABILITY_WAIT_SAMPLES)? {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                Err(TpLinkHs110Error::WaitTimeout(timeout))?;
+            }
+        }
+    }
+
+    /// Attempts to switch power off if the current power draw exceeds `max_watts` (a safety
+    /// cut-off). Returns `true` if power has been switched off, `false` if the power draw is
+    /// within the limit.
+    pub fn power_off_if_overloaded(&self, max_watts: f64) -> Result<bool, TpLinkHs110Error> {
+        if max_watts.is_nan() || max_watts <= 0.0 {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "power limit should be positive, got {max_watts} W"
+            )))?;
+        }
+
+        let power_w = self.emeter_reading()?.power_w;
+        if power_w <= max_watts {
+            return Ok(false);
+        }
+
+        log::warn!(
+            "{}: power draw {power_w} W exceeds {max_watts} W, switching power off",
+            self.socket_addr
+        );
+        self.set_power_state(PowerState::Off)?;
+
+        Ok(true)
+    }
+
+    /// Attempts to roughly estimate a daily energy cost at a given rate (cost per kWh) based on
+    /// the current load, see [`EmeterReading::estimated_daily_cost`].
+    pub fn estimated_daily_cost(&self, rate_per_kwh: f64) -> Result<f64, TpLinkHs110Error> {
+        Ok(self.emeter_reading()?.estimated_daily_cost(rate_per_kwh))
+    }
+
+    /// Attempts to roughly estimate a monthly energy cost based on the current load, see
+    /// [`EmeterReading::estimated_monthly_cost`].
+    pub fn estimated_monthly_cost(&self, rate_per_kwh: f64) -> Result<f64, TpLinkHs110Error> {
+        Ok(self.emeter_reading()?.estimated_monthly_cost(rate_per_kwh))
+    }
+
+    /// Attempts to roughly estimate an annual energy cost based on the current load, see
+    /// [`EmeterReading::estimated_annual_cost`].
+    pub fn estimated_annual_cost(&self, rate_per_kwh: f64) -> Result<f64, TpLinkHs110Error> {
+        Ok(self.emeter_reading()?.estimated_annual_cost(rate_per_kwh))
+    }
+
+    /// Attempts to get typed energy meter readings along with a time the readings were received
+    /// at.
+    pub fn emeter_timestamped(&self) -> Result<TimestampedEmeterReading, TpLinkHs110Error> {
+        let emeter = self.emeter()?;
+        let timestamp = SystemTime::now();
+
+        Ok(EmeterReading::try_from(&emeter)?.with_timestamp(timestamp))
+    }
+
+    /// Helper function which makes energy meter readings contain fields used by both hardware
+    /// versions.
+    fn normalize_emeter(emeter: &mut Value) {
         // Smart plugs of HW version 1 and HW version 2 provide results via different JSON fields
         // and use different units.
         // I.e. one uses "voltage" in Volts and another "voltage_mv" in milliVolts.
@@ -338,7 +1836,7 @@ impl HS110 {
         // As it not clear which version is "better" or more widely used - calculate and provide
         // both fields for both hardware versions:
         #[rustfmt::skip]
-        [
+        const CONVERSIONS: [(&str, &str, f64); 8] = [
             ("voltage_mv", "voltage",    0.001f64),
             ("current_ma", "current",    0.001f64),
             ("power_mw",   "power",      0.001f64),
@@ -347,306 +1845,7265 @@ impl HS110 {
             ("current",    "current_ma", 1000f64),
             ("power",      "power_mw",   1000f64),
             ("total",      "total_wh",   1000f64),
-        ]
-        .iter()
-        .for_each(|(from, to, multiplier)| {
+        ];
+
+        for (from, to, multiplier) in CONVERSIONS {
             if let Some(from) = emeter.get(from) {
                 if emeter.get(to).is_none() {
                     emeter[to] = Value::from(from.as_f64().unwrap_or(0f64) * multiplier);
                 }
             }
-        });
+        }
+    }
 
-        Ok(emeter)
+    /// Attempts to get energy consumed over each day of a given month (days without statistics
+    /// are omitted).
+    pub fn get_daystat(&self, year: u16, month: u8) -> Result<Vec<DayStat>, TpLinkHs110Error> {
+        self.emeter_stats(
+            "get_daystat",
+            json!({"year": year, "month": month}),
+            "day_list",
+        )
     }
 
-    /// Attempts to reboot a smartplug with an optional delay (in seconds).
-    pub fn reboot(&self, delay: Option<u32>) -> Result<(), TpLinkHs110Error> {
-        match serde_json::from_str::<Value>(
-            &self.request(
-                json!({"system": {"reboot": {"delay": delay.unwrap_or(0) }}}).to_string(),
-            )?,
+    /// Attempts to get energy consumed over each month of a given year (months without
+    /// statistics are omitted).
+    pub fn get_monthstat(&self, year: u16) -> Result<Vec<MonthStat>, TpLinkHs110Error> {
+        self.emeter_stats("get_monthstat", json!({"year": year}), "month_list")
+    }
+
+    /// Helper function which gets a list of energy meter statistics. Energy is reported either in
+    /// kWh (`energy`) or in Wh (`energy_wh`) depending on a hardware version, the former is used.
+    fn emeter_stats<T>(
+        &self,
+        command: &'static str,
+        params: Value,
+        list: &'static str,
+    ) -> Result<Vec<T>, TpLinkHs110Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut stats = extract_checked(
+            &self.whole_device_emeter_request(command, params)?,
+            "emeter",
+            command,
         )?
-        .extract_hierarchical(&["system", "reboot", "err_code"])?
-        .as_i64()
-        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
-        {
-            0 => Ok(()),
-            err_code => Err(TpLinkHs110Error::SmartplugErrCode(err_code)),
+        .extract_hierarchical(&[list])?;
+        for stat in stats.as_array_mut().into_iter().flatten() {
+            if stat.get("energy").is_none() {
+                if let Some(energy_wh) = stat.get("energy_wh").and_then(Value::as_f64) {
+                    stat["energy"] = Value::from(energy_wh * 0.001);
+                }
+            }
         }
+
+        Ok(serde_json::from_value(stats)?)
     }
 
-    /// Attempts to perform a factory reset with an optional delay (in seconds).
-    pub fn factory_reset(&self, delay: Option<u32>) -> Result<(), TpLinkHs110Error> {
-        match serde_json::from_str::<Value>(
-            &self.request(
-                json!({"system": {"reset": {"delay": delay.unwrap_or(0) }}}).to_string(),
-            )?,
-        )?
-        .extract_hierarchical(&["system", "reset", "err_code"])?
-        .as_i64()
-        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
-        {
-            0 => Ok(()),
-            err_code => Err(TpLinkHs110Error::SmartplugErrCode(err_code)),
+    /// Attempts to get energy (in kWh) consumed since a given time according to daily
+    /// statistics, i.e. with a granularity of a day: energy consumed during the whole day `since`
+    /// falls on (in smartplug's local time) is included.
+    pub fn emeter_total_since(&self, since: SystemTime) -> Result<f64, TpLinkHs110Error> {
+        let elapsed = SystemTime::now()
+            .duration_since(since)
+            .map_err(|_| TpLinkHs110Error::InvalidParameter("time is in the future".to_string()))?;
+        let now = self.get_time()?;
+        let since = now.before(elapsed)?;
+
+        let mut total = 0.0;
+        let (mut year, mut month) = (since.year, since.month);
+        while (year, month) <= (now.year, now.month) {
+            total += self
+                .get_daystat(year, month)?
+                .iter()
+                .filter(|stat| {
+                    (stat.year, stat.month, stat.day) >= (since.year, since.month, since.mday)
+                })
+                .map(|stat| stat.energy)
+                .sum::<f64>();
+            (year, month) = if month == 12 {
+                (year + 1, 1)
+            } else {
+                (year, month + 1)
+            };
         }
+
+        Ok(total)
     }
-}
 
-trait ExtractHierarchical {
-    fn extract_hierarchical(&self, path: &[&'static str]) -> Result<Value, TpLinkHs110Error>;
-}
+    /// Attempts to get energy (in kWh) consumed during the current month (in smartplug's local
+    /// time).
+    pub fn emeter_total_this_month(&self) -> Result<f64, TpLinkHs110Error> {
+        let now = self.get_time()?;
+        Ok(self
+            .get_daystat(now.year, now.month)?
+            .iter()
+            .map(|stat| stat.energy)
+            .sum())
+    }
 
-impl ExtractHierarchical for Value {
-    /// Attempts to traverse hierarchical structure (JSON object) over provided path and returns
-    /// corresponding sub-object/field.
-    fn extract_hierarchical(&self, path: &[&'static str]) -> Result<Value, TpLinkHs110Error> {
-        let mut current_object = self;
+    /// Attempts to get energy (in kWh) consumed during the current year (in smartplug's local
+    /// time).
+    pub fn emeter_total_this_year(&self) -> Result<f64, TpLinkHs110Error> {
+        let now = self.get_time()?;
+        Ok(self
+            .get_monthstat(now.year)?
+            .iter()
+            .map(|stat| stat.energy)
+            .sum())
+    }
+
+    /// Attempts to get a snapshot of the most commonly used smartplug state (alias, power and LED
+    /// states, energy meter readings and signal strength) using a single request.
+    ///
+    /// Energy meter readings are `None` for smartplugs without an energy meter (HS100).
+    pub fn get_status_summary(&self) -> Result<StatusSummary, TpLinkHs110Error> {
+        let response = serde_json::from_str::<Value>(&self.request(
+            json!({"system": {"get_sysinfo": {}}, "emeter": {"get_realtime": {}}}).to_string(),
+        )?)?;
+
+        let sysinfo = response.extract_hierarchical(&["system", "get_sysinfo"])?;
+        let sysinfo_field = |field| {
+            sysinfo
+                .extract_hierarchical(&[field])?
+                .as_i64()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+        };
+
+        let emeter = match response.extract_hierarchical(&["emeter", "get_realtime"]) {
+            Ok(mut emeter)
+                if emeter.get("err_code").and_then(Value::as_i64) == Some(0)
+                    && sysinfo.get("children").is_none() =>
+            {
+                Self::normalize_emeter(&mut emeter);
+                Some(EmeterReading::try_from(&emeter)?)
+            }
+            _ => None,
+        };
+
+        Ok(StatusSummary {
+            alias: sysinfo
+                .extract_hierarchical(&["alias"])?
+                .as_str()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+                .to_string(),
+            power: (sysinfo_field("relay_state")? == 1).into(),
+            led: (sysinfo_field("led_off")? == 0).into(),
+            emeter,
+            rssi: sysinfo_field("rssi")?
+                .try_into()
+                .map_err(|_| TpLinkHs110Error::UnexpectedValueRepresentation)?,
+        })
+    }
+
+    /// Attempts to get a list of schedule rules.
+    ///
+    /// LED schedule rules are not included, see [`HS110::get_led_schedule_rules`].
+    pub fn get_schedule_rules(&self) -> Result<Vec<ScheduleRule>, TpLinkHs110Error> {
+        self.schedule_rules_of_type(RELAY_RULE_TYPE)
+    }
+
+    /// Attempts to get aggregate usage statistics of the last week (collected by some firmware
+    /// versions only).
+    pub fn get_device_usage_stats(&self) -> Result<DeviceUsageStats, TpLinkHs110Error> {
+        Ok(serde_json::from_value(self.command(
+            "schedule",
+            "get_device_usage",
+            json!({}),
+        )?)?)
+    }
+
+    /// Attempts to find out which schedule rule fires next (according to smartplug's local time).
+    /// Returns `None` if no enabled rule is going to fire. Rules relative to sunrise/sunset are
+    /// not taken into account as their firing time isn't known in advance.
+    pub fn get_schedule_next_event(&self) -> Result<Option<ScheduleEvent>, TpLinkHs110Error> {
+        let rules = self.get_schedule_rules()?;
+        let device_now = self.get_time()?;
+        let now = SystemTime::now();
+
+        Ok(rules
+            .iter()
+            .filter_map(|rule| Some((rule, rule.next_firing_after(&device_now)?)))
+            .min_by_key(|(_, fires_at)| fires_at.seconds_since_epoch())
+            .map(|(rule, fires_at)| ScheduleEvent {
+                rule_id: rule.id.clone(),
+                action: rule.sact,
+                fires_at: now
+                    + Duration::from_secs(
+                        (fires_at.seconds_since_epoch() - device_now.seconds_since_epoch())
+                            .unsigned_abs(),
+                    ),
+            }))
+    }
+
+    /// Attempts to get a list of LED indicator schedule rules. LED schedules are supported only
+    /// by firmware version 2.1 and newer, LED and relay rules share the same list and differ by
+    /// `enable_type` field (see [`HS110::add_led_schedule_rule`]).
+    pub fn get_led_schedule_rules(&self) -> Result<Vec<LedScheduleRule>, TpLinkHs110Error> {
+        self.schedule_rules_of_type(LED_RULE_TYPE)
+    }
+
+    /// Attempts to add an LED indicator schedule rule (requires firmware version 2.1 or newer),
+    /// returns an identifier assigned to the rule by a smartplug.
+    pub fn add_led_schedule_rule(
+        &self,
+        rule: &LedScheduleRule,
+    ) -> Result<String, TpLinkHs110Error> {
+        let mut rule = serde_json::to_value(rule)?;
+        if let Some(rule) = rule.as_object_mut() {
+            rule.remove("id");
+            rule.insert("enable_type".to_string(), LED_RULE_TYPE.into());
+        }
+
+        Ok(self
+            .command("schedule", "add_rule", rule)?
+            .extract_hierarchical(&["id"])?
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Attempts to delete an LED indicator schedule rule with a given identifier.
+    pub fn delete_led_schedule_rule(&self, id: &str) -> Result<(), TpLinkHs110Error> {
+        self.command("schedule", "delete_rule", json!({"id": id}))
+            .map(|_| ())
+    }
+
+    /// Helper function which gets schedule rules having a given `enable_type` (rules without the
+    /// field are relay rules).
+    fn schedule_rules_of_type<T>(&self, rule_type: u64) -> Result<Vec<T>, TpLinkHs110Error>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        rules_of_type(
+            &self.command("schedule", "get_rules", json!({}))?,
+            rule_type,
+        )
+    }
+
+    /// Attempts to add a schedule rule, returns an identifier assigned to the rule by a smartplug.
+    pub fn add_schedule_rule(&self, rule: &ScheduleRule) -> Result<String, TpLinkHs110Error> {
+        Ok(self
+            .command("schedule", "add_rule", Self::schedule_rule_params(rule)?)?
+            .extract_hierarchical(&["id"])?
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Attempts to add a number of schedule rules, returns identifiers assigned to the rules by
+    /// a smartplug (in the same order as `rules`).
+    ///
+    /// Rules are added with a single `add_rules` request if firmware supports it, otherwise (if
+    /// a smartplug reports an error or doesn't recognize the command) one by one, see
+    /// [`HS110::add_schedule_rule`].
+    pub fn bulk_set_schedule_rules(
+        &self,
+        rules: &[ScheduleRule],
+    ) -> Result<Vec<String>, TpLinkHs110Error> {
+        if rules.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let rule_list = rules
+            .iter()
+            .map(Self::schedule_rule_params)
+            .collect::<Result<Vec<_>, _>>()?;
+        match self.command("schedule", "add_rules", json!({"rule_list": rule_list})) {
+            Ok(response) => {
+                let ids = response
+                    .extract_hierarchical(&["id_list"])?
+                    .as_array()
+                    .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+                    .iter()
+                    .map(|id| id.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?;
+                if ids.len() != rules.len() {
+                    Err(TpLinkHs110Error::UnexpectedValueRepresentation)?;
+                }
+                Ok(ids)
+            }
+            Err(err) if is_unsupported_command(&err) => rules
+                .iter()
+                .map(|rule| self.add_schedule_rule(rule))
+                .collect(),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Attempts to delete a schedule rule with a given identifier.
+    pub fn delete_schedule_rule(&self, id: &str) -> Result<(), TpLinkHs110Error> {
+        self.command("schedule", "delete_rule", json!({"id": id}))
+            .map(|_| ())
+    }
+
+    /// Attempts to delete a number of schedule rules with given identifiers, using a single
+    /// `delete_rules` request if firmware supports it (see [`HS110::bulk_set_schedule_rules`]).
+    pub fn bulk_delete_schedule_rules(&self, ids: &[&str]) -> Result<(), TpLinkHs110Error> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        match self.command("schedule", "delete_rules", json!({"id_list": ids})) {
+            Ok(_) => Ok(()),
+            Err(err) if is_unsupported_command(&err) => {
+                ids.iter().try_for_each(|id| self.delete_schedule_rule(id))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Helper function which represents a schedule rule as parameters of `add_rule` command.
+    fn schedule_rule_params(rule: &ScheduleRule) -> Result<Value, TpLinkHs110Error> {
+        let mut rule = serde_json::to_value(rule)?;
+        if let Some(rule) = rule.as_object_mut() {
+            rule.remove("id");
+        }
+
+        Ok(rule)
+    }
+
+    /// Attempts to delete all schedule rules (LED schedule rules included, as they share the same
+    /// list).
+    pub fn delete_all_schedule_rules(&self) -> Result<(), TpLinkHs110Error> {
+        self.command("schedule", "delete_all_rules", json!({}))
+            .map(|_| ())
+    }
+
+    /// Helper function which replaces existing schedule rules with given ones, LED schedule rules
+    /// are kept intact.
+    fn replace_schedule_rules(&self, rules: &[ScheduleRule]) -> Result<(), TpLinkHs110Error> {
+        let existing = self.get_schedule_rules()?;
+        self.bulk_delete_schedule_rules(
+            &existing
+                .iter()
+                .map(|rule| rule.id.as_str())
+                .collect::<Vec<_>>(),
+        )?;
+
+        rules
+            .iter()
+            .try_for_each(|rule| self.add_schedule_rule(rule).map(|_| ()))
+    }
+
+    /// Attempts to count schedule, countdown and anti-theft rules. Limits are reported only by
+    /// some firmware versions (as `max_rules` field), so they are optional.
+    pub fn get_rules_count(&self) -> Result<RulesCount, TpLinkHs110Error> {
+        let rules = |module| -> Result<(usize, Option<usize>), TpLinkHs110Error> {
+            let response = self.command(module, "get_rules", json!({}))?;
+            Ok((
+                response
+                    .extract_hierarchical(&["rule_list"])?
+                    .as_array()
+                    .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+                    .len(),
+                response
+                    .get("max_rules")
+                    .and_then(Value::as_u64)
+                    .map(|max_rules| max_rules as usize),
+            ))
+        };
+
+        let (schedule, max_schedule) = rules("schedule")?;
+        let (countdown, max_countdown) = rules("count_down")?;
+        let (antitheft, _) = rules("anti_theft")?;
+
+        Ok(RulesCount {
+            schedule,
+            countdown,
+            antitheft,
+            max_schedule,
+            max_countdown,
+        })
+    }
+
+    /// Attempts to get schedule rules represented as cron expressions (one per rule), see
+    /// [`ScheduleRule::to_cron`]. Rules which never fire (no weekdays selected) are omitted.
+    pub fn get_schedules_as_cron(&self) -> Result<Vec<String>, TpLinkHs110Error> {
+        Ok(self
+            .get_schedule_rules()?
+            .iter()
+            .filter_map(ScheduleRule::to_cron)
+            .collect())
+    }
+
+    /// Attempts to get a list of countdown rules.
+    pub fn get_countdown_rules(&self) -> Result<Vec<CountdownRule>, TpLinkHs110Error> {
+        Ok(serde_json::from_value(
+            self.command("count_down", "get_rules", json!({}))?
+                .extract_hierarchical(&["rule_list"])?,
+        )?)
+    }
+
+    /// Attempts to get a list of anti-theft (away mode) rules.
+    pub fn get_antitheft_rules(&self) -> Result<Vec<AntitheftRule>, TpLinkHs110Error> {
+        Ok(serde_json::from_value(
+            self.command("anti_theft", "get_rules", json!({}))?
+                .extract_hierarchical(&["rule_list"])?,
+        )?)
+    }
+
+    /// Attempts to get schedule, countdown and anti-theft rules at once.
+    ///
+    /// Rules are requested with a single (batch) request, if a smartplug fails to answer it
+    /// properly, rules of each type are requested separately.
+    pub fn all_rules_summary(&self) -> Result<RulesSummary, TpLinkHs110Error> {
+        let request = json!({
+            "schedule": {"get_rules": {}},
+            "count_down": {"get_rules": {}},
+            "anti_theft": {"get_rules": {}},
+        });
+        let batch = || -> Result<RulesSummary, TpLinkHs110Error> {
+            let response = serde_json::from_str::<Value>(&self.request(request.to_string())?)?;
+            let rule_list = |module| {
+                extract_checked(&response, module, "get_rules")?
+                    .extract_hierarchical(&["rule_list"])
+            };
+
+            Ok(RulesSummary {
+                schedule_rules: rules_of_type(
+                    &extract_checked(&response, "schedule", "get_rules")?,
+                    RELAY_RULE_TYPE,
+                )?,
+                countdown_rules: serde_json::from_value(rule_list("count_down")?)?,
+                antitheft_rules: serde_json::from_value(rule_list("anti_theft")?)?,
+            })
+        };
+
+        match batch() {
+            Err(err) if is_unsupported_command(&err) => Ok(RulesSummary {
+                schedule_rules: self.get_schedule_rules()?,
+                countdown_rules: self.get_countdown_rules()?,
+                antitheft_rules: self.get_antitheft_rules()?,
+            }),
+            summary => summary,
+        }
+    }
+
+    /// Attempts to get time left until an active (enabled) countdown rule fires. Returns `None`
+    /// if there is no active countdown rule.
+    pub fn countdown_remaining(&self) -> Result<Option<Duration>, TpLinkHs110Error> {
+        Ok(self
+            .get_countdown_rules()?
+            .into_iter()
+            .find(|rule| rule.enable)
+            .map(|rule| Duration::from_secs(rule.remain.unwrap_or(rule.delay).into())))
+    }
+
+    /// Attempts to get time left until a mode a smartplug is currently in ends, i.e. until an
+    /// active countdown rule fires. Returns `None` if a smartplug isn't in a countdown mode (end
+    /// of a schedule mode isn't known in advance).
+    ///
+    /// Unless a smartplug reports remaining time itself, it is computed as a rule delay minus time
+    /// passed since the last power relay switch (`on_time`), which is an approximation subject
+    /// to clock drift and to the relay being switched manually meanwhile.
+    pub fn get_time_remaining_in_active_mode(&self) -> Result<Option<Duration>, TpLinkHs110Error> {
+        let sysinfo = self
+            .info()?
+            .extract_hierarchical(&["system", "get_sysinfo"])?;
+        let active_mode =
+            serde_json::from_value::<ActiveMode>(sysinfo.extract_hierarchical(&["active_mode"])?)?;
+        if active_mode != ActiveMode::CountDown {
+            return Ok(None);
+        }
+
+        let on_time = sysinfo
+            .extract_hierarchical(&["on_time"])?
+            .as_u64()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?;
+        Ok(self
+            .get_countdown_rules()?
+            .into_iter()
+            .find(|rule| rule.enable)
+            .map(|rule| {
+                Duration::from_secs(
+                    rule.remain
+                        .map_or(u64::from(rule.delay).saturating_sub(on_time), u64::from),
+                )
+            }))
+    }
+
+    /// Attempts to add a countdown rule, returns an identifier assigned to the rule by a smartplug.
+    ///
+    /// Smartplugs support only a single countdown rule, so adding a rule fails while another one
+    /// exists (see [`HS110::delete_all_countdown_rules`]).
+    pub fn add_countdown_rule(&self, rule: &CountdownRule) -> Result<String, TpLinkHs110Error> {
+        let mut rule = serde_json::to_value(rule)?;
+        if let Some(rule) = rule.as_object_mut() {
+            rule.remove("id");
+            rule.remove("remain");
+        }
+
+        Ok(self
+            .command("count_down", "add_rule", rule)?
+            .extract_hierarchical(&["id"])?
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Attempts to delete all countdown rules.
+    pub fn delete_all_countdown_rules(&self) -> Result<(), TpLinkHs110Error> {
+        self.command("count_down", "delete_all_rules", json!({}))
+            .map(|_| ())
+    }
+
+    /// Attempts to schedule powering off a smartplug after a given `delay`, e.g. to let a
+    /// connected device complete its shutdown. If `then_power_off` is `false`, the smartplug is
+    /// powered on after the delay instead.
+    ///
+    /// Any existing countdown rule is replaced.
+    pub fn graceful_shutdown(
+        &self,
+        delay: Duration,
+        then_power_off: bool,
+    ) -> Result<(), TpLinkHs110Error> {
+        self.replace_countdown_rule("graceful shutdown", delay, (!then_power_off).into())
+    }
+
+    /// Attempts to switch a smartplug to the opposite power state for a given `duration` (at
+    /// least a second) and then restore the original state, e.g. to run a coffee maker for 30
+    /// minutes. Restoring is done by a countdown rule (any existing one is replaced), so it
+    /// happens even if the caller goes away. Blocks until the original state is restored.
+    ///
+    /// Returns [`TpLinkHs110Error::WaitTimeout`] if the state isn't restored within 30 seconds
+    /// after `duration` elapses.
+    pub fn set_countdown_then_restore(&self, duration: Duration) -> Result<(), TpLinkHs110Error> {
+        if duration < Duration::from_secs(1) {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "duration {duration:?} is shorter than a second"
+            )))?;
+        }
+
+        let original = self.power_state()?;
+        self.replace_countdown_rule("restore power state", duration, original)?;
+        self.set_power_state(!original)?;
+
+        let timeout = duration + Duration::from_secs(30);
+        let deadline = Instant::now() + timeout;
+        std::thread::sleep(duration);
+        while self.power_state()? != original {
+            if Instant::now() >= deadline {
+                Err(TpLinkHs110Error::WaitTimeout(timeout))?;
+            }
+            std::thread::sleep(REACHABILITY_POLL_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    /// Attempts to schedule switching power relay to a `target` state at a given time.
+    ///
+    /// Changes within [`COUNTDOWN_RULE_MAX_DELAY`] are scheduled with a countdown rule (precise to
+    /// a second, fails if another countdown rule exists), later ones with a non-repeating schedule
+    /// rule (precise to a minute). Schedule rules fire on smartplug's local time, so `at` is
+    /// converted relative to the time reported by the smartplug.
+    ///
+    /// Returns [`TpLinkHs110Error::ScheduleTimeInPast`] if `at` has already passed.
+    pub fn schedule_state_change(
+        &self,
+        target: PowerState,
+        at: SystemTime,
+    ) -> Result<(), TpLinkHs110Error> {
+        let delay = at
+            .duration_since(SystemTime::now())
+            .map_err(|_| TpLinkHs110Error::ScheduleTimeInPast)?;
+
+        if delay <= COUNTDOWN_RULE_MAX_DELAY {
+            self.add_countdown_rule(&CountdownRule {
+                id: String::new(),
+                name: "scheduled state change".to_string(),
+                enable: true,
+                delay: u32::try_from(delay.as_secs()).map_err(|_| {
+                    TpLinkHs110Error::InvalidParameter(format!("delay {delay:?} is too long"))
+                })?,
+                act: target,
+                remain: None,
+            })?;
+        } else {
+            // Schedule rules fire at the beginning of a minute, so round to the nearest one.
+            let at = self.get_time()?.after(delay + Duration::from_secs(30))?;
+            let mut wday = [false; 7];
+            wday[usize::from(at.weekday())] = true;
+            self.add_schedule_rule(&ScheduleRule {
+                id: String::new(),
+                name: "scheduled state change".to_string(),
+                enable: true,
+                wday,
+                stime_opt: ScheduleTimeOption::Time,
+                smin: u16::from(at.hour) * 60 + u16::from(at.min),
+                soffset: None,
+                sact: target,
+                repeat: false,
+                year: Some(at.year),
+                month: Some(at.month),
+                day: Some(at.mday),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Helper function which replaces any existing countdown rule with a new one switching power
+    /// relay to a given state after a delay.
+    fn replace_countdown_rule(
+        &self,
+        name: &str,
+        delay: Duration,
+        act: PowerState,
+    ) -> Result<(), TpLinkHs110Error> {
+        let delay = u32::try_from(delay.as_secs()).map_err(|_| {
+            TpLinkHs110Error::InvalidParameter(format!("delay {delay:?} is too long"))
+        })?;
+
+        self.delete_all_countdown_rules()?;
+        self.add_countdown_rule(&CountdownRule {
+            id: String::new(),
+            name: name.to_string(),
+            enable: true,
+            delay,
+            act,
+            remain: None,
+        })?;
+
+        Ok(())
+    }
+
+    /// Attempts to export a full smartplug state (system information, cloud information,
+    /// schedule and countdown rules and energy meter readings) as a pretty printed JSON document:
+    /// ```text
+    /// {
+    ///   "export_version": 1,
+    ///   "sysinfo": { ... },
+    ///   "cloudinfo": { ... },
+    ///   "schedule_rules": [ ... ],
+    ///   "countdown_rules": [ ... ],
+    ///   "emeter": { ... }
+    /// }
+    /// ```
+    /// `emeter` is `null` for smartplugs without an energy meter.
+    pub fn export_json(&self) -> Result<String, TpLinkHs110Error> {
+        Ok(serde_json::to_string_pretty(&json!({
+            "export_version": EXPORT_VERSION,
+            "sysinfo": self.command("system", "get_sysinfo", json!({}))?,
+            "cloudinfo": self.command("cnCloud", "get_info", json!({}))?,
+            "schedule_rules": self.get_schedule_rules()?,
+            "countdown_rules": self.get_countdown_rules()?,
+            "emeter": self
+                .command("emeter", "get_realtime", json!({}))
+                .ok()
+                .map(|mut emeter| {
+                    Self::normalize_emeter(&mut emeter);
+                    emeter
+                }),
+        }))?)
+    }
+
+    /// Attempts to apply a state exported with [`HS110::export_json`] to a smartplug. Only an
+    /// alias, LED state and schedule rules are applied (existing schedule rules are replaced,
+    /// LED schedule rules are kept), everything else can't be set remotely and is skipped.
+    pub fn import_json(&self, json: &str) -> Result<(), TpLinkHs110Error> {
+        let export = serde_json::from_str::<Value>(json)?;
+        match export.get("export_version").and_then(Value::as_u64) {
+            Some(EXPORT_VERSION) => (),
+            version => Err(TpLinkHs110Error::InvalidParameter(format!(
+                "unsupported export version: {version:?}"
+            )))?,
+        }
+
+        if let Some(alias) = export.pointer("/sysinfo/alias").and_then(Value::as_str) {
+            self.set_alias(alias)?;
+        }
+        if let Some(led_off) = export.pointer("/sysinfo/led_off").and_then(Value::as_u64) {
+            self.set_led_state((led_off == 0).into())?;
+        }
+        if let Some(rules) = export.get("schedule_rules") {
+            let rules = serde_json::from_value::<Vec<ScheduleRule>>(rules.clone())?;
+            self.replace_schedule_rules(&rules)?;
+        }
+
+        Ok(())
+    }
+
+    /// Attempts to capture a smartplug configuration which is tied to a place rather than to
+    /// a device (an alias, a location and schedule rules), so it could be moved to another
+    /// smartplug with [`HS110::restore`].
+    pub fn snapshot(&self) -> Result<DeviceSnapshot, TpLinkHs110Error> {
+        let sysinfo = self
+            .info()?
+            .extract_hierarchical(&["system", "get_sysinfo"])?;
+        let coordinate = |name: &str| {
+            sysinfo.get(name).and_then(Value::as_f64).or_else(|| {
+                sysinfo
+                    .get(format!("{name}_i"))
+                    .and_then(Value::as_f64)
+                    .map(|value| value / 10_000.0)
+            })
+        };
+
+        Ok(DeviceSnapshot {
+            alias: sysinfo
+                .get("alias")
+                .and_then(Value::as_str)
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+                .to_string(),
+            location: coordinate("latitude").zip(coordinate("longitude")).map(
+                |(latitude, longitude)| Location {
+                    latitude,
+                    longitude,
+                },
+            ),
+            schedule_rules: self.get_schedule_rules()?,
+        })
+    }
+
+    /// Attempts to apply a configuration captured with [`HS110::snapshot`] (existing schedule
+    /// rules are replaced, LED schedule rules are kept). Every part of a snapshot is applied
+    /// independently, if some of them fail [`TpLinkHs110Error::PartialRestore`] is returned.
+    pub fn restore(&self, snapshot: &DeviceSnapshot) -> Result<(), TpLinkHs110Error> {
+        let mut errors = vec![];
+        if let Err(err) = self.set_alias(&snapshot.alias) {
+            errors.push(("alias".to_string(), err));
+        }
+        if let Some(location) = snapshot.location {
+            let result = self.command(
+                "system",
+                "set_dev_location",
+                json!({"latitude": location.latitude, "longitude": location.longitude}),
+            );
+            if let Err(err) = result {
+                errors.push(("location".to_string(), err));
+            }
+        }
+        if let Err(err) = self.replace_schedule_rules(&snapshot.schedule_rules) {
+            errors.push(("schedule_rules".to_string(), err));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(TpLinkHs110Error::PartialRestore(errors))
+        }
+    }
+
+    /// Attempts to exchange configurations (see [`HS110::snapshot`]) of two smartplugs, e.g. once
+    /// they are physically swapped. Both smartplugs are snapshotted before anything is changed.
+    /// If some parts fail to be restored, [`TpLinkHs110Error::PartialRestore`] is returned with
+    /// field names prefixed by a smartplug address (e.g. `192.168.0.10:9999 alias`).
+    pub fn swap_devices(&self, other: &HS110) -> Result<(), TpLinkHs110Error> {
+        let (this_snapshot, other_snapshot) = (self.snapshot()?, other.snapshot()?);
+
+        let errors = [(self, &other_snapshot), (other, &this_snapshot)]
+            .into_iter()
+            .flat_map(|(smartplug, snapshot)| match smartplug.restore(snapshot) {
+                Ok(()) => vec![],
+                Err(TpLinkHs110Error::PartialRestore(errors)) => errors
+                    .into_iter()
+                    .map(|(field, err)| (format!("{} {field}", smartplug.socket_addr), err))
+                    .collect(),
+                Err(err) => vec![(smartplug.socket_addr.to_string(), err)],
+            })
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(TpLinkHs110Error::PartialRestore(errors))
+        }
+    }
+
+    /// Attempts to find out what [`HS110::swap_devices`] would change without changing anything.
+    pub fn swap_devices_dry_run(&self, other: &HS110) -> Result<SwapPlan, TpLinkHs110Error> {
+        let (this, other) = (self.snapshot()?, other.snapshot()?);
+
+        Ok(SwapPlan {
+            changed_fields: this.changed_fields(&other),
+            this,
+            other,
+        })
+    }
+
+    /// Attempts to measure a round-trip time of a single request (from establishing a connection
+    /// till receiving a complete response).
+    pub fn ping(&self) -> Result<Duration, TpLinkHs110Error> {
+        let request = json!({"system": {"get_sysinfo": {}}}).to_string();
+        let started = Instant::now();
+        self.request(request)?;
+
+        Ok(started.elapsed())
+    }
+
+    /// Attempts to measure a full round-trip time of a request at the application layer:
+    /// a system information request is encrypted and sent, and a response is received, decrypted
+    /// and validated (it should be a successful `get_sysinfo` response).
+    ///
+    /// Durations of the last [`LOOPBACK_HISTORY_LEN`] successful tests are kept, see
+    /// [`HS110::avg_loopback_latency`].
+    pub fn perform_loopback_test(&self) -> Result<Duration, TpLinkHs110Error> {
+        let started = Instant::now();
+        self.command("system", "get_sysinfo", json!({}))?;
+        let latency = started.elapsed();
+
+        let mut latencies = self
+            .loopback_latencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if latencies.len() == LOOPBACK_HISTORY_LEN {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+
+        Ok(latency)
+    }
+
+    /// Returns an average duration of the last successful loopback tests (see
+    /// [`HS110::perform_loopback_test`]), `None` if there were none.
+    pub fn avg_loopback_latency(&self) -> Option<Duration> {
+        let latencies = self
+            .loopback_latencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        (!latencies.is_empty()).then(|| latencies.iter().sum::<Duration>() / latencies.len() as u32)
+    }
+
+    /// Attempts to measure a distribution of round-trip times (see [`HS110::ping`]) over
+    /// a given number of sequential requests. Failed requests are counted, but don't contribute
+    /// to the distribution. An error is returned if every request has failed.
+    pub fn measure_latency_histogram(
+        &self,
+        samples: u32,
+    ) -> Result<LatencyHistogram, TpLinkHs110Error> {
+        if samples == 0 {
+            Err(TpLinkHs110Error::InvalidParameter(
+                "number of samples should be positive".to_string(),
+            ))?;
+        }
+
+        let mut latencies_ms = vec![];
+        let mut last_error = None;
+        for _ in 0..samples {
+            match self.ping() {
+                Ok(latency) => latencies_ms.push(latency.as_secs_f64() * 1000.0),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        if latencies_ms.is_empty() {
+            Err(last_error.expect("at least one sample has failed"))?;
+        }
+        latencies_ms.sort_by(f64::total_cmp);
+
+        // Nearest-rank percentile.
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * latencies_ms.len() as f64).ceil() as usize;
+            latencies_ms[rank.clamp(1, latencies_ms.len()) - 1]
+        };
+
+        Ok(LatencyHistogram {
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p99_ms: percentile(99.0),
+            min_ms: latencies_ms[0],
+            max_ms: latencies_ms[latencies_ms.len() - 1],
+            samples,
+            errors: samples - latencies_ms.len() as u32,
+        })
+    }
+
+    /// Attempts to get smartplug's local date and time.
+    pub fn get_time(&self) -> Result<DeviceTime, TpLinkHs110Error> {
+        Ok(serde_json::from_value(self.command(
+            "time",
+            "get_time",
+            json!({}),
+        )?)?)
+    }
+
+    /// Attempts to get smartplug's time zone index (an index in the time zone list of the Kasa
+    /// app, see also `timezone::timezone_by_index` with `chrono` feature enabled).
+    pub fn get_timezone_index(&self) -> Result<u8, TpLinkHs110Error> {
+        self.command("time", "get_timezone", json!({}))?
+            .extract_hierarchical(&["index"])?
+            .as_u64()
+            .and_then(|index| u8::try_from(index).ok())
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+    }
+
+    /// Attempts to collect everything known about a smartplug into a single report (e.g. to be
+    /// attached to a bug report). Every part of the report is collected independently, failures
+    /// are recorded in [`DiagnosticReport::collect_errors`] instead of being returned. An error is
+    /// returned only if nothing could be collected at all.
+    pub fn diagnose(&self) -> Result<DiagnosticReport, TpLinkHs110Error> {
+        fn collect<T>(
+            result: Result<T, TpLinkHs110Error>,
+            errors: &mut Vec<TpLinkHs110Error>,
+        ) -> Option<T> {
+            result.map_err(|err| errors.push(err)).ok()
+        }
+
+        let mut errors = vec![];
+        let sysinfo = collect(
+            self.command("system", "get_sysinfo", json!({})),
+            &mut errors,
+        );
+        let report = DiagnosticReport {
+            firmware_version: sysinfo
+                .as_ref()
+                .and_then(|sysinfo| sysinfo.get("sw_ver"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            sysinfo,
+            cloudinfo: collect(self.cloud_info_typed(), &mut errors),
+            emeter: collect(self.emeter_reading(), &mut errors),
+            schedule_rules: collect(self.get_schedule_rules(), &mut errors),
+            countdown_rules: collect(self.get_countdown_rules(), &mut errors),
+            time: collect(self.get_time(), &mut errors),
+            network: collect(self.command("netif", "get_stainfo", json!({})), &mut errors),
+            collect_errors: vec![],
+        };
+
+        if report.sysinfo.is_none()
+            && report.cloudinfo.is_none()
+            && report.emeter.is_none()
+            && report.schedule_rules.is_none()
+            && report.countdown_rules.is_none()
+            && report.time.is_none()
+            && report.network.is_none()
+        {
+            Err(errors.remove(0))?;
+        }
+
+        Ok(DiagnosticReport {
+            collect_errors: errors,
+            ..report
+        })
+    }
+
+    /// Checks whether a smartplug responds to requests.
+    pub fn is_online(&self) -> bool {
+        self.ping().is_ok()
+    }
+
+    /// Attempts to check whether a smartplug is currently connected to TP-Link cloud.
+    pub fn is_cloud_connected(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self.cloud_info_typed()?.cld_connection)
+    }
+
+    /// Attempts to check whether a firmware update is available (according to TP-Link cloud, so
+    /// a smartplug needs to be connected to it).
+    pub fn check_firmware_update(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self
+            .command("cnCloud", "get_intl_fw_list", json!({}))?
+            .extract_hierarchical(&["fw_list"])?
+            .as_array()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            .is_empty()
+            .not())
+    }
+
+    /// Performs a number of health checks of a smartplug (reachability, power relay, energy
+    /// meter, cloud connection and firmware being up to date). Every check is performed
+    /// independently, failures are recorded in [`HealthReport::errors`], so a report is returned
+    /// even if some (or all) of the checks failed.
+    pub fn health_check(&self) -> Result<HealthReport, TpLinkHs110Error> {
+        fn check<T>(result: Result<T, TpLinkHs110Error>, errors: &mut Vec<String>) -> Option<T> {
+            result.map_err(|err| errors.push(err.to_string())).ok()
+        }
+
+        let mut errors = vec![];
+        let latency = check(self.ping(), &mut errors);
+        let relay_ok = check(self.power_state(), &mut errors).is_some();
+        let emeter_ok = check(self.emeter_reading(), &mut errors).is_some();
+        let cloud_ok = check(self.is_cloud_connected(), &mut errors) == Some(true);
+        let firmware_current = check(self.check_firmware_update(), &mut errors).map(Not::not);
+
+        Ok(HealthReport {
+            reachable: latency.is_some(),
+            relay_ok,
+            emeter_ok,
+            cloud_ok,
+            firmware_current,
+            latency_ms: latency.map(|latency| latency.as_secs_f64() * 1000.0),
+            errors,
+        })
+    }
+
+    /// Attempts to test connectivity with a smartplug layer by layer: TCP reachability and
+    /// protocol handshake (a system information request), and if `full` is set, also device
+    /// identity (see [`HS110::verify_connectivity`]), cloud connection and time synchronization
+    /// (see [`ConnectivityTest::ntp_ok`]).
+    ///
+    /// Every check is performed independently, failures are recorded in
+    /// [`ConnectivityTest::errors`] under a check name (`tcp`, `protocol`, `device_id`, `cloud`,
+    /// `ntp`), so a result is returned even if some (or all) of the checks failed. Checks which
+    /// are skipped are reported as failed without an error.
+    pub fn test_connectivity(&self, full: bool) -> Result<ConnectivityTest, TpLinkHs110Error> {
+        fn check<T, E: Display>(
+            name: &str,
+            result: Result<T, E>,
+            errors: &mut HashMap<String, String>,
+        ) -> bool {
+            result
+                .map_err(|err| errors.insert(name.to_string(), err.to_string()))
+                .is_ok()
+        }
+
+        let mut errors = HashMap::new();
+        let tcp_ok = check("tcp", self.connect(), &mut errors);
+        let protocol_ok = check(
+            "protocol",
+            self.info()
+                .and_then(|info| info.extract_hierarchical(&["system", "get_sysinfo"])),
+            &mut errors,
+        );
+        if !full {
+            return Ok(ConnectivityTest {
+                tcp_ok,
+                protocol_ok,
+                device_id_ok: false,
+                cloud_ok: false,
+                ntp_ok: false,
+                errors,
+            });
+        }
+
+        let device_id_ok = check("device_id", self.verify_connectivity(), &mut errors);
+        let cloud_ok = check(
+            "cloud",
+            self.is_cloud_connected()
+                .map_err(|err| err.to_string())
+                .and_then(|connected| {
+                    connected
+                        .then_some(())
+                        .ok_or("smartplug isn't connected to the cloud".to_string())
+                }),
+            &mut errors,
+        );
+        let ntp_ok = check("ntp", self.check_clock_sync(), &mut errors);
+
+        Ok(ConnectivityTest {
+            tcp_ok,
+            protocol_ok,
+            device_id_ok,
+            cloud_ok,
+            ntp_ok,
+            errors,
+        })
+    }
+
+    /// Helper function which makes sure a smartplug clock differs from UTC by an offset of
+    /// a configured smartplug time zone, give or take [`NTP_SYNC_TOLERANCE`].
+    #[cfg(feature = "chrono")]
+    fn check_clock_sync(&self) -> Result<(), String> {
+        let time = self.get_time().map_err(|err| err.to_string())?;
+        let timezone_index = self.get_timezone_index().map_err(|err| err.to_string())?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|err| err.to_string())?
+            .as_secs() as i64;
+        let timezone_offset =
+            timezone::utc_offset(timezone_index, now).map_err(|err| err.to_string())?;
+
+        let deviation = time.seconds_since_epoch() - now - timezone_offset;
+        if deviation.unsigned_abs() > NTP_SYNC_TOLERANCE.as_secs() {
+            Err(format!(
+                "smartplug clock is {deviation} seconds off its time zone \
+                (UTC{timezone_offset:+} seconds)"
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Helper function which reports that clock synchronization couldn't be checked: time zones
+    /// of smartplugs are known only with `chrono` feature.
+    #[cfg(not(feature = "chrono"))]
+    #[allow(clippy::unused_self)]
+    fn check_clock_sync(&self) -> Result<(), String> {
+        Err("checking clock synchronization requires `chrono` feature".to_string())
+    }
+
+    /// Attempts to get over/under-voltage protection configuration.
+    ///
+    /// Voltage protection is supported only by some firmware versions, others report an error
+    /// ([`TpLinkHs110Error::SmartplugErrCode`]).
+    pub fn get_voltage_protection(&self) -> Result<VoltageProtection, TpLinkHs110Error> {
+        let response = self.command("emeter", "get_voltage_protection", json!({}))?;
+        let field = |field| {
+            response
+                .extract_hierarchical(&[field])?
+                .as_u64()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+        };
+
+        Ok(VoltageProtection {
+            min_mv: field("min_mv")?
+                .try_into()
+                .map_err(|_| TpLinkHs110Error::UnexpectedValueRepresentation)?,
+            max_mv: field("max_mv")?
+                .try_into()
+                .map_err(|_| TpLinkHs110Error::UnexpectedValueRepresentation)?,
+            enabled: field("enable")? != 0,
+        })
+    }
+
+    /// Attempts to configure over/under-voltage protection. Voltage thresholds are given in
+    /// milliVolts and should be within [`VoltageProtection::MIN_MV`] and
+    /// [`VoltageProtection::MAX_MV`].
+    ///
+    /// Voltage protection is supported only by some firmware versions, others report an error
+    /// ([`TpLinkHs110Error::SmartplugErrCode`]).
+    pub fn set_voltage_protection(
+        &self,
+        min_mv: u32,
+        max_mv: u32,
+        enabled: bool,
+    ) -> Result<(), TpLinkHs110Error> {
+        let range = VoltageProtection::MIN_MV..=VoltageProtection::MAX_MV;
+        if !range.contains(&min_mv) || !range.contains(&max_mv) {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "voltage thresholds should be within {}..={} mV",
+                range.start(),
+                range.end()
+            )))?;
+        }
+        if min_mv >= max_mv {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "minimum voltage ({min_mv} mV) should be less than maximum voltage ({max_mv} mV)"
+            )))?;
+        }
+
+        self.command(
+            "emeter",
+            "set_voltage_protection",
+            json!({"min_mv": min_mv, "max_mv": max_mv, "enable": u8::from(enabled)}),
+        )?;
+        Ok(())
+    }
+
+    /// Attempts to get over-current protection configuration.
+    ///
+    /// The command name is unverified, see [`HS110::set_overcurrent_protection`].
+    /// [`TpLinkHs110Error::FeatureNotSupported`] is returned if a smartplug doesn't recognize it.
+    pub fn get_overcurrent_protection(&self) -> Result<OvercurrentConfig, TpLinkHs110Error> {
+        match self.command("emeter", "get_overcurrent_protection", json!({})) {
+            Ok(response) => Ok(serde_json::from_value(response)?),
+            Err(err) if is_unsupported_command(&err) => Err(TpLinkHs110Error::FeatureNotSupported),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Attempts to configure over-current protection, i.e. opening power relay once current
+    /// exceeds a threshold. The threshold is given in milliAmperes and should not exceed
+    /// [`OvercurrentConfig::MAX_MA`], if it is `None` a previously configured threshold is kept.
+    ///
+    /// The command names are unverified: they aren't covered by public protocol descriptions and
+    /// only follow the naming of [`HS110::set_voltage_protection`] (`emeter` namespace,
+    /// `enable`/`max_ma` fields). [`TpLinkHs110Error::FeatureNotSupported`] is returned if a
+    /// smartplug doesn't recognize them, which is the case for all the firmware tested so far.
+    pub fn set_overcurrent_protection(
+        &self,
+        enabled: bool,
+        threshold_ma: Option<u32>,
+    ) -> Result<(), TpLinkHs110Error> {
+        let mut params = json!({"enable": u8::from(enabled)});
+        if let Some(threshold_ma) = threshold_ma {
+            if threshold_ma > OvercurrentConfig::MAX_MA {
+                Err(TpLinkHs110Error::InvalidParameter(format!(
+                    "current threshold should be within 0..={} mA",
+                    OvercurrentConfig::MAX_MA
+                )))?;
+            }
+            params["max_ma"] = threshold_ma.into();
+        }
+
+        match self.command("emeter", "set_overcurrent_protection", params) {
+            Ok(_) => Ok(()),
+            Err(err) if is_unsupported_command(&err) => Err(TpLinkHs110Error::FeatureNotSupported),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Attempts to find out whether a smartplug switches power relay off automatically once
+    /// current draw exceeds hardware limits, see [`HS110::set_auto_off_on_overload`].
+    pub fn get_auto_off_on_overload(&self) -> Result<bool, TpLinkHs110Error> {
+        for command in OVERLOAD_PROTECTION_COMMANDS.map(|(get, _)| get) {
+            match self.command("emeter", command, json!({})) {
+                Ok(response) => {
+                    return Ok(response
+                        .extract_hierarchical(&["enable"])?
+                        .as_u64()
+                        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+                        != 0)
+                }
+                Err(err) if is_unsupported_command(&err) => {
+                    log::debug!("{command} is not supported: {err}");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(TpLinkHs110Error::FeatureNotSupported)
+    }
+
+    /// Attempts to enable or disable automatic switching power relay off once current draw
+    /// exceeds hardware limits.
+    ///
+    /// The feature is available only in some firmware versions (1.5 and newer of hardware
+    /// version 2.0 and newer), and its command name varies between them, so known names are
+    /// tried in order. [`TpLinkHs110Error::FeatureNotSupported`] is returned if none of them is
+    /// recognized by a smartplug.
+    pub fn set_auto_off_on_overload(&self, enabled: bool) -> Result<(), TpLinkHs110Error> {
+        for command in OVERLOAD_PROTECTION_COMMANDS.map(|(_, set)| set) {
+            match self.command("emeter", command, json!({"enable": u8::from(enabled)})) {
+                Ok(_) => return Ok(()),
+                Err(err) if is_unsupported_command(&err) => {
+                    log::debug!("{command} is not supported: {err}");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(TpLinkHs110Error::FeatureNotSupported)
+    }
+
+    /// Attempts to check whether current draw exceeds a hardware limit of a smartplug model (see
+    /// [`DeviceModel::max_current_a`]).
+    pub fn is_overloaded(&self) -> Result<bool, TpLinkHs110Error> {
+        let max_current_a = self.device_model()?.max_current_a();
+        Ok(self.emeter_reading()?.current_a > max_current_a)
+    }
+
+    /// Attempts to get energy meter calibration constants (voltage and current gains). All the
+    /// energy meter readings depend on them, so consistently low or high readings may be caused
+    /// by constants which are out of factory spec.
+    pub fn get_emeter_calibration(&self) -> Result<EmeterCalibration, TpLinkHs110Error> {
+        Ok(serde_json::from_value(self.command(
+            "emeter",
+            "get_vgain_igain",
+            json!({}),
+        )?)?)
+    }
+
+    /// Attempts to set energy meter calibration constants (voltage and current gains). Zero gains
+    /// are rejected, as they would turn all the readings into zeros.
+    ///
+    /// Incorrect constants make energy meter readings meaningless, so it's worth saving current
+    /// ones (see [`HS110::get_emeter_calibration`]) before changing them.
+    pub fn set_emeter_calibration(
+        &self,
+        calibration: &EmeterCalibration,
+    ) -> Result<(), TpLinkHs110Error> {
+        if calibration.vgain == 0 || calibration.igain == 0 {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "calibration gains should be positive, got vgain {}, igain {}",
+                calibration.vgain, calibration.igain
+            )))?;
+        }
+
+        self.command(
+            "emeter",
+            "set_vgain_igain",
+            serde_json::to_value(calibration)?,
+        )?;
+        Ok(())
+    }
+
+    /// Attempts to reboot a smartplug with an optional delay (in seconds).
+    #[deprecated(since = "0.3.0", note = "use `reboot_after()` instead")]
+    pub fn reboot(&self, delay: Option<u32>) -> Result<(), TpLinkHs110Error> {
+        self.reboot_after(Duration::from_secs(delay.unwrap_or(0).into()))
+    }
+
+    /// Attempts to reboot a smartplug after a given delay (rounded down to whole seconds).
+    pub fn reboot_after(&self, delay: Duration) -> Result<(), TpLinkHs110Error> {
+        self.command("system", "reboot", json!({"delay": delay_secs(delay)?}))
+            .map(|_| ())
+    }
+
+    /// Attempts to reboot a smartplug and wait until it's back online (reachable again) for up to
+    /// `timeout`, see [`HS110::wait_for_reachable`].
+    pub fn reboot_and_wait(&self, timeout: Duration) -> Result<(), TpLinkHs110Error> {
+        self.reboot_after(Duration::ZERO)?;
+        // Reboot happens with a small delay, make sure the smartplug has gone offline.
+        std::thread::sleep(Duration::from_secs(2));
+
+        self.wait_for_reachable(timeout)
+    }
+
+    /// Waits until a smartplug responds to requests for up to `timeout`. Returns
+    /// [`TpLinkHs110Error::WaitTimeout`] if it doesn't.
+    pub fn wait_for_reachable(&self, timeout: Duration) -> Result<(), TpLinkHs110Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                Err(TpLinkHs110Error::WaitTimeout(timeout))?;
+            }
+
+            let attempt_timeout = self.timeout.map_or(remaining, |t| t.min(remaining));
+            let probe = self.clone().with_timeout(attempt_timeout);
+            if probe.info().is_ok() {
+                return Ok(());
+            }
+
+            std::thread::sleep(REACHABILITY_POLL_INTERVAL.min(remaining));
+        }
+    }
+
+    /// Attempts to perform a factory reset with an optional delay (in seconds).
+    #[deprecated(since = "0.3.0", note = "use `factory_reset_after()` instead")]
+    pub fn factory_reset(&self, delay: Option<u32>) -> Result<(), TpLinkHs110Error> {
+        self.factory_reset_after(Duration::from_secs(delay.unwrap_or(0).into()))
+    }
+
+    /// Attempts to perform a factory reset after a given delay (rounded down to whole seconds).
+    pub fn factory_reset_after(&self, delay: Duration) -> Result<(), TpLinkHs110Error> {
+        self.command("system", "reset", json!({"delay": delay_secs(delay)?}))
+            .map(|_| ())
+    }
+
+    /// Attempts to perform a "soft" reset with an optional delay. Unlike
+    /// [`HS110::factory_reset`], a soft reset clears schedules and settings but preserves Wi-Fi
+    /// network settings, so a smartplug stays reachable afterwards.
+    ///
+    /// Behavior varies by firmware version: firmware without soft reset support reports an error
+    /// ([`TpLinkHs110Error::SmartplugErrCode`]).
+    pub fn soft_reset(&self, delay: Option<Duration>) -> Result<(), TpLinkHs110Error> {
+        self.command(
+            "system",
+            "reset",
+            json!({
+                "delay": delay_secs(delay.unwrap_or_default())?,
+                "preserve_net_setting": 1,
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Attempts to send a raw JSON command to a smartplug and returns a full response.
+    ///
+    /// This is an escape hatch for commands which are not (yet) supported by the library, see
+    /// [the list of known commands](https://github.com/softScheck/tplink-smartplug/blob/master/tplink-smarthome-commands.txt).
+    pub fn send_raw_command(&self, command: &str) -> Result<Value, TpLinkHs110Error> {
+        Ok(serde_json::from_str::<Value>(&self.request(command)?)?)
+    }
+
+    /// Attempts to send a parameterless command given as a path (module and command, optionally
+    /// followed by a sub-command) and returns a full response without any extraction or
+    /// `err_code` checks, which is useful for debugging. E.g. `&["system", "get_sysinfo"]` sends
+    /// `{"system":{"get_sysinfo":{}}}`.
+    pub fn get_raw_response(&self, command_path: &[&str]) -> Result<Value, TpLinkHs110Error> {
+        if !(2..=3).contains(&command_path.len()) {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "command path should consist of 2 or 3 elements, got {command_path:?}"
+            )))?;
+        }
+
+        let request = command_path
+            .iter()
+            .rev()
+            .fold(json!({}), |inner, key| json!({ *key: inner }));
+        self.send_raw_command(&request.to_string())
+    }
+
+    /// Attempts to send a `{module: {command: params}}` request and returns a response under
+    /// `[module][command]`, e.g. `send_arbitrary_json("emeter", "get_realtime", json!({}))`
+    /// sends `{"emeter":{"get_realtime":{}}}`. Unlike [`HS110::send_raw_command`], a request
+    /// doesn't need to be formatted manually. `err_code` of a command isn't checked, so it is up
+    /// to a caller, while an error reported in place of a command (e.g. for an unsupported
+    /// module) is returned as [`TpLinkHs110Error::SmartplugErrCode`].
+    pub fn send_arbitrary_json(
+        &self,
+        module: &str,
+        command: &str,
+        params: Value,
+    ) -> Result<Value, TpLinkHs110Error> {
+        self.send_nested_command(&[module, command], params)
+    }
+
+    /// Same as [`HS110::send_arbitrary_json`], but for commands nested one level deeper, i.e.
+    /// sends a `{module: {command: {subcommand: params}}}` request and returns a response under
+    /// `[module][command][subcommand]`.
+    pub fn send_three_level(
+        &self,
+        module: &str,
+        command: &str,
+        subcommand: &str,
+        params: Value,
+    ) -> Result<Value, TpLinkHs110Error> {
+        self.send_nested_command(&[module, command, subcommand], params)
+    }
+
+    /// Helper function which nests `params` under a given path of keys, sends the request and
+    /// extracts a response under the same path.
+    fn send_nested_command(&self, path: &[&str], params: Value) -> Result<Value, TpLinkHs110Error> {
+        let response = self.send_raw_command(&nested_request(path, params)?.to_string())?;
+
+        let mut level = &response;
+        for key in path {
+            level = match level.get(key) {
+                Some(nested) => nested,
+                None => match level.get("err_code").and_then(Value::as_i64) {
+                    Some(err_code) if err_code != 0 => {
+                        Err(TpLinkHs110Error::SmartplugErrCode(err_code))?
+                    }
+                    _ => return response.extract_hierarchical(path),
+                },
+            };
+        }
+
+        Ok(level.clone())
+    }
+
+    /// Attempts to send a command within `schedule` namespace, i.e. `command` is wrapped into
+    /// `{"schedule": command}`. Returns a response under `schedule` namespace.
+    pub fn send_schedule_command(&self, command: &Value) -> Result<Value, TpLinkHs110Error> {
+        self.send_namespaced_command("schedule", command)
+    }
+
+    /// Attempts to send a command within `count_down` namespace, i.e. `command` is wrapped into
+    /// `{"count_down": command}`. Returns a response under `count_down` namespace.
+    pub fn send_countdown_command(&self, command: &Value) -> Result<Value, TpLinkHs110Error> {
+        self.send_namespaced_command("count_down", command)
+    }
+
+    /// Attempts to send a command within `emeter` namespace, i.e. `command` is wrapped into
+    /// `{"emeter": command}`. Returns a response under `emeter` namespace.
+    pub fn send_emeter_command(&self, command: &Value) -> Result<Value, TpLinkHs110Error> {
+        self.send_namespaced_command("emeter", command)
+    }
+
+    /// Attempts to send a command within `system` namespace, i.e. `command` is wrapped into
+    /// `{"system": command}`. Returns a response under `system` namespace.
+    pub fn send_system_command(&self, command: &Value) -> Result<Value, TpLinkHs110Error> {
+        self.send_namespaced_command("system", command)
+    }
+
+    /// Helper function which wraps a command into `{namespace: command}`, sends it and extracts
+    /// a response under the namespace.
+    fn send_namespaced_command(
+        &self,
+        namespace: &'static str,
+        command: &Value,
+    ) -> Result<Value, TpLinkHs110Error> {
+        self.send_raw_command(&json!({ namespace: command }).to_string())?
+            .extract_hierarchical(&[namespace])
+    }
+
+    /// Helper function which sends a `{module: {command: params}}` request, extracts the
+    /// `[module][command]` object from a response and makes sure the smartplug reported success.
+    fn command(
+        &self,
+        module: &'static str,
+        command: &'static str,
+        params: Value,
+    ) -> Result<Value, TpLinkHs110Error> {
+        self.checked_command(&wrap_command(module, command, params), module, command)
+    }
+
+    /// Same as [`HS110::command`], but the command is addressed to a given child outlet of a
+    /// multi-outlet power strip.
+    fn child_command(
+        &self,
+        child_id: &str,
+        module: &'static str,
+        command: &'static str,
+        params: Value,
+    ) -> Result<Value, TpLinkHs110Error> {
+        let mut request = wrap_command(module, command, params);
+        request["context"] = json!({"child_ids": [child_id]});
+        self.checked_command(&request, module, command)
+    }
+
+    /// Helper function which sends a whole-device `emeter` module command along with
+    /// `get_sysinfo` and returns a whole response. Returns [`TpLinkHs110Error::PowerStripEmeter`]
+    /// for multi-outlet power strips (system information lists `children`), their energy meters
+    /// are read per outlet with [`HS110::child_emeter`].
+    fn whole_device_emeter_request(
+        &self,
+        command: &'static str,
+        params: Value,
+    ) -> Result<Value, TpLinkHs110Error> {
+        let mut request = wrap_command("emeter", command, params);
+        request["system"] = json!({"get_sysinfo": {}});
+
+        let response = serde_json::from_str::<Value>(&self.request(request.to_string())?)?;
+        if response.pointer("/system/get_sysinfo/children").is_some() {
+            Err(TpLinkHs110Error::PowerStripEmeter)?;
+        }
+
+        Ok(response)
+    }
+
+    /// Helper function which sends a request, extracts the `[module][command]` object from
+    /// a response and makes sure the smartplug reported success.
+    fn checked_command(
+        &self,
+        request: &Value,
+        module: &'static str,
+        command: &'static str,
+    ) -> Result<Value, TpLinkHs110Error> {
+        extract_checked(
+            &serde_json::from_str::<Value>(&self.request(request.to_string())?)?,
+            module,
+            command,
+        )
+    }
+
+    /// Attempts to find out whether schedule rules are globally enabled. Individual schedule
+    /// rules have no effect while this global flag is off.
+    pub fn get_schedule_enable(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self
+            .command("schedule", "get_rules", json!({}))?
+            .extract_hierarchical(&["enable"])?
+            .as_u64()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            != 0)
+    }
+
+    /// Attempts to globally enable or disable schedule rules.
+    pub fn set_schedule_enable(&self, enabled: bool) -> Result<(), TpLinkHs110Error> {
+        self.command(
+            "schedule",
+            "set_overall_enable",
+            json!({"enable": u8::from(enabled)}),
+        )?;
+        Ok(())
+    }
+
+    /// Attempts to find out whether countdown rules are globally enabled.
+    pub fn get_countdown_enable(&self) -> Result<bool, TpLinkHs110Error> {
+        Ok(self
+            .command("count_down", "get_rules", json!({}))?
+            .extract_hierarchical(&["enable"])?
+            .as_u64()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+            != 0)
+    }
+
+    /// Attempts to globally enable or disable countdown rules.
+    pub fn set_countdown_enable(&self, enabled: bool) -> Result<(), TpLinkHs110Error> {
+        self.command(
+            "count_down",
+            "set_overall_enable",
+            json!({"enable": u8::from(enabled)}),
+        )?;
+        Ok(())
+    }
+
+    /// Attempts to apply a preset, i.e. a common configuration (see [`DevicePreset`]).
+    pub fn apply_preset(&self, preset: DevicePreset) -> Result<(), TpLinkHs110Error> {
+        match preset {
+            DevicePreset::EnergySaving => {
+                self.set_led_state(LedState::Off)?;
+
+                // Rules of a previously applied preset are replaced rather than duplicated.
+                for rule in self.get_schedule_rules()? {
+                    if rule
+                        .name
+                        .starts_with(DevicePreset::ENERGY_SAVING_RULE_PREFIX)
+                    {
+                        self.delete_schedule_rule(&rule.id)?;
+                    }
+                }
+                for (name, smin, sact) in [
+                    (
+                        "Energy saving: peak hours start",
+                        DevicePreset::PEAK_HOURS_START,
+                        PowerState::Off,
+                    ),
+                    (
+                        "Energy saving: peak hours end",
+                        DevicePreset::PEAK_HOURS_END,
+                        PowerState::On,
+                    ),
+                ] {
+                    self.add_schedule_rule(&ScheduleRule {
+                        id: String::new(),
+                        name: name.to_string(),
+                        enable: true,
+                        wday: [true; 7],
+                        stime_opt: ScheduleTimeOption::Time,
+                        smin,
+                        soffset: None,
+                        sact,
+                        repeat: true,
+                        year: None,
+                        month: None,
+                        day: None,
+                    })?;
+                }
+
+                // Schedules might have been disabled, e.g. by `AlwaysOn` preset.
+                self.set_schedule_enable(true)
+            }
+            DevicePreset::AlwaysOn => {
+                self.set_power_state(PowerState::On)?;
+                self.set_schedule_enable(false)?;
+                self.set_countdown_enable(false)
+            }
+            DevicePreset::NightMode => self.set_led_state(LedState::Off),
+            DevicePreset::Custom(apply) => apply(self),
+        }
+    }
+}
+
+/// Wraps command parameters into a `{module: {command: params}}` request.
+fn wrap_command(module: &str, command: &str, params: Value) -> Value {
+    let command = serde_json::Map::from_iter([(command.to_string(), params)]);
+    Value::Object(serde_json::Map::from_iter([(
+        module.to_string(),
+        Value::Object(command),
+    )]))
+}
+
+/// Nests `params` under a given path of keys, e.g. `["emeter", "get_realtime"]` gives
+/// `{"emeter":{"get_realtime":params}}`. Keys are required to be non-empty.
+fn nested_request(path: &[&str], params: Value) -> Result<Value, TpLinkHs110Error> {
+    if let Some(position) = path.iter().position(|key| key.is_empty()) {
+        Err(TpLinkHs110Error::InvalidParameter(format!(
+            "command path element #{} is empty: {path:?}",
+            position + 1
+        )))?;
+    }
+
+    Ok(path
+        .iter()
+        .rev()
+        .fold(params, |inner, key| json!({ *key: inner })))
+}
+
+/// Calculates CRC-32 (IEEE 802.3) checksum of given data.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+/// Extraction of nested fields of smartplug responses.
+pub trait ExtractHierarchical {
+    /// Attempts to get a (cloned) value under a given path of object keys, e.g.
+    /// `["system", "get_sysinfo", "alias"]`.
+    fn extract_hierarchical(&self, path: &[&str]) -> Result<Value, TpLinkHs110Error>;
+}
+
+impl ExtractHierarchical for Value {
+    /// Attempts to traverse hierarchical structure (JSON object) over provided path and returns
+    /// corresponding sub-object/field. Traversal is done over references, only the resulting
+    /// value (or the whole response in case of an error) is cloned.
+    fn extract_hierarchical(&self, path: &[&str]) -> Result<Value, TpLinkHs110Error> {
+        let mut current_object = self;
         for key in path {
             current_object =
                 current_object
                     .get(key)
                     .ok_or_else(|| TpLinkHs110Error::KeyIsNotAvailable {
                         response: self.clone(),
-                        key,
+                        key: (*key).to_string(),
                     })?;
         }
 
-        Ok(current_object.clone())
+        Ok(current_object.clone())
+    }
+}
+
+/// Smartplug hardware version (hardware revision).
+#[derive(Debug, Clone, PartialEq)]
+pub enum HwVersion {
+    Version1,
+    Version2,
+    Unsupported(String),
+}
+
+impl From<&str> for HwVersion {
+    fn from(value: &str) -> Self {
+        match value {
+            "1.0" => Self::Version1,
+            "2.0" => Self::Version2,
+            other => Self::Unsupported(other.into()),
+        }
+    }
+}
+
+/// Smartplug hardware version along with hardware, firmware and OEM identifiers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HwVersionInfo {
+    /// Hardware version (hardware revision).
+    pub hw_ver: HwVersion,
+
+    /// Hardware identifier (a hex string, stored as reported by a smartplug).
+    pub hw_id: String,
+
+    /// Firmware identifier.
+    pub fw_id: String,
+
+    /// OEM identifier.
+    pub oem_id: String,
+}
+
+impl std::fmt::Debug for HS110 {
+    /// Formats only an address and a timeout (which is the most useful for log messages), e.g.
+    /// `HS110 { addr: "192.168.1.100:9999", timeout: Some("3s") }`. The timeout is formatted with
+    /// `humantime` crate if `humantime` feature is enabled.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        #[cfg(feature = "humantime")]
+        let format_timeout = |timeout| humantime::format_duration(timeout).to_string();
+        #[cfg(not(feature = "humantime"))]
+        let format_timeout = |timeout| format!("{timeout:?}");
+
+        f.debug_struct("HS110")
+            .field("addr", &self.socket_addr.to_string())
+            .field("timeout", &self.timeout.map(format_timeout))
+            .finish()
+    }
+}
+
+impl PartialEq for HS110 {
+    fn eq(&self, other: &Self) -> bool {
+        self.socket_addr == other.socket_addr
+    }
+}
+
+impl Eq for HS110 {}
+
+impl Hash for HS110 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.socket_addr.hash(state);
+    }
+}
+
+impl TryFrom<String> for HS110 {
+    type Error = TpLinkHs110Error;
+
+    fn try_from(addr: String) -> Result<Self, Self::Error> {
+        Self::new(&addr)
+    }
+}
+
+impl TryFrom<HS110Config> for HS110 {
+    type Error = TpLinkHs110Error;
+
+    fn try_from(config: HS110Config) -> Result<Self, Self::Error> {
+        Self::from_config(&config)
+    }
+}
+
+/// Smartplug connection configuration which could be (de)serialized, e.g. kept in a config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HS110Config {
+    /// Smartplug address (see [`HS110::new`] for supported formats).
+    pub addr: String,
+
+    /// Name to refer to a smartplug by, e.g. in a [`registry::DeviceRegistry`] (unlike an alias,
+    /// it is kept locally and isn't sent to a smartplug).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Timeout for network communication in seconds (whole seconds only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl From<HS110> for HS110Config {
+    fn from(smartplug: HS110) -> Self {
+        Self {
+            addr: smartplug.socket_addr.to_string(),
+            name: None,
+            timeout_secs: smartplug.timeout.map(|timeout| timeout.as_secs()),
+        }
+    }
+}
+
+/// A smartplug with a limited request rate, see [`HS110::rate_limited`]. All the methods of
+/// [`HS110`] are available through [`Deref`](std::ops::Deref).
+#[derive(Debug, Clone)]
+pub struct RateLimitedHS110(HS110);
+
+impl RateLimitedHS110 {
+    /// Returns a wrapped instance, which is no longer rate limited.
+    pub fn into_inner(self) -> HS110 {
+        HS110 {
+            min_request_interval: None,
+            ..self.0
+        }
+    }
+}
+
+impl std::ops::Deref for RateLimitedHS110 {
+    type Target = HS110;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Endless iterator polling system information, see [`HS110::sysinfo_stream`]. Failed polls are
+/// yielded as errors, iteration continues after them.
+pub struct SysinfoStream {
+    /// Smartplug being polled.
+    smartplug: HS110,
+
+    /// Interval between polls.
+    interval: Duration,
+
+    /// When the next poll should happen (`None` before the first poll).
+    next_poll: Option<Instant>,
+
+    /// Power relay state reported by the last successful poll.
+    power_state: Option<PowerState>,
+
+    /// Callbacks called on power relay state changes.
+    on_power_change: Vec<Box<dyn Fn(PowerState, PowerState)>>,
+}
+
+impl SysinfoStream {
+    /// Adds a callback which is called with old and new states once a change of power relay
+    /// state is detected between consecutive successful polls.
+    #[must_use]
+    pub fn on_power_change<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(PowerState, PowerState) + 'static,
+    {
+        self.on_power_change.push(Box::new(callback));
+        self
+    }
+}
+
+impl std::fmt::Debug for SysinfoStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SysinfoStream")
+            .field("smartplug", &self.smartplug)
+            .field("interval", &self.interval)
+            .field("power_state", &self.power_state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for SysinfoStream {
+    type Item = Result<SysInfo, TpLinkHs110Error>;
+
+    /// Waits for the next poll and performs it, never returns `None`.
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(next_poll) = self.next_poll {
+            std::thread::sleep(next_poll.saturating_duration_since(Instant::now()));
+        }
+        self.next_poll = Some(Instant::now() + self.interval);
+
+        let sysinfo = self.smartplug.sysinfo();
+        if let Ok(sysinfo) = &sysinfo {
+            match self.power_state.replace(sysinfo.relay_state) {
+                Some(old) if old != sysinfo.relay_state => {
+                    for callback in &self.on_power_change {
+                        callback(old, sysinfo.relay_state);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(sysinfo)
+    }
+}
+
+/// Distribution of request round-trip times, see [`HS110::measure_latency_histogram`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LatencyHistogram {
+    /// Median round-trip time in milliseconds.
+    pub p50_ms: f64,
+
+    /// 90th percentile of round-trip times in milliseconds.
+    pub p90_ms: f64,
+
+    /// 99th percentile of round-trip times in milliseconds.
+    pub p99_ms: f64,
+
+    /// Minimal round-trip time in milliseconds.
+    pub min_ms: f64,
+
+    /// Maximal round-trip time in milliseconds.
+    pub max_ms: f64,
+
+    /// Number of requests issued.
+    pub samples: u32,
+
+    /// Number of failed requests.
+    pub errors: u32,
+}
+
+impl Display for LatencyHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "samples: {}, errors: {}, min: {:.1} ms, p50: {:.1} ms, p90: {:.1} ms, p99: {:.1} ms, max: {:.1} ms",
+            self.samples,
+            self.errors,
+            self.min_ms,
+            self.p50_ms,
+            self.p90_ms,
+            self.p99_ms,
+            self.max_ms
+        )
+    }
+}
+
+/// Smartplug's local date and time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceTime {
+    /// Year.
+    pub year: u16,
+
+    /// Month (1-12).
+    pub month: u8,
+
+    /// Day of month (1-31).
+    pub mday: u8,
+
+    /// Hour (0-23).
+    pub hour: u8,
+
+    /// Minute (0-59).
+    pub min: u8,
+
+    /// Second (0-59).
+    pub sec: u8,
+}
+
+impl DeviceTime {
+    /// Returns date and time which is `duration` after this one.
+    ///
+    /// Returns [`TpLinkHs110Error::InvalidParameter`] if the result doesn't fit into a year
+    /// range of [`DeviceTime`] (`0..=65535`).
+    pub fn after(&self, duration: Duration) -> Result<DeviceTime, TpLinkHs110Error> {
+        Self::checked_from_seconds_since_epoch(
+            i64::try_from(duration.as_secs())
+                .ok()
+                .and_then(|seconds| self.seconds_since_epoch().checked_add(seconds)),
+            duration,
+        )
+    }
+
+    /// Returns date and time which is `duration` before this one.
+    ///
+    /// Returns [`TpLinkHs110Error::InvalidParameter`] if the result doesn't fit into a year
+    /// range of [`DeviceTime`] (`0..=65535`).
+    pub fn before(&self, duration: Duration) -> Result<DeviceTime, TpLinkHs110Error> {
+        Self::checked_from_seconds_since_epoch(
+            i64::try_from(duration.as_secs())
+                .ok()
+                .and_then(|seconds| self.seconds_since_epoch().checked_sub(seconds)),
+            duration,
+        )
+    }
+
+    /// Same as [`DeviceTime::from_seconds_since_epoch`], but makes sure the year is in range,
+    /// `None` stands for an overflow while shifting a time by `duration`.
+    fn checked_from_seconds_since_epoch(
+        seconds: Option<i64>,
+        duration: Duration,
+    ) -> Result<DeviceTime, TpLinkHs110Error> {
+        let supported =
+            days_from_civil(0, 1, 1) * 86_400..(days_from_civil(u16::MAX, 12, 31) + 1) * 86_400;
+        seconds
+            .filter(|seconds| supported.contains(seconds))
+            .map(Self::from_seconds_since_epoch)
+            .ok_or_else(|| {
+                TpLinkHs110Error::InvalidParameter(format!(
+                    "shifting time by {duration:?} gets it out of the supported range"
+                ))
+            })
+    }
+
+    /// Inverse of [`DeviceTime::seconds_since_epoch`].
+    fn from_seconds_since_epoch(seconds: i64) -> DeviceTime {
+        let (days, seconds) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+        let (year, month, mday) = civil_from_days(days);
+
+        DeviceTime {
+            year,
+            month,
+            mday,
+            hour: (seconds / 3600) as u8,
+            min: (seconds / 60 % 60) as u8,
+            sec: (seconds % 60) as u8,
+        }
+    }
+
+    /// Returns day of week (0 is Sunday, as in [`ScheduleRule::wday`]).
+    pub fn weekday(&self) -> u8 {
+        // 1970-01-01 was Thursday.
+        (days_from_civil(self.year, self.month, self.mday) + 4).rem_euclid(7) as u8
+    }
+
+    /// Seconds since 1970-01-01 00:00:00 (as if the time was UTC).
+    fn seconds_since_epoch(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.mday) * 86_400
+            + i64::from(self.hour) * 3600
+            + i64::from(self.min) * 60
+            + i64::from(self.sec)
+    }
+}
+
+/// Extracts the `[module][command]` object from a response and makes sure the smartplug reported
+/// success.
+fn extract_checked(
+    response: &Value,
+    module: &'static str,
+    command: &'static str,
+) -> Result<Value, TpLinkHs110Error> {
+    let response = response.extract_hierarchical(&[module, command])?;
+
+    match response
+        .extract_hierarchical(&["err_code"])?
+        .as_i64()
+        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+    {
+        0 => Ok(response),
+        err_code => Err(TpLinkHs110Error::SmartplugErrCode(err_code)),
+    }
+}
+
+/// Deserializes rules of a given type (relay or LED, see `enable_type` field) from a `schedule`
+/// module `get_rules` response.
+fn rules_of_type<T>(response: &Value, rule_type: u64) -> Result<Vec<T>, TpLinkHs110Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    response
+        .extract_hierarchical(&["rule_list"])?
+        .as_array()
+        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+        .iter()
+        .filter(|rule| {
+            rule.get("enable_type")
+                .and_then(Value::as_u64)
+                .unwrap_or(RELAY_RULE_TYPE)
+                == rule_type
+        })
+        .map(|rule| Ok(serde_json::from_value(rule.clone())?))
+        .collect()
+}
+
+/// Whether an error means a smartplug doesn't support a command: it has either reported `-1`
+/// ("module not support") or `-2` ("member not support") error code, for a command or for
+/// a whole module (instead of a command response), or it has left a command out of a response.
+fn is_unsupported_command(err: &TpLinkHs110Error) -> bool {
+    let is_unsupported = |err_code| matches!(err_code, MODULE_NOT_SUPPORTED | MEMBER_NOT_SUPPORTED);
+
+    match err {
+        TpLinkHs110Error::SmartplugErrCode(err_code) => is_unsupported(*err_code),
+        TpLinkHs110Error::KeyIsNotAvailable { response, .. } => {
+            let mut err_codes = response
+                .as_object()
+                .into_iter()
+                .flat_map(serde_json::Map::values)
+                .chain([response])
+                .filter_map(|value| value.get("err_code").and_then(Value::as_i64))
+                .peekable();
+            err_codes.peek().is_none() || err_codes.any(is_unsupported)
+        }
+        _ => false,
+    }
+}
+
+/// Makes sure a smartplug name no longer than `max_len` bytes would be accepted by firmware, see
+/// [`HS110::set_alias`].
+fn validate_name(name: &str, max_len: usize) -> Result<(), TpLinkHs110Error> {
+    if name.is_empty() || name.len() > max_len {
+        Err(TpLinkHs110Error::InvalidParameter(format!(
+            "name should be 1 to {max_len} bytes long, got {}",
+            name.len()
+        )))?;
+    }
+    if name.chars().any(char::is_control) {
+        Err(TpLinkHs110Error::InvalidParameter(format!(
+            "name {name:?} contains control characters"
+        )))?;
+    }
+
+    Ok(())
+}
+
+/// Converts a delay to whole seconds as expected by smartplug commands.
+fn delay_secs(delay: Duration) -> Result<u32, TpLinkHs110Error> {
+    u32::try_from(delay.as_secs())
+        .map_err(|_| TpLinkHs110Error::InvalidParameter(format!("delay {delay:?} is too long")))
+}
+
+/// Attempts to represent a cloud server hostname as parameters of `set_server_url` command, see
+/// [`HS110::set_cloud_server`].
+fn cloud_server_params(server: &str) -> Result<Value, TpLinkHs110Error> {
+    if server.is_empty() {
+        Err(TpLinkHs110Error::InvalidParameter(
+            "cloud server hostname should not be empty".to_string(),
+        ))?;
+    }
+
+    Ok(json!({"server": server}))
+}
+
+/// Number of days since 1970-01-01 for a given date of the proleptic Gregorian calendar.
+fn days_from_civil(year: u16, month: u8, mday: u8) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(mday) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of the proleptic Gregorian calendar for a given number of days since 1970-01-01, the
+/// inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (u16, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let mday = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year as u16, month as u8, mday as u8)
+}
+
+/// Energy consumed over a day, see [`HS110::get_daystat`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DayStat {
+    /// Year.
+    pub year: u16,
+
+    /// Month (1-12).
+    pub month: u8,
+
+    /// Day of month (1-31).
+    pub day: u8,
+
+    /// Consumed energy in kWh.
+    pub energy: f64,
+}
+
+/// Energy consumed over a month, see [`HS110::get_monthstat`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MonthStat {
+    /// Year.
+    pub year: u16,
+
+    /// Month (1-12).
+    pub month: u8,
+
+    /// Consumed energy in kWh.
+    pub energy: f64,
+}
+
+/// Everything known about a smartplug, see [`HS110::diagnose`]. Parts which couldn't be collected
+/// are `None`.
+#[derive(Debug, Serialize)]
+pub struct DiagnosticReport {
+    /// System information (as returned by `get_sysinfo` command).
+    pub sysinfo: Option<Value>,
+
+    /// Firmware version.
+    pub firmware_version: Option<String>,
+
+    /// TP-Link cloud connection information.
+    pub cloudinfo: Option<CloudInfo>,
+
+    /// Energy meter readings.
+    pub emeter: Option<EmeterReading>,
+
+    /// Schedule rules.
+    pub schedule_rules: Option<Vec<ScheduleRule>>,
+
+    /// Countdown rules.
+    pub countdown_rules: Option<Vec<CountdownRule>>,
+
+    /// Smartplug's local date and time.
+    pub time: Option<DeviceTime>,
+
+    /// Wireless network connection information (as returned by `get_stainfo` command).
+    pub network: Option<Value>,
+
+    /// Errors occurred while collecting the report.
+    #[serde(serialize_with = "serialize_errors")]
+    pub collect_errors: Vec<TpLinkHs110Error>,
+}
+
+/// Serializes errors as a list of their descriptions.
+fn serialize_errors<S>(errors: &[TpLinkHs110Error], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(errors.iter().map(ToString::to_string))
+}
+
+/// Power draw stability over a period of time, see [`HS110::measure_power_stability`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PowerStability {
+    /// Mean power in Watts.
+    pub mean_w: f64,
+
+    /// Standard deviation of power in Watts.
+    pub std_dev_w: f64,
+
+    /// Coefficient of variation (standard deviation relative to the mean) in percents.
+    pub cv_pct: f64,
+
+    /// Number of readings the statistics are computed over.
+    pub samples: u32,
+}
+
+/// Results of connectivity checks, see [`HS110::test_connectivity`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectivityTest {
+    /// Whether a TCP connection could be established.
+    pub tcp_ok: bool,
+
+    /// Whether a smartplug answers a system information request.
+    pub protocol_ok: bool,
+
+    /// Whether a smartplug identifies itself as a smartplug.
+    pub device_id_ok: bool,
+
+    /// Whether a smartplug is connected to TP-Link cloud.
+    pub cloud_ok: bool,
+
+    /// Whether a smartplug clock is synchronized, i.e. differs from UTC by an offset of its
+    /// configured time zone give or take [`NTP_SYNC_TOLERANCE`]. Requires `chrono` feature
+    /// (time zones are unknown otherwise), the check always fails without it.
+    pub ntp_ok: bool,
+
+    /// Descriptions of errors occurred during the checks by check names.
+    pub errors: HashMap<String, String>,
+}
+
+/// Results of smartplug health checks, see [`HS110::health_check`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// Whether a smartplug responds to requests.
+    pub reachable: bool,
+
+    /// Whether power relay state could be read.
+    pub relay_ok: bool,
+
+    /// Whether energy meter could be read.
+    pub emeter_ok: bool,
+
+    /// Whether a smartplug is connected to TP-Link cloud.
+    pub cloud_ok: bool,
+
+    /// Whether firmware is up to date (`None` if it couldn't be checked).
+    pub firmware_current: Option<bool>,
+
+    /// Round-trip time of a request in milliseconds (`None` if a smartplug is unreachable).
+    pub latency_ms: Option<f64>,
+
+    /// Descriptions of errors occurred during the checks.
+    pub errors: Vec<String>,
+}
+
+/// Smartplug icon, see [`HS110::get_device_icons`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceIcon {
+    /// Icon hash (32 hexadecimal digits).
+    pub icon_hash: String,
+
+    /// Icon name.
+    pub name: String,
+}
+
+/// Minimal smartplug identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    /// Unique device identifier.
+    pub device_id: String,
+
+    /// Model string (like `"HS110(EU)"`).
+    pub model: String,
+
+    /// Smartplug name (alias).
+    pub alias: String,
+}
+
+/// Smartplug found by [`HS110::discover`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredDevice {
+    /// Smartplug address (with the default port).
+    pub addr: SocketAddr,
+
+    /// Unique device identifier.
+    pub device_id: String,
+
+    /// Model string (like `"HS110(EU)"`).
+    pub model: String,
+
+    /// Smartplug name (alias).
+    pub alias: String,
+}
+
+impl DiscoveredDevice {
+    /// Extracts device details from a system information response, returns `None` if the
+    /// response doesn't look like a smartplug one.
+    fn from_sysinfo(ip: IpAddr, response: &Value) -> Option<Self> {
+        let sysinfo = response.pointer("/system/get_sysinfo")?;
+        let field = |field| sysinfo.get(field)?.as_str().map(str::to_string);
+
+        Some(Self {
+            addr: SocketAddr::new(ip, DEFAULT_PORT),
+            device_id: field("deviceId")?,
+            model: field("model")?,
+            alias: field("alias")?,
+        })
+    }
+}
+
+/// System information reported by a smartplug, see [`HS110::sysinfo`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SysInfo {
+    /// Smartplug name (alias).
+    pub alias: String,
+
+    /// Model string (like `"HS110(EU)"`).
+    pub model: String,
+
+    /// Unique device identifier.
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+
+    /// Hardware version (like `"1.0"`).
+    pub hw_ver: String,
+
+    /// Firmware version (like `"1.2.6 Build 200727 Rel.120821"`).
+    pub sw_ver: String,
+
+    /// MAC address (reported as `mic_mac` by some hardware versions).
+    #[serde(default, alias = "mic_mac")]
+    pub mac: String,
+
+    /// Power relay state.
+    pub relay_state: PowerState,
+
+    /// Whether LED indicator is switched off.
+    #[serde(with = "int_bool")]
+    pub led_off: bool,
+
+    /// Seconds since power relay has been switched on (`0` if it's off).
+    #[serde(default)]
+    pub on_time: u64,
+
+    /// Wi-Fi signal strength in dBm.
+    #[serde(default)]
+    pub rssi: i32,
+}
+
+/// Wi-Fi access point observed by a smartplug, see [`HS110::get_cached_ap_list`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessPoint {
+    /// Network name.
+    pub ssid: String,
+
+    /// Security type (`0` - none, `1` - WEP, `2` - WPA, `3` - WPA2).
+    pub key_type: u8,
+}
+
+/// MAC address of a smartplug, formatted as `AA:BB:CC:DD:EE:FF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl MacAddress {
+    /// Organizationally unique identifiers (the first 3 bytes of MAC addresses) assigned to
+    /// TP-Link. The list isn't exhaustive, it covers prefixes commonly seen on TP-Link smart home
+    /// devices.
+    pub const TPLINK_OUIS: &'static [[u8; 3]] = &[
+        [0x00, 0x31, 0x92],
+        [0x10, 0x27, 0xf5],
+        [0x14, 0xeb, 0xb6],
+        [0x1c, 0x3b, 0xf3],
+        [0x1c, 0x61, 0xb4],
+        [0x3c, 0x84, 0x6a],
+        [0x50, 0xc7, 0xbf],
+        [0x54, 0xaf, 0x97],
+        [0x5c, 0xa6, 0xe6],
+        [0x60, 0x32, 0xb1],
+        [0x68, 0xff, 0x7b],
+        [0x70, 0x4f, 0x57],
+        [0x74, 0xda, 0x88],
+        [0x84, 0x16, 0xf9],
+        [0x98, 0xda, 0xc4],
+        [0x9c, 0xa2, 0xf4],
+        [0xa8, 0x42, 0xa1],
+        [0xac, 0x84, 0xc6],
+        [0xb0, 0x95, 0x75],
+        [0xb0, 0xbe, 0x76],
+        [0xc0, 0x06, 0xc3],
+        [0xd8, 0x07, 0xb6],
+        [0xe8, 0x48, 0xb8],
+        [0xec, 0x08, 0x6b],
+        [0xf4, 0xf2, 0x6d],
+    ];
+
+    /// Returns bytes of the address.
+    pub fn to_bytes(&self) -> &[u8; 6] {
+        &self.0
+    }
+
+    /// Converts the address into bytes.
+    pub fn into_bytes(self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Returns an organizationally unique identifier of the address (its first 3 bytes).
+    pub fn oui(&self) -> [u8; 3] {
+        let [a, b, c, ..] = self.0;
+        [a, b, c]
+    }
+
+    /// Whether the address belongs to TP-Link (see [`MacAddress::TPLINK_OUIS`]).
+    pub fn is_tplink_oui(&self) -> bool {
+        Self::TPLINK_OUIS.contains(&self.oui())
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02X}:{b:02X}:{c:02X}:{d:02X}:{e:02X}:{g:02X}")
+    }
+}
+
+impl std::fmt::UpperHex for MacAddress {
+    /// Formats the address as 12 uppercase hexadecimal digits without separators.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02X}"))
+    }
+}
+
+impl std::fmt::LowerHex for MacAddress {
+    /// Formats the address as 12 lowercase hexadecimal digits without separators.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl TryFrom<&str> for MacAddress {
+    type Error = TpLinkHs110Error;
+
+    /// Parses a MAC address in any of the common formats (hexadecimal digits are case
+    /// insensitive): `AA:BB:CC:DD:EE:FF`, `AA-BB-CC-DD-EE-FF`, `AABB.CCDD.EEFF` or
+    /// `AABBCCDDEEFF`.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let groups = [':', '-', '.']
+            .into_iter()
+            .find(|separator| value.contains(*separator))
+            .map_or_else(|| vec![value], |separator| value.split(separator).collect());
+        let group_len = match groups.len() {
+            1 => 12,
+            3 if value.contains('.') => 4,
+            6 if !value.contains('.') => 2,
+            _ => 0,
+        };
+        let digits = groups.concat();
+        if groups.iter().any(|group| group.len() != group_len)
+            || !digits.bytes().all(|digit| digit.is_ascii_hexdigit())
+        {
+            Err(TpLinkHs110Error::InvalidParameter(format!(
+                "invalid MAC address {value:?}"
+            )))?;
+        }
+
+        let mut mac = [0u8; 6];
+        for (index, byte) in mac.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16)
+                .map_err(|err| TpLinkHs110Error::InvalidParameter(err.to_string()))?;
+        }
+
+        Ok(Self(mac))
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = TpLinkHs110Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::try_from(String::deserialize(deserializer)?.as_str())
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Smartplug model along with an optional regional suffix (like `"EU"` or `"US"`).
+///
+/// Models are ordered by feature richness: `Hs100 < Hs110 < Unknown` (the order of variants
+/// matters).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceModel {
+    /// HS100 smartplug (no energy meter).
+    Hs100(Option<String>),
+
+    /// HS110 smartplug (with energy meter).
+    Hs110(Option<String>),
+
+    /// Any other model, contains a model string as reported by a device.
+    Unknown(String),
+}
+
+impl DeviceModel {
+    /// Whether a smartplug of this model is equipped with an energy meter.
+    pub fn has_energy_monitor(&self) -> bool {
+        matches!(self, Self::Hs110(_))
+    }
+
+    /// Highest current (in Amperes) a smartplug of this model is rated for, depends on a region:
+    /// 15 A for US, 13 A for UK, 10 A for AU and 16 A for others (e.g. EU). The lowest rating
+    /// (10 A) is assumed for unknown models.
+    pub fn max_current_a(&self) -> f64 {
+        match self {
+            Self::Hs100(region) | Self::Hs110(region) => match region.as_deref() {
+                Some("US") => 15.0,
+                Some("UK") => 13.0,
+                Some("AU") => 10.0,
+                _ => 16.0,
+            },
+            Self::Unknown(_) => 10.0,
+        }
+    }
+
+    /// Numeric feature richness level of the model, consistent with models ordering.
+    pub fn capability_level(&self) -> u8 {
+        match self {
+            Self::Hs100(_) => 0,
+            Self::Hs110(_) => 1,
+            Self::Unknown(_) => 2,
+        }
+    }
+}
+
+impl FromStr for DeviceModel {
+    type Err = Infallible;
+
+    /// Parses a model string like `"HS110(EU)"` or `"HS100"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, region) = match s.split_once('(') {
+            Some((name, rest)) => match rest.strip_suffix(')') {
+                Some(region) => (name, Some(region.to_string())),
+                None => return Ok(Self::Unknown(s.into())),
+            },
+            None => (s, None),
+        };
+
+        Ok(match name {
+            "HS100" => Self::Hs100(region),
+            "HS110" => Self::Hs110(region),
+            _ => Self::Unknown(s.into()),
+        })
+    }
+}
+
+/// Energy meter readings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EmeterReading {
+    /// Voltage in Volts.
+    pub voltage_v: f64,
+
+    /// Current in Amperes.
+    pub current_a: f64,
+
+    /// Power in Watts.
+    pub power_w: f64,
+
+    /// Total consumed energy in kWh.
+    pub total_kwh: f64,
+}
+
+impl TryFrom<&Value> for EmeterReading {
+    type Error = TpLinkHs110Error;
+
+    /// Attempts to extract readings from energy meter JSON (as returned by [`HS110::emeter`]).
+    fn try_from(emeter: &Value) -> Result<Self, Self::Error> {
+        let field = |field| {
+            emeter
+                .extract_hierarchical(&[field])?
+                .as_f64()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+        };
+
+        Ok(Self {
+            voltage_v: field("voltage")?,
+            current_a: field("current")?,
+            power_w: field("power")?,
+            total_kwh: field("total")?,
+        })
+    }
+}
+
+impl EmeterReading {
+    /// Roughly estimates a daily energy cost at a given rate (cost per kWh) assuming the current
+    /// load stays the same for the whole day.
+    pub fn estimated_daily_cost(&self, rate_per_kwh: f64) -> f64 {
+        self.power_w * 24.0 / 1000.0 * rate_per_kwh
+    }
+
+    /// Roughly estimates a monthly energy cost (an average month is 30.44 days long), see
+    /// [`EmeterReading::estimated_daily_cost`].
+    pub fn estimated_monthly_cost(&self, rate_per_kwh: f64) -> f64 {
+        self.estimated_daily_cost(rate_per_kwh) * 30.44
+    }
+
+    /// Roughly estimates an annual energy cost (an average year is 365.25 days long), see
+    /// [`EmeterReading::estimated_daily_cost`].
+    pub fn estimated_annual_cost(&self, rate_per_kwh: f64) -> f64 {
+        self.estimated_daily_cost(rate_per_kwh) * 365.25
+    }
+
+    /// Attaches a given timestamp to the readings.
+    pub fn with_timestamp(self, timestamp: SystemTime) -> TimestampedEmeterReading {
+        TimestampedEmeterReading {
+            reading: self,
+            timestamp,
+        }
+    }
+
+    /// Attaches current system time to the readings.
+    pub fn now(self) -> TimestampedEmeterReading {
+        self.with_timestamp(SystemTime::now())
+    }
+}
+
+#[cfg(feature = "measurements")]
+impl EmeterReading {
+    /// Voltage as a typed physical quantity.
+    pub fn voltage_typed(&self) -> measurements::Voltage {
+        measurements::Voltage::from_volts(self.voltage_v)
+    }
+
+    /// Current as a typed physical quantity.
+    pub fn current_typed(&self) -> measurements::Current {
+        measurements::Current::from_amperes(self.current_a)
+    }
+
+    /// Power as a typed physical quantity.
+    pub fn power_typed(&self) -> measurements::Power {
+        measurements::Power::from_watts(self.power_w)
+    }
+}
+
+/// Energy meter readings along with a time they were taken at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimestampedEmeterReading {
+    /// Energy meter readings.
+    pub reading: EmeterReading,
+
+    /// Time the readings were taken at.
+    pub timestamp: SystemTime,
+}
+
+impl TimestampedEmeterReading {
+    /// Returns the timestamp as a number of nanoseconds since Unix epoch (`0` for timestamps
+    /// before the epoch).
+    pub fn as_unix_nanos(&self) -> u128 {
+        self.since_unix_epoch().as_nanos()
+    }
+
+    /// Returns the timestamp as a number of milliseconds since Unix epoch (`0` for timestamps
+    /// before the epoch).
+    pub fn as_unix_millis(&self) -> u64 {
+        self.since_unix_epoch().as_millis() as u64
+    }
+
+    /// Returns time elapsed since Unix epoch till the timestamp.
+    fn since_unix_epoch(&self) -> Duration {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// Snapshot of the most commonly used smartplug state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusSummary {
+    /// Smartplug name (alias).
+    pub alias: String,
+
+    /// Power relay state.
+    pub power: PowerState,
+
+    /// LED state.
+    pub led: LedState,
+
+    /// Energy meter readings (`None` for smartplugs without an energy meter).
+    pub emeter: Option<EmeterReading>,
+
+    /// Wi-Fi signal strength (RSSI) in dBm.
+    pub rssi: i32,
+}
+
+/// Child outlet of a multi-outlet power strip (e.g. HS300), see [`HS110::get_children`].
+/// Commands are sent to the power strip addressed to the outlet.
+#[derive(Debug, Clone)]
+pub struct ChildPlug {
+    /// Outlet identifier.
+    pub id: String,
+
+    /// Outlet name (alias).
+    pub alias: String,
+
+    /// Power relay state of the outlet (as of [`HS110::get_children`] call).
+    pub state: PowerState,
+
+    /// Power strip the outlet belongs to.
+    parent: HS110,
+}
+
+impl ChildPlug {
+    /// Attempts to switch the outlet on or off, other outlets are left intact, see
+    /// [`HS110::set_child_power_state`].
+    pub fn set_power_state(&self, state: PowerState) -> Result<(), TpLinkHs110Error> {
+        self.parent.set_child_power_state(&self.id, state)
+    }
+
+    /// Returns the power strip the outlet belongs to.
+    pub fn parent(&self) -> &HS110 {
+        &self.parent
+    }
+}
+
+/// Smartplug configuration tied to a place rather than to a device, see [`HS110::snapshot`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    /// Smartplug name (alias).
+    pub alias: String,
+
+    /// Smartplug location (`None` if a smartplug doesn't report it).
+    pub location: Option<Location>,
+
+    /// Schedule rules.
+    pub schedule_rules: Vec<ScheduleRule>,
+}
+
+impl DeviceSnapshot {
+    /// Names of fields which differ from `other` snapshot (identifiers of schedule rules, which
+    /// are assigned by smartplugs, are not compared).
+    pub fn changed_fields(&self, other: &DeviceSnapshot) -> Vec<&'static str> {
+        let rules = |snapshot: &DeviceSnapshot| {
+            snapshot
+                .schedule_rules
+                .iter()
+                .map(|rule| ScheduleRule {
+                    id: String::new(),
+                    ..rule.clone()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        [
+            ("alias", self.alias != other.alias),
+            ("location", self.location != other.location),
+            ("schedule_rules", rules(self) != rules(other)),
+        ]
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect()
+    }
+}
+
+/// Geographic location of a smartplug (used for sunrise/sunset schedule rules).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    /// Latitude in degrees.
+    pub latitude: f64,
+
+    /// Longitude in degrees.
+    pub longitude: f64,
+}
+
+/// Changes [`HS110::swap_devices`] would make, see [`HS110::swap_devices_dry_run`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapPlan {
+    /// Configuration of the smartplug a swap is called on (to be applied to the other one).
+    pub this: DeviceSnapshot,
+
+    /// Configuration of the other smartplug (to be applied to the one a swap is called on).
+    pub other: DeviceSnapshot,
+
+    /// Names of fields which would change (on both smartplugs).
+    pub changed_fields: Vec<&'static str>,
+}
+
+/// Information about smartplug connection to TP-Link cloud. Fields missing in a response (e.g.
+/// firmware of a smartplug which isn't bound to a cloud account omits some) get default values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudInfo {
+    /// Whether a smartplug is bound to a cloud account.
+    #[serde(with = "int_bool")]
+    pub binded: bool,
+
+    /// Whether a smartplug is connected to a cloud.
+    #[serde(with = "int_bool")]
+    pub cld_connection: bool,
+
+    /// Firmware download page.
+    #[serde(rename = "fwDlPage")]
+    pub fw_dl_page: String,
+
+    /// Firmware notification type.
+    #[serde(rename = "fwNotifyType")]
+    pub fw_notify_type: u8,
+
+    /// Illegal type.
+    #[serde(rename = "illegalType")]
+    pub illegal_type: u8,
+
+    /// Cloud server hostname.
+    pub server: String,
+
+    /// Whether a smartplug stopped connecting to a cloud.
+    #[serde(rename = "stopConnect", with = "int_bool")]
+    pub stop_connect: bool,
+
+    /// TCSP information.
+    #[serde(rename = "tcspInfo")]
+    pub tcsp_info: String,
+
+    /// TCSP status.
+    #[serde(rename = "tcspStatus")]
+    pub tcsp_status: u8,
+
+    /// Cloud account username.
+    pub username: String,
+}
+
+/// Over/under-voltage protection configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoltageProtection {
+    /// Lower voltage threshold in milliVolts.
+    pub min_mv: u32,
+
+    /// Upper voltage threshold in milliVolts.
+    pub max_mv: u32,
+
+    /// Whether voltage protection is enabled.
+    pub enabled: bool,
+}
+
+impl VoltageProtection {
+    /// Lowest acceptable voltage threshold (80 V).
+    pub const MIN_MV: u32 = 80_000;
+
+    /// Highest acceptable voltage threshold (280 V).
+    pub const MAX_MV: u32 = 280_000;
+}
+
+/// Over-current protection configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OvercurrentConfig {
+    /// Whether over-current protection is enabled.
+    #[serde(rename = "enable", with = "int_bool")]
+    pub enabled: bool,
+
+    /// Current threshold in milliAmperes.
+    #[serde(rename = "max_ma")]
+    pub threshold_ma: u32,
+}
+
+impl OvercurrentConfig {
+    /// Highest acceptable current threshold (16 A).
+    pub const MAX_MA: u32 = 16_000;
+}
+
+/// Energy meter calibration constants, see [`HS110::get_emeter_calibration`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmeterCalibration {
+    /// Voltage gain.
+    pub vgain: u32,
+
+    /// Current gain.
+    pub igain: u32,
+}
+
+/// Usage statistics of the last week indexed by day of week (0 is Sunday, as in
+/// [`ScheduleRule::wday`]), see [`HS110::get_device_usage_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DeviceUsageStats {
+    /// Time power relay was on (in minutes).
+    pub time_usage: [u32; 7],
+
+    /// Consumed energy (in Wh).
+    pub power_usage: [f64; 7],
+
+    /// Energy saved by schedule and countdown rules (in Wh).
+    pub saved_power: f64,
+}
+
+impl DeviceUsageStats {
+    /// Total time power relay was on during the week (in hours).
+    pub fn total_weekly_on_hours(&self) -> f64 {
+        f64::from(self.time_usage.iter().sum::<u32>()) / 60.0
+    }
+
+    /// Average daily consumed energy (in Wh).
+    pub fn avg_daily_power_usage_wh(&self) -> f64 {
+        self.power_usage.iter().sum::<f64>() / self.power_usage.len() as f64
+    }
+}
+
+/// Schedule rule, i.e. a rule which switches power relay to a given state at a given time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    /// Rule identifier assigned by a smartplug.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+
+    /// Rule name.
+    #[serde(default)]
+    pub name: String,
+
+    /// Whether the rule is enabled.
+    #[serde(with = "int_bool")]
+    pub enable: bool,
+
+    /// Days of week the rule fires on (starting from Sunday).
+    #[serde(with = "int_bool_week")]
+    pub wday: [bool; 7],
+
+    /// What the start time is relative to.
+    pub stime_opt: ScheduleTimeOption,
+
+    /// Start time in minutes after midnight.
+    pub smin: u16,
+
+    /// Start time offset in minutes (used with sunrise/sunset start time option).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soffset: Option<i32>,
+
+    /// Power relay state to switch to when the rule fires.
+    pub sact: PowerState,
+
+    /// Whether the rule repeats weekly (otherwise it fires once on `year`/`month`/`day`).
+    #[serde(with = "int_bool")]
+    pub repeat: bool,
+
+    /// Year of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+
+    /// Month of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub month: Option<u8>,
+
+    /// Day of month of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<u8>,
+}
+
+impl ScheduleRule {
+    /// Represents the rule as a cron expression, the power action is given as a comment.
+    ///
+    /// E.g. a rule switching power off at 22:30 on Monday, Wednesday and Friday is represented as
+    /// `"30 22 * * 1,3,5 # power OFF"`. Returns `None` for a rule which never fires (a rule without
+    /// a date and with no weekdays selected), since cron has no way to express it.
+    pub fn to_cron(&self) -> Option<String> {
+        let (hour, minute) = (self.smin / 60, self.smin % 60);
+        let days = match self.wday {
+            [true, true, true, true, true, true, true] => "*".to_string(),
+            [false, false, false, false, false, false, false] => String::new(),
+            wday => wday
+                .iter()
+                .enumerate()
+                .filter(|(_, enabled)| **enabled)
+                .map(|(day, _)| day.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        let date = match (self.repeat, self.day, self.month) {
+            (false, Some(day), Some(month)) => format!("{day} {month} *"),
+            _ if days.is_empty() => return None,
+            _ => format!("* * {days}"),
+        };
+        let offset = match self.stime_opt {
+            ScheduleTimeOption::Time => String::new(),
+            option => format!(", {option} offset {:+}", self.soffset.unwrap_or(0)),
+        };
+
+        Some(format!(
+            "{minute} {hour} {date} # power {}{offset}",
+            self.sact
+        ))
+    }
+
+    /// Finds out when an enabled rule fires next after a given time (exclusive). Returns `None`
+    /// for disabled rules, rules relative to sunrise/sunset and non-repeating rules which have
+    /// already fired.
+    fn next_firing_after(&self, now: &DeviceTime) -> Option<DeviceTime> {
+        if !self.enable || self.stime_opt != ScheduleTimeOption::Time {
+            return None;
+        }
+
+        let at = |date: &DeviceTime| DeviceTime {
+            hour: (self.smin / 60) as u8,
+            min: (self.smin % 60) as u8,
+            sec: 0,
+            ..*date
+        };
+        if !self.repeat {
+            let fires_at = at(&DeviceTime {
+                year: self.year?,
+                month: self.month?,
+                mday: self.day?,
+                ..*now
+            });
+            return (fires_at.seconds_since_epoch() > now.seconds_since_epoch())
+                .then_some(fires_at);
+        }
+
+        // Checking a week ahead (8 days, as today's firing time might have already passed) is
+        // enough for weekly rules.
+        (0..8)
+            .map_while(|days| now.after(Duration::from_secs(days * 86_400)).ok())
+            .map(|date| at(&date))
+            .find(|fires_at| {
+                self.wday[usize::from(fires_at.weekday())]
+                    && fires_at.seconds_since_epoch() > now.seconds_since_epoch()
+            })
+    }
+}
+
+/// Upcoming firing of a schedule rule, see [`HS110::get_schedule_next_event`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleEvent {
+    /// Identifier of a rule which fires.
+    pub rule_id: String,
+
+    /// Power relay state the rule switches to.
+    pub action: PowerState,
+
+    /// Time the rule fires at.
+    pub fires_at: SystemTime,
+}
+
+/// LED indicator schedule rule, i.e. a rule which switches LED indicator to a given state at
+/// a given time (requires firmware version 2.1 or newer). Fields have the same meaning as the ones
+/// of [`ScheduleRule`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedScheduleRule {
+    /// Rule identifier assigned by a smartplug.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+
+    /// Rule name.
+    #[serde(default)]
+    pub name: String,
+
+    /// Whether the rule is enabled.
+    #[serde(with = "int_bool")]
+    pub enable: bool,
+
+    /// Days of week the rule fires on (starting from Sunday).
+    #[serde(with = "int_bool_week")]
+    pub wday: [bool; 7],
+
+    /// What the start time is relative to.
+    pub stime_opt: ScheduleTimeOption,
+
+    /// Start time in minutes after midnight.
+    pub smin: u16,
+
+    /// Start time offset in minutes (used with sunrise/sunset start time option).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soffset: Option<i32>,
+
+    /// LED indicator state to switch to when the rule fires.
+    #[serde(rename = "sact")]
+    pub action: LedState,
+
+    /// Whether the rule repeats weekly (otherwise it fires once on `year`/`month`/`day`).
+    #[serde(with = "int_bool")]
+    pub repeat: bool,
+
+    /// Year of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+
+    /// Month of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub month: Option<u8>,
+
+    /// Day of month of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<u8>,
+}
+
+/// What a schedule rule time is relative to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleTimeOption {
+    /// Time is given in minutes after midnight.
+    Time,
+
+    /// Time is relative to sunrise.
+    Sunrise,
+
+    /// Time is relative to sunset.
+    Sunset,
+}
+
+impl Display for ScheduleTimeOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ScheduleTimeOption::Time => "time",
+                ScheduleTimeOption::Sunrise => "sunrise",
+                ScheduleTimeOption::Sunset => "sunset",
+            }
+        )
+    }
+}
+
+impl Serialize for ScheduleTimeOption {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i8(match self {
+            ScheduleTimeOption::Time => 0,
+            ScheduleTimeOption::Sunrise => 1,
+            ScheduleTimeOption::Sunset => 2,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ScheduleTimeOption {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match i64::deserialize(deserializer)? {
+            0 => Ok(ScheduleTimeOption::Time),
+            1 => Ok(ScheduleTimeOption::Sunrise),
+            2 => Ok(ScheduleTimeOption::Sunset),
+            other => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Signed(other),
+                &"0, 1 or 2",
+            )),
+        }
+    }
+}
+
+/// Number of rules of each type configured on a smartplug, see [`HS110::get_rules_count`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RulesCount {
+    /// Number of schedule rules.
+    pub schedule: usize,
+
+    /// Number of countdown rules.
+    pub countdown: usize,
+
+    /// Number of anti-theft (away mode) rules.
+    pub antitheft: usize,
+
+    /// Maximal number of schedule rules (if reported by a smartplug).
+    pub max_schedule: Option<usize>,
+
+    /// Maximal number of countdown rules (if reported by a smartplug).
+    pub max_countdown: Option<usize>,
+}
+
+/// Rules of all types configured on a smartplug, see [`HS110::all_rules_summary`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RulesSummary {
+    /// Schedule rules (LED schedule rules are not included).
+    pub schedule_rules: Vec<ScheduleRule>,
+
+    /// Countdown rules.
+    pub countdown_rules: Vec<CountdownRule>,
+
+    /// Anti-theft (away mode) rules.
+    pub antitheft_rules: Vec<AntitheftRule>,
+}
+
+/// Anti-theft (away mode) rule, i.e. a rule which randomly switches power relay on and off
+/// within a given time window to simulate presence.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AntitheftRule {
+    /// Rule identifier assigned by a smartplug.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+
+    /// Rule name.
+    #[serde(default)]
+    pub name: String,
+
+    /// Whether the rule is enabled.
+    #[serde(with = "int_bool")]
+    pub enable: bool,
+
+    /// Days of week the rule is active on (starting from Sunday).
+    #[serde(with = "int_bool_week")]
+    pub wday: [bool; 7],
+
+    /// What the start time is relative to.
+    pub stime_opt: ScheduleTimeOption,
+
+    /// Start time in minutes after midnight.
+    pub smin: u16,
+
+    /// What the end time is relative to.
+    pub etime_opt: ScheduleTimeOption,
+
+    /// End time in minutes after midnight.
+    pub emin: u16,
+
+    /// How many times power relay is switched within the time window.
+    pub frequency: u32,
+
+    /// Whether the rule repeats weekly (otherwise it's active once on `year`/`month`/`day`).
+    #[serde(with = "int_bool")]
+    pub repeat: bool,
+
+    /// Year of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+
+    /// Month of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub month: Option<u8>,
+
+    /// Day of month of a non-repeating rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<u8>,
+}
+
+/// Countdown rule, i.e. a rule which switches power relay to a given state after a delay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountdownRule {
+    /// Rule identifier assigned by a smartplug.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+
+    /// Rule name.
+    #[serde(default)]
+    pub name: String,
+
+    /// Whether the rule is enabled.
+    #[serde(with = "int_bool")]
+    pub enable: bool,
+
+    /// Delay (in seconds) after which the rule fires.
+    pub delay: u32,
+
+    /// Power relay state to switch to when the rule fires.
+    pub act: PowerState,
+
+    /// Seconds left until the rule fires (reported by a smartplug for an active rule).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remain: Option<u32>,
+}
+
+/// (De)serialization of booleans which smartplugs represent as `0`/`1` integers.
+mod int_bool {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S>(value: &bool, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u8(u8::from(*value))
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<bool, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(i64::deserialize(deserializer)? != 0)
+    }
+}
+
+/// (De)serialization of days of week which smartplugs represent as an array of `0`/`1` integers.
+mod int_bool_week {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S>(value: &[bool; 7], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value.map(u8::from).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<[bool; 7], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(<[i64; 7]>::deserialize(deserializer)?.map(|day| day != 0))
+    }
+}
+
+/// Common smartplug configuration, see [`HS110::apply_preset`].
+pub enum DevicePreset {
+    /// LED is switched off, power relay is switched off during peak hours (from
+    /// [`DevicePreset::PEAK_HOURS_START`] till [`DevicePreset::PEAK_HOURS_END`] every day) and
+    /// schedule rules are enabled. Schedule rules named with
+    /// [`DevicePreset::ENERGY_SAVING_RULE_PREFIX`] are replaced.
+    EnergySaving,
+
+    /// Power relay is switched on, schedule and countdown rules are disabled.
+    AlwaysOn,
+
+    /// LED is switched off.
+    NightMode,
+
+    /// User-defined configuration.
+    #[allow(clippy::type_complexity)]
+    Custom(Box<dyn Fn(&HS110) -> Result<(), TpLinkHs110Error>>),
+}
+
+impl DevicePreset {
+    /// Prefix of names of schedule rules added by [`DevicePreset::EnergySaving`].
+    pub const ENERGY_SAVING_RULE_PREFIX: &'static str = "Energy saving:";
+
+    /// Start of peak hours in minutes after midnight (17:00).
+    pub const PEAK_HOURS_START: u16 = 17 * 60;
+
+    /// End of peak hours in minutes after midnight (21:00).
+    pub const PEAK_HOURS_END: u16 = 21 * 60;
+}
+
+impl std::fmt::Debug for DevicePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EnergySaving => write!(f, "EnergySaving"),
+            Self::AlwaysOn => write!(f, "AlwaysOn"),
+            Self::NightMode => write!(f, "NightMode"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// Mode a smartplug is in, i.e. which kind of rules currently controls its power relay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActiveMode {
+    /// No rules are active.
+    None,
+
+    /// Power relay is controlled by schedule rules.
+    Schedule,
+
+    /// Power relay is controlled by a countdown rule.
+    CountDown,
+
+    /// Power relay is controlled by anti-theft (away mode) rules.
+    AntiTheft,
+}
+
+/// Smartplug's power relay state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerState {
+    /// Power relay is ON, i.e. smartplug is powering its outlet (connected device).
+    On,
+
+    /// Power relay is OFF, i.e. there is no power on smartplug's outlet.
+    Off,
+}
+
+impl PowerState {
+    /// Returns numeric representation of the state: `1` for ON, `0` for OFF.
+    pub fn as_u8(self) -> u8 {
+        u8::from(self == PowerState::On)
+    }
+
+    /// Attempts to convert a numeric representation (`0` or `1`) to a power relay state.
+    pub fn from_u8(value: u8) -> Result<PowerState, TpLinkHs110Error> {
+        match value {
+            0 => Ok(PowerState::Off),
+            1 => Ok(PowerState::On),
+            other => Err(TpLinkHs110Error::InvalidParameter(format!(
+                "power state should be 0 or 1, got {other}"
+            ))),
+        }
+    }
+}
+
+impl Display for PowerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PowerState::On => "ON",
+                PowerState::Off => "OFF",
+            }
+        )
+    }
+}
+
+impl Not for PowerState {
+    type Output = PowerState;
+
+    fn not(self) -> Self::Output {
+        match self {
+            PowerState::On => PowerState::Off,
+            PowerState::Off => PowerState::On,
+        }
+    }
+}
+
+impl Serialize for PowerState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u8(self.as_u8())
+    }
+}
+
+impl<'de> Deserialize<'de> for PowerState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match i64::deserialize(deserializer)? {
+            0 => Ok(PowerState::Off),
+            1 => Ok(PowerState::On),
+            other => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Signed(other),
+                &"0 or 1",
+            )),
+        }
+    }
+}
+
+impl From<PowerState> for bool {
+    fn from(value: PowerState) -> Self {
+        match value {
+            PowerState::On => true,
+            PowerState::Off => false,
+        }
+    }
+}
+
+impl From<bool> for PowerState {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::On
+        } else {
+            Self::Off
+        }
+    }
+}
+
+/// Smartplug LED indicator state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LedState {
+    /// LED light is ON.
+    On,
+
+    /// LED light is OFF.
+    Off,
+}
+
+impl LedState {
+    /// Returns numeric representation of the state: `1` for ON, `0` for OFF.
+    pub fn as_u8(self) -> u8 {
+        u8::from(self == LedState::On)
+    }
+}
+
+impl Display for LedState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LedState::On => "ON",
+                LedState::Off => "OFF",
+            }
+        )
+    }
+}
+
+impl Not for LedState {
+    type Output = LedState;
+
+    fn not(self) -> Self::Output {
+        match self {
+            LedState::On => LedState::Off,
+            LedState::Off => LedState::On,
+        }
+    }
+}
+
+impl Serialize for LedState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u8(self.as_u8())
+    }
+}
+
+impl<'de> Deserialize<'de> for LedState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match i64::deserialize(deserializer)? {
+            0 => Ok(LedState::Off),
+            1 => Ok(LedState::On),
+            other => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Signed(other),
+                &"0 or 1",
+            )),
+        }
+    }
+}
+
+impl From<LedState> for bool {
+    fn from(value: LedState) -> Self {
+        match value {
+            LedState::On => true,
+            LedState::Off => false,
+        }
+    }
+}
+
+impl From<bool> for LedState {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::On
+        } else {
+            Self::Off
+        }
+    }
+}
+
+#[cfg(test)]
+// Hardware tests dereference `TEST_TARGET_ADDR` explicitly (`&*TEST_TARGET_ADDR`).
+#[allow(clippy::explicit_auto_deref)]
+mod tests {
+    use crate::{mock::MockSmartplug, *};
+    use once_cell::sync::Lazy;
+    use serial_test::serial;
+    use std::{
+        collections::HashSet,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    static TEST_TARGET_ADDR: Lazy<String> =
+        Lazy::new(|| std::env::var("TEST_TARGET_ADDR").expect("TEST_TARGET_ADDR env variable"));
+
+    #[test]
+    #[serial]
+    #[allow(deprecated)]
+    fn hostname() {
+        let smartplug = HS110::new(&*TEST_TARGET_ADDR)
+            .unwrap()
+            .with_timeout(Duration::from_secs(3));
+        assert!(smartplug.hostname().is_ok());
+
+        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
+        assert!(smartplug.hostname().is_ok());
+
+        assert!(matches!(
+            smartplug.hw_version_full().map(|info| info.hw_ver),
+            Ok(HwVersion::Version1 | HwVersion::Version2)
+        ));
+    }
+
+    #[test]
+    fn switch_led_on_off() {
+        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
+
+        let original_state = smartplug.led_state().expect("failed to obtain LED state");
+
+        assert!(smartplug.set_led_state(!original_state).is_ok());
+        assert_eq!(
+            smartplug.led_state().expect("failed to obtain LED state"),
+            !original_state
+        );
+
+        assert!(smartplug.set_led_state(original_state).is_ok());
+        assert_eq!(
+            smartplug.led_state().expect("failed to obtain LED state"),
+            original_state
+        );
+    }
+
+    #[test]
+    #[serial]
+    #[ignore = "power-cycles devices connected to the plug"]
+    fn switch_power_on_off() {
+        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
+
+        let original_state = smartplug
+            .power_state()
+            .expect("failed to obtain smartplug power state");
+
+        assert!(smartplug.set_power_state(!original_state).is_ok());
+        assert_eq!(
+            smartplug
+                .power_state()
+                .expect("failed to obtain smartplug power state"),
+            !original_state
+        );
+
+        assert!(smartplug.set_power_state(original_state).is_ok());
+        assert_eq!(
+            smartplug
+                .power_state()
+                .expect("failed to obtain smartplug power state"),
+            original_state
+        );
+    }
+
+    #[test]
+    fn get_cloudinfo() {
+        assert!(HS110::new(&*TEST_TARGET_ADDR)
+            .unwrap()
+            .cloud_info_typed()
+            .is_ok());
+    }
+
+    #[test]
+    #[serial]
+    fn access_points_list_and_scan() {
+        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
+
+        smartplug
+            .ap_list(false)
+            .expect("failed to obtain AP list")
+            .as_array()
+            .expect("json array is expected");
+
+        assert!(
+            !smartplug
+                .ap_list(true)
+                .expect("failed to obtain AP list")
+                .as_array()
+                .expect("json array is expected")
+                .is_empty(),
+            "list of access points is not expected to be empty"
+        );
+    }
+
+    #[test]
+    #[serial]
+    #[ignore = "power-cycles devices connected to the plug"]
+    fn reboot() {
+        let hs110 = HS110::new(&*TEST_TARGET_ADDR)
+            .unwrap()
+            .with_timeout(Duration::from_secs(10));
+        hs110
+            .reboot_and_wait(Duration::from_secs(200))
+            .expect("device didn't back online after reboot");
+    }
+
+    #[test]
+    fn schedule_and_countdown_enable() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"schedule": {"get_rules": {"rule_list": [], "version": 2, "enable": 1, "err_code": 0}}}),
+            json!({"schedule": {"set_overall_enable": {"err_code": 0}}}),
+            json!({"count_down": {"get_rules": {"rule_list": [], "enable": 0, "err_code": 0}}}),
+            json!({"count_down": {"set_overall_enable": {"err_code": -3}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert!(smartplug.get_schedule_enable().unwrap());
+        assert!(smartplug.set_schedule_enable(false).is_ok());
+        assert!(!smartplug.get_countdown_enable().unwrap());
+        assert!(matches!(
+            smartplug.set_countdown_enable(true),
+            Err(TpLinkHs110Error::SmartplugErrCode(-3))
+        ));
+
+        let requests = mock.requests();
+        assert_eq!(
+            requests[1],
+            json!({"schedule": {"set_overall_enable": {"enable": 0}}})
+        );
+        assert_eq!(
+            requests[3],
+            json!({"count_down": {"set_overall_enable": {"enable": 1}}})
+        );
+    }
+
+    #[test]
+    fn hw_version_full() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "err_code": 0,
+            "fwId": "00000000000000000000000000000000",
+            "hwId": "47E30DA8382497D2E82691B52A3B2EB3",
+            "hw_ver": "2.0",
+            "oemId": "4D345ECE299C0641C96E27CE2430548B",
+        }}})]);
+
+        assert_eq!(
+            mock.hs110().hw_version_full().unwrap(),
+            HwVersionInfo {
+                hw_ver: HwVersion::Version2,
+                hw_id: "47E30DA8382497D2E82691B52A3B2EB3".into(),
+                fw_id: "00000000000000000000000000000000".into(),
+                oem_id: "4D345ECE299C0641C96E27CE2430548B".into(),
+            }
+        );
+        assert_eq!(HwVersion::from("3.1"), HwVersion::Unsupported("3.1".into()));
+    }
+
+    #[test]
+    fn device_model() {
+        assert_eq!(
+            "HS110(EU)".parse(),
+            Ok(DeviceModel::Hs110(Some("EU".into())))
+        );
+        assert_eq!(
+            "HS100(US)".parse(),
+            Ok(DeviceModel::Hs100(Some("US".into())))
+        );
+        assert_eq!("HS110".parse(), Ok(DeviceModel::Hs110(None)));
+        assert_eq!(
+            "HS300(US)".parse(),
+            Ok(DeviceModel::Unknown("HS300(US)".into()))
+        );
+        assert_eq!(
+            "HS110(EU".parse(),
+            Ok(DeviceModel::Unknown("HS110(EU".into()))
+        );
+
+        assert!(DeviceModel::Hs110(None).has_energy_monitor());
+        assert!(!DeviceModel::Hs100(Some("EU".into())).has_energy_monitor());
+        assert!(!DeviceModel::Unknown("KP115".into()).has_energy_monitor());
+
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"system": {"get_sysinfo": {"err_code": 0, "model": "HS110(EU)"}}}),
+        ]);
+        assert_eq!(
+            mock.hs110().device_model().unwrap(),
+            DeviceModel::Hs110(Some("EU".into()))
+        );
+    }
+
+    #[test]
+    fn voltage_protection() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"emeter": {"get_voltage_protection": {"min_mv": 190000, "max_mv": 250000, "enable": 1, "err_code": 0}}}),
+            json!({"emeter": {"set_voltage_protection": {"err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert_eq!(
+            smartplug.get_voltage_protection().unwrap(),
+            VoltageProtection {
+                min_mv: 190000,
+                max_mv: 250000,
+                enabled: true
+            }
+        );
+        assert!(smartplug
+            .set_voltage_protection(200000, 245000, false)
+            .is_ok());
+        assert_eq!(
+            mock.requests()[1],
+            json!({"emeter": {"set_voltage_protection": {"min_mv": 200000, "max_mv": 245000, "enable": 0}}})
+        );
+
+        for (min_mv, max_mv) in [
+            (79999, 250000),
+            (190000, 280001),
+            (250000, 190000),
+            (230000, 230000),
+        ] {
+            assert!(matches!(
+                smartplug.set_voltage_protection(min_mv, max_mv, true),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[test]
+    fn countdown_remaining() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"count_down": {"get_rules": {"rule_list": [
+                {"id": "7C90311A1CD3227F25C6001D88F7FC13", "name": "off", "enable": 0, "delay": 600, "act": 0},
+                {"id": "C2A3B7F3E0B4A4F8D77A6C0E1F9A1B2C", "name": "on", "enable": 1, "delay": 1800, "act": 1, "remain": 1795},
+            ], "err_code": 0}}}),
+            json!({"count_down": {"get_rules": {"rule_list": [
+                {"id": "C2A3B7F3E0B4A4F8D77A6C0E1F9A1B2C", "name": "on", "enable": 1, "delay": 1800, "act": 1},
+            ], "err_code": 0}}}),
+            json!({"count_down": {"get_rules": {"rule_list": [], "err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert_eq!(
+            smartplug.countdown_remaining().unwrap(),
+            Some(Duration::from_secs(1795))
+        );
+        assert_eq!(
+            smartplug.countdown_remaining().unwrap(),
+            Some(Duration::from_secs(1800))
+        );
+        assert_eq!(smartplug.countdown_remaining().unwrap(), None);
+    }
+
+    #[test]
+    fn device_model_ordering() {
+        let mut models = vec![
+            DeviceModel::Unknown("KP115(EU)".into()),
+            DeviceModel::Hs110(Some("US".into())),
+            DeviceModel::Hs100(None),
+            DeviceModel::Hs110(Some("EU".into())),
+            DeviceModel::Hs100(Some("EU".into())),
+        ];
+        models.sort();
+
+        assert_eq!(
+            models,
+            vec![
+                DeviceModel::Hs100(None),
+                DeviceModel::Hs100(Some("EU".into())),
+                DeviceModel::Hs110(Some("EU".into())),
+                DeviceModel::Hs110(Some("US".into())),
+                DeviceModel::Unknown("KP115(EU)".into()),
+            ]
+        );
+        assert!(models
+            .windows(2)
+            .all(|pair| pair[0].capability_level() <= pair[1].capability_level()));
+    }
+
+    #[test]
+    fn soft_reset() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"system": {"reset": {"err_code": 0}}}),
+            json!({"system": {"reset": {"err_code": -3, "err_msg": "invalid argument"}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert!(smartplug.soft_reset(Some(Duration::from_secs(5))).is_ok());
+        assert_eq!(
+            mock.requests()[0],
+            json!({"system": {"reset": {"delay": 5, "preserve_net_setting": 1}}})
+        );
+
+        assert!(matches!(
+            smartplug.soft_reset(None),
+            Err(TpLinkHs110Error::SmartplugErrCode(-3))
+        ));
+
+        assert!(matches!(
+            smartplug.soft_reset(Some(Duration::from_secs(u64::from(u32::MAX) + 1))),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[test]
+    fn schedules_as_cron() {
+        let mock = MockSmartplug::with_responses(vec![json!({"schedule": {"get_rules": {
+            "rule_list": [
+                {
+                    "id": "8AA75A50A8440B17941D192BD9E01FFA", "name": "night", "enable": 1,
+                    "wday": [0, 1, 0, 1, 0, 1, 0], "stime_opt": 0, "smin": 1350, "sact": 0,
+                    "etime_opt": -1, "emin": 0, "eact": -1, "repeat": 1,
+                },
+                {
+                    "id": "E7B2D1B3F1A6C2D1E4F5A6B7C8D9E0F1", "name": "morning", "enable": 1,
+                    "wday": [1, 1, 1, 1, 1, 1, 1], "stime_opt": 1, "smin": 390, "soffset": 15,
+                    "sact": 1, "repeat": 1,
+                },
+                {
+                    "id": "0F1E2D3C4B5A69788796A5B4C3D2E1F0", "name": "once", "enable": 0,
+                    "wday": [0, 0, 0, 0, 0, 1, 0], "stime_opt": 0, "smin": 5, "sact": 1,
+                    "repeat": 0, "year": 2024, "month": 4, "day": 26,
+                },
+                {
+                    "id": "1A2B3C4D5E6F708192A3B4C5D6E7F809", "name": "never", "enable": 1,
+                    "wday": [0, 0, 0, 0, 0, 0, 0], "stime_opt": 0, "smin": 600, "sact": 1,
+                    "repeat": 1,
+                },
+            ],
+            "version": 2, "enable": 1, "err_code": 0,
+        }}})]);
+
+        assert_eq!(
+            mock.hs110().get_schedules_as_cron().unwrap(),
+            [
+                "30 22 * * 1,3,5 # power OFF",
+                "30 6 * * * # power ON, sunrise offset +15",
+                "5 0 26 4 * # power ON",
+            ]
+        );
+        assert_eq!(
+            mock.hs110().get_schedule_rules().unwrap()[3].to_cron(),
+            None
+        );
+    }
+
+    #[test]
+    fn response_verification() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let response = json!({"system": {"get_sysinfo": {"alias": "Bathroom", "err_code": 0}}});
+        let checksum = crc32(response.to_string().as_bytes());
+        let mut with_checksum = response.clone();
+        with_checksum["checksum"] = checksum.into();
+        let mut with_bad_checksum = response.clone();
+        with_bad_checksum["checksum"] = (checksum ^ 1).into();
+
+        let mock = MockSmartplug::with_responses(vec![
+            response,
+            with_checksum,
+            with_bad_checksum.clone(),
+            with_bad_checksum,
+        ]);
+
+        let smartplug = mock.hs110();
+        assert!(!smartplug.verify);
+        let smartplug = smartplug.with_response_verification(true);
+        assert!(smartplug.verify);
+
+        assert!(smartplug.info().is_ok());
+        assert!(smartplug.info().is_ok());
+        assert!(matches!(
+            smartplug.info(),
+            Err(TpLinkHs110Error::ChecksumMismatch { reported, calculated })
+                if reported == checksum ^ 1 && calculated == checksum
+        ));
+        assert!(smartplug.with_response_verification(false).info().is_ok());
+    }
+
+    #[test]
+    fn status_summary() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({
+                "system": {"get_sysinfo": {"alias": "Bathroom", "relay_state": 1, "led_off": 0, "rssi": -64, "err_code": 0}},
+                "emeter": {"get_realtime": {"current_ma": 250, "power_mw": 57500, "total_wh": 12500, "voltage_mv": 230000, "err_code": 0}},
+            }),
+            json!({
+                "system": {"get_sysinfo": {"alias": "Kitchen", "relay_state": 0, "led_off": 1, "rssi": -70, "err_code": 0}},
+                "emeter": {"err_code": -1, "err_msg": "module not support"},
+            }),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert_eq!(
+            smartplug.get_status_summary().unwrap(),
+            StatusSummary {
+                alias: "Bathroom".into(),
+                power: PowerState::On,
+                led: LedState::On,
+                emeter: Some(EmeterReading {
+                    voltage_v: 230.0,
+                    current_a: 0.25,
+                    power_w: 57.5,
+                    total_kwh: 12.5,
+                }),
+                rssi: -64,
+            }
+        );
+        assert_eq!(
+            smartplug.get_status_summary().unwrap(),
+            StatusSummary {
+                alias: "Kitchen".into(),
+                power: PowerState::Off,
+                led: LedState::Off,
+                emeter: None,
+                rssi: -70,
+            }
+        );
+        assert_eq!(
+            mock.requests(),
+            vec![json!({"system": {"get_sysinfo": {}}, "emeter": {"get_realtime": {}}}); 2]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "measurements")]
+    fn emeter_typed_units() {
+        let mut emeter = json!({"current_ma": 27.824, "power_mw": 770.242, "total_wh": 625833.0, "voltage_mv": 228603.726});
+        HS110::normalize_emeter(&mut emeter);
+        let reading = EmeterReading::try_from(&emeter).unwrap();
+
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9 * b.abs().max(1.0);
+        assert!(close(reading.voltage_typed().as_millivolts(), 228603.726));
+        assert!(close(reading.current_typed().as_milliamperes(), 27.824));
+        assert!(close(reading.current_typed().as_microamperes(), 27824.0));
+        assert!(close(reading.power_typed().as_milliwatts(), 770.242));
+    }
+
+    #[test]
+    fn namespaced_commands() {
+        let mock = MockSmartplug::start(|request| {
+            let (namespace, command) = request.as_object().unwrap().iter().next().unwrap();
+            let (name, _) = command.as_object().unwrap().iter().next().unwrap();
+            json!({ namespace: { name: {"err_code": 0} } })
+        });
+        let smartplug = mock.hs110();
+
+        let command = json!({"get_rules": {}});
+        assert_eq!(
+            smartplug.send_schedule_command(&command).unwrap(),
+            json!({"get_rules": {"err_code": 0}})
+        );
+        smartplug.send_countdown_command(&command).unwrap();
+        smartplug
+            .send_emeter_command(&json!({"get_daystat": {"month": 4, "year": 2024}}))
+            .unwrap();
+        smartplug
+            .send_system_command(&json!({"set_led_off": {"off": 1}}))
+            .unwrap();
+        assert_eq!(
+            smartplug
+                .send_raw_command(r#"{"netif":{"get_stainfo":{}}}"#)
+                .unwrap(),
+            json!({"netif": {"get_stainfo": {"err_code": 0}}})
+        );
+
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"schedule": {"get_rules": {}}}),
+                json!({"count_down": {"get_rules": {}}}),
+                json!({"emeter": {"get_daystat": {"month": 4, "year": 2024}}}),
+                json!({"system": {"set_led_off": {"off": 1}}}),
+                json!({"netif": {"get_stainfo": {}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn cloud_info_typed() {
+        let fixture = json!({
+            "binded": 1,
+            "cld_connection": 1,
+            "fwDlPage": "",
+            "fwNotifyType": 0,
+            "illegalType": 0,
+            "server": "n-devs.tplinkcloud.com",
+            "stopConnect": 0,
+            "tcspInfo": "",
+            "tcspStatus": 1,
+            "username": "username@example.com",
+        });
+        let cloud_info = serde_json::from_value::<CloudInfo>(fixture.clone()).unwrap();
+        assert_eq!(
+            cloud_info,
+            CloudInfo {
+                binded: true,
+                cld_connection: true,
+                fw_dl_page: String::new(),
+                fw_notify_type: 0,
+                illegal_type: 0,
+                server: "n-devs.tplinkcloud.com".into(),
+                stop_connect: false,
+                tcsp_info: String::new(),
+                tcsp_status: 1,
+                username: "username@example.com".into(),
+            }
+        );
+        assert_eq!(serde_json::to_value(&cloud_info).unwrap(), fixture);
+
+        let mut response = fixture;
+        response["err_code"] = 0.into();
+        let mock = MockSmartplug::with_responses(vec![json!({"cnCloud": {"get_info": response}})]);
+        assert_eq!(mock.hs110().cloud_info_typed().unwrap(), cloud_info);
+    }
+
+    #[test]
+    fn address_getters() {
+        let smartplug = HS110::new("192.168.0.155").unwrap();
+        assert_eq!(
+            smartplug.socket_addr(),
+            "192.168.0.155:9999".parse().unwrap()
+        );
+        assert_eq!(
+            smartplug.ip_addr(),
+            "192.168.0.155".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(smartplug.ip_v4(), Some(Ipv4Addr::new(192, 168, 0, 155)));
+        assert_eq!(smartplug.port(), 9999);
+
+        let smartplug = HS110::new("[fe80::1]:10000").unwrap();
+        assert_eq!(smartplug.ip_addr(), "fe80::1".parse::<IpAddr>().unwrap());
+        assert_eq!(smartplug.ip_v4(), None);
+        assert_eq!(smartplug.port(), 10000);
+    }
+
+    #[test]
+    fn verify_connectivity() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"system": {"get_sysinfo": {
+                "alias": "Bathroom",
+                "deviceId": "800644100000BB3AC70000FB15245D6C190F936B",
+                "model": "HS110(EU)",
+                "type": "IOT.SMARTPLUGSWITCH",
+                "err_code": 0,
+            }}}),
+            json!({"system": {"get_sysinfo": {
+                "alias": "Bulb",
+                "deviceId": "800644100000BB3AC70000FB15245D6C190F936C",
+                "model": "LB100(EU)",
+                "type": "IOT.SMARTBULB",
+                "err_code": 0,
+            }}}),
+            json!({"status": "ok"}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert_eq!(
+            smartplug.verify_connectivity().unwrap(),
+            DeviceIdentity {
+                device_id: "800644100000BB3AC70000FB15245D6C190F936B".into(),
+                model: "HS110(EU)".into(),
+                alias: "Bathroom".into(),
+            }
+        );
+        assert!(matches!(
+            smartplug.verify_connectivity(),
+            Err(TpLinkHs110Error::NotASmartPlug)
+        ));
+        assert!(matches!(
+            smartplug.verify_connectivity(),
+            Err(TpLinkHs110Error::NotASmartPlug)
+        ));
+    }
+
+    #[test]
+    fn graceful_shutdown() {
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/count_down/add_rule").is_some() {
+                json!({"count_down": {"add_rule": {"id": "7C90C0E1", "err_code": 0}}})
+            } else {
+                json!({"count_down": {"delete_all_rules": {"err_code": 0}}})
+            }
+        });
+        let smartplug = mock.hs110();
+
+        smartplug
+            .graceful_shutdown(Duration::from_secs(120), true)
+            .unwrap();
+        smartplug
+            .graceful_shutdown(Duration::from_secs(30), false)
+            .unwrap();
+        assert!(matches!(
+            smartplug.graceful_shutdown(Duration::from_secs(u64::MAX), true),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"count_down": {"delete_all_rules": {}}}),
+                json!({"count_down": {"add_rule": {
+                    "name": "graceful shutdown", "enable": 1, "delay": 120, "act": 0
+                }}}),
+                json!({"count_down": {"delete_all_rules": {}}}),
+                json!({"count_down": {"add_rule": {
+                    "name": "graceful shutdown", "enable": 1, "delay": 30, "act": 1
+                }}}),
+            ]
+        );
+    }
+
+    #[test]
+    // Hashing relies on an address only, which is immutable, shared last error doesn't matter.
+    #[allow(clippy::mutable_key_type)]
+    fn equality_and_hash() {
+        use std::{collections::HashMap, hash::BuildHasher};
+
+        let a = HS110::new("192.168.0.10").unwrap();
+        let b = HS110::new("192.168.0.10:9999")
+            .unwrap()
+            .with_timeout(Duration::from_secs(1));
+        let c = HS110::new("192.168.0.11").unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        let hasher = std::collections::hash_map::RandomState::new();
+        assert_eq!(hasher.hash_one(&a), hasher.hash_one(&b));
+
+        let mut states = HashMap::new();
+        states.insert(a, "kitchen");
+        states.insert(c, "hall");
+        assert_eq!(states.get(&b), Some(&"kitchen"));
+        assert_eq!(states.len(), 2);
+    }
+
+    #[test]
+    fn export_and_import_json() {
+        let mock = MockSmartplug::start(|request| {
+            let (module, command) = request
+                .as_object()
+                .and_then(|modules| modules.iter().next())
+                .and_then(|(module, commands)| {
+                    Some((module.clone(), commands.as_object()?.keys().next()?.clone()))
+                })
+                .unwrap();
+            let response = match (module.as_str(), command.as_str()) {
+                ("system", "get_sysinfo") => {
+                    json!({"alias": "Kitchen", "led_off": 1, "relay_state": 1, "err_code": 0})
+                }
+                ("cnCloud", "get_info") => json!({"binded": 1, "err_code": 0}),
+                ("schedule", "get_rules") => json!({"rule_list": [{
+                    "id": "E3A6B1E7", "name": "evening", "enable": 1,
+                    "wday": [0, 1, 0, 1, 0, 1, 0], "stime_opt": 0, "smin": 1350,
+                    "sact": 0, "repeat": 1
+                }, {
+                    "id": "LED1", "name": "night", "enable": 1, "enable_type": 1,
+                    "wday": [1, 1, 1, 1, 1, 1, 1], "stime_opt": 0, "smin": 1380,
+                    "sact": 0, "repeat": 1
+                }], "err_code": 0}),
+                ("count_down", "get_rules") => json!({"rule_list": [], "err_code": 0}),
+                ("emeter", "get_realtime") => {
+                    json!({"err_code": -1, "err_msg": "module not support"})
+                }
+                ("schedule", "add_rule") => json!({"id": "F00D", "err_code": 0}),
+                _ => json!({"err_code": 0}),
+            };
+            json!({module: {command: response}})
+        });
+        let smartplug = mock.hs110();
+
+        let export = smartplug.export_json().unwrap();
+        let parsed = serde_json::from_str::<Value>(&export).unwrap();
+        assert_eq!(parsed["export_version"], 1);
+        assert_eq!(parsed["sysinfo"]["alias"], "Kitchen");
+        assert_eq!(parsed["cloudinfo"]["binded"], 1);
+        assert_eq!(parsed["schedule_rules"][0]["name"], "evening");
+        assert_eq!(parsed["schedule_rules"].as_array().unwrap().len(), 1);
+        assert_eq!(parsed["countdown_rules"], json!([]));
+        assert_eq!(parsed["emeter"], Value::Null);
+
+        let requests_before_import = mock.requests().len();
+        smartplug.import_json(&export).unwrap();
+        assert_eq!(
+            mock.requests()[requests_before_import..],
+            [
+                json!({"system": {"set_dev_alias": {"alias": "Kitchen"}}}),
+                json!({"system": {"set_led_off": {"off": 1}}}),
+                json!({"schedule": {"get_rules": {}}}),
+                // The LED schedule rule is not deleted.
+                json!({"schedule": {"delete_rules": {"id_list": ["E3A6B1E7"]}}}),
+                json!({"schedule": {"add_rule": {
+                    "name": "evening", "enable": 1, "wday": [0, 1, 0, 1, 0, 1, 0],
+                    "stime_opt": 0, "smin": 1350, "sact": 0, "repeat": 1
+                }}}),
+            ]
+        );
+
+        assert!(matches!(
+            smartplug.import_json(r#"{"export_version": 2}"#),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn emeter_timestamped() {
+        let mock = MockSmartplug::with_responses(vec![json!({"emeter": {"get_realtime": {
+            "voltage_mv": 229000, "current_ma": 500, "power_mw": 114500, "total_wh": 1500,
+            "err_code": 0
+        }}})]);
+
+        let before = SystemTime::now();
+        let timestamped = mock.hs110().emeter_timestamped().unwrap();
+        let after = SystemTime::now();
+
+        assert!(before <= timestamped.timestamp && timestamped.timestamp <= after);
+        assert_eq!(timestamped.reading.power_w, 114.5);
+
+        let timestamped = timestamped
+            .reading
+            .with_timestamp(UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789));
+        assert_eq!(timestamped.as_unix_nanos(), 1_700_000_000_123_456_789);
+        assert_eq!(timestamped.as_unix_millis(), 1_700_000_000_123);
+    }
+
+    #[test]
+    fn rules_count() {
+        let mock = MockSmartplug::start(|request| match request.as_object() {
+            Some(modules) if modules.contains_key("schedule") => {
+                json!({"schedule": {"get_rules": {"rule_list": [
+                    {"id": "1", "enable": 1}, {"id": "2", "enable": 0}
+                ], "enable": 1, "version": 2, "max_rules": 32, "err_code": 0}}})
+            }
+            Some(modules) if modules.contains_key("count_down") => {
+                json!({"count_down": {"get_rules": {"rule_list": [
+                    {"id": "3", "enable": 1, "delay": 60, "act": 0}
+                ], "err_code": 0}}})
+            }
+            _ => json!({"anti_theft": {"get_rules": {"rule_list": [], "err_code": 0}}}),
+        });
+
+        assert_eq!(
+            mock.hs110().get_rules_count().unwrap(),
+            RulesCount {
+                schedule: 2,
+                countdown: 1,
+                antitheft: 0,
+                max_schedule: Some(32),
+                max_countdown: None,
+            }
+        );
+    }
+
+    #[test]
+    fn ipv6_addresses() {
+        for (addr, expected) in [
+            ("::1", "[::1]:9999"),
+            ("[::1]:9999", "[::1]:9999"),
+            ("[2001:db8::1]", "[2001:db8::1]:9999"),
+            ("[2001:db8::1]:9999", "[2001:db8::1]:9999"),
+            ("[2001:db8::1]:10000", "[2001:db8::1]:10000"),
+        ] {
+            assert_eq!(
+                HS110::new(addr).unwrap().socket_addr(),
+                expected.parse().unwrap(),
+                "{addr}"
+            );
+        }
+
+        for addr in ["[::1", "::1]", "[::1]:", "2001:db8::g"] {
+            assert!(
+                matches!(HS110::new(addr), Err(TpLinkHs110Error::AddrParse(_))),
+                "{addr}"
+            );
+        }
+    }
+
+    #[test]
+    fn await_stable_emeter() {
+        let reading = |power_mw: u32| {
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230000, "current_ma": power_mw / 230, "power_mw": power_mw,
+                "total_wh": 1000, "err_code": 0
+            }}})
+        };
+
+        let mock = MockSmartplug::with_responses(vec![
+            reading(500_000),
+            reading(1_500_000),
+            reading(800_000),
+            reading(1_200_000),
+            reading(1_000_000),
+            reading(1_010_000),
+            reading(990_000),
+            reading(1_000_000),
+        ]);
+        let stable = mock
+            .hs110()
+            .await_stable_emeter(Duration::from_millis(50), 5.0)
+            .unwrap();
+        assert!((990.0..=1010.0).contains(&stable.power_w));
+        assert_eq!(stable.voltage_v, 230.0);
+        assert!(mock.requests().len() >= 7);
+
+        let toggle = AtomicBool::new(false);
+        let mock = MockSmartplug::start(move |_| {
+            reading(if toggle.fetch_xor(true, Ordering::SeqCst) {
+                500_000
+            } else {
+                1_500_000
+            })
+        });
+        assert!(matches!(
+            mock.hs110()
+                .await_stable_emeter(Duration::from_millis(20), 5.0),
+            Err(TpLinkHs110Error::StabilizationTimeout(_))
+        ));
+    }
+
+    #[test]
+    fn power_strip_children() {
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/system/get_sysinfo").is_some() {
+                json!({"system": {"get_sysinfo": {
+                    "model": "HS300(US)", "alias": "Power strip", "child_num": 2,
+                    "children": [
+                        {"id": "8006AF35494E7DB13DDE9B8F40BF2E7A00", "state": 1, "alias": "Desk"},
+                        {"id": "8006AF35494E7DB13DDE9B8F40BF2E7A01", "state": 0, "alias": "Lamp"}
+                    ],
+                    "err_code": 0
+                }}})
+            } else if request.pointer("/emeter/get_realtime").is_some() {
+                json!({"emeter": {"get_realtime": {
+                    "voltage_mv": 120500, "current_ma": 100, "power_mw": 12050, "total_wh": 42,
+                    "err_code": 0
+                }}})
+            } else {
+                json!({"system": {"set_relay_state": {"err_code": 0}}})
+            }
+        });
+        let smartplug = mock.hs110();
+
+        let child_ids = smartplug.get_child_ids().unwrap();
+        assert_eq!(
+            child_ids,
+            [
+                "8006AF35494E7DB13DDE9B8F40BF2E7A00",
+                "8006AF35494E7DB13DDE9B8F40BF2E7A01"
+            ]
+        );
+        smartplug
+            .set_child_power_state(&child_ids[1], PowerState::On)
+            .unwrap();
+        let emeter = smartplug.child_emeter(&child_ids[0]).unwrap();
+        assert_eq!(emeter["power"], 12.05);
+
+        assert_eq!(
+            mock.requests()[1..],
+            [
+                json!({
+                    "context": {"child_ids": ["8006AF35494E7DB13DDE9B8F40BF2E7A01"]},
+                    "system": {"set_relay_state": {"state": 1}}
+                }),
+                json!({
+                    "context": {"child_ids": ["8006AF35494E7DB13DDE9B8F40BF2E7A00"]},
+                    "emeter": {"get_realtime": {}}
+                }),
+            ]
+        );
+
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "model": "HS110(EU)", "err_code": 0
+        }}})]);
+        assert!(mock.hs110().get_child_ids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn diagnose() {
+        let mock = MockSmartplug::start(|request| {
+            let (module, command) = request
+                .as_object()
+                .and_then(|modules| modules.iter().next())
+                .and_then(|(module, commands)| {
+                    Some((module.clone(), commands.as_object()?.keys().next()?.clone()))
+                })
+                .unwrap();
+            let response = match (module.as_str(), command.as_str()) {
+                ("system", "get_sysinfo") => {
+                    json!({"sw_ver": "1.5.4 Build 180815 Rel.121440", "model": "HS100(EU)", "err_code": 0})
+                }
+                ("cnCloud", "get_info") => json!({
+                    "binded": 1, "cld_connection": 1, "fwDlPage": "", "fwNotifyType": 0,
+                    "illegalType": 0, "server": "n-devs.tplinkcloud.com", "stopConnect": 0,
+                    "tcspInfo": "", "tcspStatus": 1, "username": "user@example.com",
+                    "err_code": 0
+                }),
+                ("schedule" | "count_down", "get_rules") => json!({"rule_list": [], "err_code": 0}),
+                ("time", "get_time") => json!({
+                    "year": 2024, "month": 3, "mday": 17, "hour": 21, "min": 5, "sec": 9,
+                    "err_code": 0
+                }),
+                ("netif", "get_stainfo") => {
+                    json!({"ssid": "home", "key_type": 3, "rssi": -55, "err_code": 0})
+                }
+                _ => json!({"err_code": -1, "err_msg": "module not support"}),
+            };
+            json!({module: {command: response}})
+        });
+
+        let report = mock.hs110().diagnose().unwrap();
+        assert_eq!(
+            report.firmware_version.as_deref(),
+            Some("1.5.4 Build 180815 Rel.121440")
+        );
+        assert_eq!(
+            report.time,
+            Some(DeviceTime {
+                year: 2024,
+                month: 3,
+                mday: 17,
+                hour: 21,
+                min: 5,
+                sec: 9
+            })
+        );
+        assert_eq!(report.schedule_rules, Some(vec![]));
+        assert_eq!(report.network.unwrap()["ssid"], "home");
+        assert!(report.cloudinfo.is_some());
+        assert!(report.emeter.is_none());
+        assert!(matches!(
+            report.collect_errors[..],
+            [TpLinkHs110Error::KeyIsNotAvailable { .. }]
+        ));
+
+        let report = serde_json::to_value(mock.hs110().diagnose().unwrap()).unwrap();
+        assert_eq!(report["emeter"], Value::Null);
+        assert_eq!(report["collect_errors"].as_array().unwrap().len(), 1);
+
+        let unreachable = HS110::new("127.0.0.1:1")
+            .unwrap()
+            .with_timeout(Duration::from_secs(1));
+        assert!(matches!(
+            unreachable.diagnose(),
+            Err(TpLinkHs110Error::IO(_))
+        ));
+    }
+
+    #[test]
+    fn local_bind_addr() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "alias": "Kitchen", "err_code": 0
+        }}})]);
+        let smartplug = mock
+            .hs110()
+            .with_local_bind_addr("127.0.0.1:0".parse().unwrap());
+        assert_eq!(smartplug.get_alias().unwrap(), "Kitchen");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn local_bind_addr_is_used() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let smartplug = HS110::new(&listener.local_addr().unwrap().to_string())
+            .unwrap()
+            .with_timeout(Duration::from_millis(200))
+            .with_local_bind_addr("127.0.0.2:0".parse().unwrap());
+
+        let peer = std::thread::spawn(move || listener.accept().unwrap().1);
+        let _ = smartplug.info();
+        assert_eq!(peer.join().unwrap().ip(), Ipv4Addr::new(127, 0, 0, 2));
+    }
+
+    #[test]
+    fn reboot_and_wait() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"system": {"reboot": {"err_code": 0}}}),
+            json!({"system": {"get_sysinfo": {"err_code": 0}}}),
+        ]);
+        mock.hs110()
+            .reboot_and_wait(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(
+            mock.requests()[0],
+            json!({"system": {"reboot": {"delay": 0}}})
+        );
+        assert_eq!(mock.requests().len(), 2);
+
+        let unreachable = HS110::new("127.0.0.1:1").unwrap();
+        assert!(matches!(
+            unreachable.wait_for_reachable(Duration::from_millis(300)),
+            Err(TpLinkHs110Error::WaitTimeout(_))
+        ));
+    }
+
+    #[test]
+    fn emeter_and_sysinfo_fields() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230100, "current_ma": 120, "power_mw": 27612, "total_wh": 3500,
+                "slot_id": 0, "err_code": 0
+            }}}),
+            json!({"emeter": {"get_realtime": {
+                "voltage": 229.5, "current": 0.5, "power": 114.75, "total": 1.5, "err_code": 0
+            }}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        let fields = smartplug.get_emeter_fields().unwrap();
+        assert_eq!(fields["voltage_mv"], 230100.0);
+        assert_eq!(fields["voltage"], 230.1);
+        assert_eq!(fields["power_mw"], 27612.0);
+        assert_eq!(fields["total"], 3.5);
+        assert_eq!(fields["slot_id"], 0.0);
+        assert!(!fields.contains_key("err_code"));
+        assert_eq!(fields.len(), 9);
+
+        let fields = smartplug.get_emeter_fields().unwrap();
+        assert_eq!(fields["current_ma"], 500.0);
+        assert_eq!(fields["total_wh"], 1500.0);
+        assert_eq!(fields.len(), 8);
+
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "alias": "Kitchen", "relay_state": 1, "next_action": {"type": -1}, "err_code": 0
+        }}})]);
+        let fields = mock.hs110().get_sysinfo_fields().unwrap();
+        assert_eq!(
+            fields,
+            HashMap::from([
+                ("alias".to_string(), json!("Kitchen")),
+                ("relay_state".to_string(), json!(1)),
+                ("next_action".to_string(), json!({"type": -1})),
+            ])
+        );
+    }
+
+    #[test]
+    fn last_error() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "alias": "Kitchen", "err_code": 0
+        }}})]);
+        let smartplug = mock.hs110();
+        smartplug.info().unwrap();
+        assert!(smartplug.last_error().is_none());
+
+        let unreachable = HS110::new("127.0.0.1:1").unwrap();
+        let clone = unreachable.clone();
+        assert!(unreachable.info().is_err());
+        assert!(matches!(
+            unreachable.last_error(),
+            Some(TpLinkHs110Error::IO(err)) if err.kind() == std::io::ErrorKind::ConnectionRefused
+        ));
+        assert!(clone.last_error().is_some());
+
+        unreachable.clear_last_error();
+        assert!(unreachable.last_error().is_none());
+        assert!(clone.last_error().is_none());
+    }
+
+    #[test]
+    fn estimated_costs() {
+        let mock = MockSmartplug::with_responses(vec![json!({"emeter": {"get_realtime": {
+            "voltage_mv": 230000, "current_ma": 435, "power_mw": 100000, "total_wh": 0,
+            "err_code": 0
+        }}})]);
+        let smartplug = mock.hs110();
+
+        // 100 W during 24 hours is 2.4 kWh.
+        let daily = smartplug.estimated_daily_cost(0.25).unwrap();
+        assert!((daily - 0.6).abs() < 1e-9);
+        let monthly = smartplug.estimated_monthly_cost(0.25).unwrap();
+        assert!((monthly - 0.6 * 30.44).abs() < 1e-9);
+        let annual = smartplug.estimated_annual_cost(0.25).unwrap();
+        assert!((annual - 0.6 * 365.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn led_schedule_rules() {
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/schedule/get_rules").is_some() {
+                json!({"schedule": {"get_rules": {"rule_list": [
+                    {
+                        "id": "A1", "name": "night", "enable": 1, "wday": [1, 1, 1, 1, 1, 1, 1],
+                        "stime_opt": 0, "smin": 1320, "sact": 0, "repeat": 1
+                    },
+                    {
+                        "id": "B2", "name": "led off", "enable": 1, "enable_type": 1,
+                        "wday": [1, 1, 1, 1, 1, 1, 1], "stime_opt": 2, "smin": 0, "soffset": 30,
+                        "sact": 0, "repeat": 1
+                    },
+                    {
+                        "id": "C3", "name": "relay", "enable": 0, "enable_type": 0,
+                        "wday": [0, 0, 0, 0, 0, 0, 1], "stime_opt": 0, "smin": 600, "sact": 1,
+                        "repeat": 1
+                    }
+                ], "err_code": 0}}})
+            } else if request.pointer("/schedule/add_rule").is_some() {
+                json!({"schedule": {"add_rule": {"id": "D4", "err_code": 0}}})
+            } else {
+                json!({"schedule": {"delete_rule": {"err_code": 0}}})
+            }
+        });
+        let smartplug = mock.hs110();
+
+        let relay_rules = smartplug.get_schedule_rules().unwrap();
+        assert_eq!(
+            relay_rules
+                .iter()
+                .map(|rule| &rule.id[..])
+                .collect::<Vec<_>>(),
+            ["A1", "C3"]
+        );
+
+        let led_rules = smartplug.get_led_schedule_rules().unwrap();
+        assert_eq!(
+            led_rules,
+            [LedScheduleRule {
+                id: "B2".to_string(),
+                name: "led off".to_string(),
+                enable: true,
+                wday: [true; 7],
+                stime_opt: ScheduleTimeOption::Sunset,
+                smin: 0,
+                soffset: Some(30),
+                action: LedState::Off,
+                repeat: true,
+                year: None,
+                month: None,
+                day: None,
+            }]
+        );
+
+        let rule = LedScheduleRule {
+            id: String::new(),
+            name: "led on".to_string(),
+            stime_opt: ScheduleTimeOption::Time,
+            smin: 420,
+            soffset: None,
+            action: LedState::On,
+            ..led_rules[0].clone()
+        };
+        assert_eq!(smartplug.add_led_schedule_rule(&rule).unwrap(), "D4");
+        smartplug.delete_led_schedule_rule("B2").unwrap();
+
+        assert_eq!(
+            mock.requests()[2..],
+            [
+                json!({"schedule": {"add_rule": {
+                    "name": "led on", "enable": 1, "enable_type": 1,
+                    "wday": [1, 1, 1, 1, 1, 1, 1], "stime_opt": 0, "smin": 420, "sact": 1,
+                    "repeat": 1
+                }}}),
+                json!({"schedule": {"delete_rule": {"id": "B2"}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_countdown_then_restore() {
+        // Fake relay with a countdown rule which fires after its delay.
+        let state = Mutex::new((0, None::<(Instant, u64)>));
+        let mock = MockSmartplug::start(move |request| {
+            let mut state = state.lock().unwrap();
+            if let Some((fires_at, act)) = state.1 {
+                if Instant::now() >= fires_at {
+                    *state = (act, None);
+                }
+            }
+
+            if let Some(relay_state) = request.pointer("/system/set_relay_state/state") {
+                state.0 = relay_state.as_u64().unwrap();
+                json!({"system": {"set_relay_state": {"err_code": 0}}})
+            } else if let Some(rule) = request.pointer("/count_down/add_rule") {
+                let delay = Duration::from_secs(rule["delay"].as_u64().unwrap());
+                state.1 = Some((Instant::now() + delay, rule["act"].as_u64().unwrap()));
+                json!({"count_down": {"add_rule": {"id": "1", "err_code": 0}}})
+            } else if request.pointer("/count_down/delete_all_rules").is_some() {
+                state.1 = None;
+                json!({"count_down": {"delete_all_rules": {"err_code": 0}}})
+            } else {
+                json!({"system": {"get_sysinfo": {"relay_state": state.0, "err_code": 0}}})
+            }
+        });
+        let smartplug = mock.hs110();
+
+        smartplug
+            .set_countdown_then_restore(Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(smartplug.power_state().unwrap(), PowerState::Off);
+
+        let requests = mock.requests();
+        assert_eq!(
+            requests[..4],
+            [
+                json!({"system": {"get_sysinfo": {}}}),
+                json!({"count_down": {"delete_all_rules": {}}}),
+                json!({"count_down": {"add_rule": {
+                    "name": "restore power state", "enable": 1, "delay": 1, "act": 0
+                }}}),
+                json!({"system": {"set_relay_state": {"state": 1}}}),
+            ]
+        );
+
+        assert!(matches!(
+            smartplug.set_countdown_then_restore(Duration::from_millis(500)),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn get_raw_response() {
+        let response = json!({"system": {"get_sysinfo": {"err_code": -1, "err_msg": "oops"}}});
+        let mock = MockSmartplug::with_responses(vec![response.clone()]);
+        let smartplug = mock.hs110();
+
+        assert_eq!(
+            smartplug
+                .get_raw_response(&["system", "get_sysinfo"])
+                .unwrap(),
+            response
+        );
+        smartplug
+            .get_raw_response(&["emeter", "get_realtime"])
+            .unwrap();
+        smartplug
+            .get_raw_response(&["smartlife.iot.common.emeter", "get_realtime", "extra"])
+            .unwrap();
+        for path in [&["system"][..], &[], &["a", "b", "c", "d"]] {
+            assert!(matches!(
+                smartplug.get_raw_response(path),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"system": {"get_sysinfo": {}}}),
+                json!({"emeter": {"get_realtime": {}}}),
+                json!({"smartlife.iot.common.emeter": {"get_realtime": {"extra": {}}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn power_off_if_overloaded() {
+        let reading = |power_mw: u32| {
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230000, "current_ma": power_mw / 230, "power_mw": power_mw,
+                "total_wh": 0, "err_code": 0
+            }}})
+        };
+        let mock = MockSmartplug::with_responses(vec![
+            reading(1_500_000),
+            reading(2_400_000),
+            json!({"system": {"set_relay_state": {"err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert!(!smartplug.power_off_if_overloaded(2300.0).unwrap());
+        assert!(smartplug.power_off_if_overloaded(2300.0).unwrap());
+        assert_eq!(
+            mock.requests()[2],
+            json!({"system": {"set_relay_state": {"state": 0}}})
+        );
+
+        for max_watts in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                smartplug.power_off_if_overloaded(max_watts),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[test]
+    fn latency_histogram() {
+        let mock = MockSmartplug::start(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            json!({"system": {"get_sysinfo": {"err_code": 0}}})
+        });
+        let smartplug = mock.hs110();
+
+        assert!(smartplug.ping().unwrap() >= Duration::from_millis(20));
+
+        let histogram = smartplug.measure_latency_histogram(10).unwrap();
+        assert_eq!(histogram.samples, 10);
+        assert_eq!(histogram.errors, 0);
+        assert!(histogram.min_ms >= 20.0);
+        assert!(histogram.min_ms <= histogram.p50_ms);
+        assert!(histogram.p50_ms <= histogram.p90_ms);
+        assert!(histogram.p90_ms <= histogram.p99_ms);
+        assert!(histogram.p99_ms <= histogram.max_ms);
+        assert_eq!(mock.requests().len(), 11);
+
+        assert!(matches!(
+            smartplug.measure_latency_histogram(0),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            HS110::new("127.0.0.1:1")
+                .unwrap()
+                .measure_latency_histogram(3),
+            Err(TpLinkHs110Error::IO(_))
+        ));
+    }
+
+    #[test]
+    fn cloud_poll_interval() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"cnCloud": {"get_info": {
+                "binded": 1, "server": "n-devs.tplinkcloud.com", "poll_interval": 300,
+                "err_code": 0
+            }}}),
+            json!({"cnCloud": {"set_server_url": {"err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert_eq!(smartplug.get_cloud_poll_interval().unwrap(), 300);
+        smartplug.set_cloud_poll_interval(60).unwrap();
+        assert!(matches!(
+            smartplug.set_cloud_poll_interval(59),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"cnCloud": {"get_info": {}}}),
+                json!({"cnCloud": {"set_server_url": {"poll_interval": 60}}}),
+            ]
+        );
+    }
+
+    const DHCP_LEASES: &str = "\
+        1718000000 50:c7:bf:00:00:01 192.168.0.10 HS110 01:50:c7:bf:00:00:01\n\
+        1718000100 50:c7:bf:00:00:02 192.168.0.11 kitchen-hs100 *\n\
+        1718000200 a4:83:e7:00:00:03 192.168.0.12 laptop 01:a4:83:e7:00:00:03\n\
+        \n\
+        duid 00:01:00:01:2b:4c:5d:6e:00:00:00:00:00:00\n\
+        1718000300 12345 fd00::20 Plug-HS110 00:03:00:01:50:c7:bf:00:00:04\n";
+
+    #[test]
+    fn dhcp_leases() {
+        let path = std::env::temp_dir().join(format!("tplink-hs110-{}.leases", std::process::id()));
+        fs::write(&path, DHCP_LEASES).unwrap();
+
+        let smartplugs = HS110::from_dhcp_lease_file(&path).unwrap();
+        assert_eq!(
+            smartplugs
+                .iter()
+                .map(HS110::socket_addr)
+                .collect::<Vec<_>>(),
+            [
+                "192.168.0.10:9999".parse().unwrap(),
+                "192.168.0.11:9999".parse().unwrap(),
+                "[fd00::20]:9999".parse().unwrap(),
+            ]
+        );
+
+        let smartplugs = HS110::from_dhcp_lease_file_with_prefix(&path, "LAP").unwrap();
+        assert_eq!(smartplugs.len(), 1);
+        assert_eq!(
+            smartplugs[0].ip_addr(),
+            "192.168.0.12".parse::<IpAddr>().unwrap()
+        );
+        fs::remove_file(&path).unwrap();
+
+        let error = HS110::from_dhcp_leases("1718000000 50:c7:bf:00:00:01 HS110\n", |_| true);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "failed to parse DHCP leases: line 1: expected at least 4 fields, got 3"
+        );
+        let error = HS110::from_dhcp_leases("\n1718000000 mac 192.168.0.300 HS110 *\n", |_| true);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "failed to parse DHCP leases: line 2: invalid IP address \"192.168.0.300\""
+        );
+        assert!(matches!(
+            HS110::from_dhcp_lease_file(Path::new("/nonexistent/dnsmasq.leases")),
+            Err(TpLinkHs110Error::IO(_))
+        ));
+    }
+
+    #[test]
+    fn device_time_arithmetic() {
+        let time = DeviceTime {
+            year: 2024,
+            month: 2,
+            mday: 28,
+            hour: 23,
+            min: 0,
+            sec: 0,
+        };
+        assert_eq!(time.weekday(), 3);
+        assert_eq!(
+            time.after(Duration::from_secs(2 * 86_400 + 3 * 3600 + 59))
+                .unwrap(),
+            DeviceTime {
+                year: 2024,
+                month: 3,
+                mday: 2,
+                hour: 2,
+                min: 0,
+                sec: 59,
+            }
+        );
+
+        let new_year = DeviceTime {
+            year: 2023,
+            month: 12,
+            mday: 31,
+            hour: 23,
+            min: 59,
+            sec: 59,
+        }
+        .after(Duration::from_secs(1))
+        .unwrap();
+        assert_eq!((new_year.year, new_year.month, new_year.mday), (2024, 1, 1));
+        assert_eq!(new_year.weekday(), 1);
+
+        assert_eq!(new_year.before(Duration::from_secs(1)).unwrap().year, 2023);
+        assert!(matches!(
+            time.before(Duration::from_secs(2025 * 366 * 86_400)),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            time.after(Duration::from_secs(u64::MAX)),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        let last = DeviceTime {
+            year: u16::MAX,
+            month: 12,
+            mday: 31,
+            hour: 23,
+            min: 59,
+            sec: 59,
+        };
+        assert!(matches!(
+            last.after(Duration::from_secs(1)),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(last.after(Duration::ZERO).unwrap(), last);
+    }
+
+    #[test]
+    fn schedule_state_change() {
+        let mock = MockSmartplug::start(|request| {
+            match request.as_object().unwrap().keys().next() {
+                Some(module) if module == "time" => json!({"time": {"get_time": {
+                    "year": 2024, "month": 2, "mday": 28, "hour": 23, "min": 0, "sec": 0, "err_code": 0
+                }}}),
+                Some(module) => json!({module: {"add_rule": {"id": "RULE-ID", "err_code": 0}}}),
+                None => json!({}),
+            }
+        });
+        let smartplug = mock.hs110();
+
+        assert!(matches!(
+            smartplug
+                .schedule_state_change(PowerState::On, SystemTime::now() - Duration::from_secs(1)),
+            Err(TpLinkHs110Error::ScheduleTimeInPast)
+        ));
+        assert!(mock.requests().is_empty());
+
+        smartplug
+            .schedule_state_change(
+                PowerState::Off,
+                SystemTime::now() + Duration::from_secs(600),
+            )
+            .unwrap();
+        let rule = mock.requests()[0]["count_down"]["add_rule"].clone();
+        assert!((598..=600).contains(&rule["delay"].as_u64().unwrap()));
+        assert_eq!(rule["act"], 0);
+
+        smartplug
+            .schedule_state_change(
+                PowerState::On,
+                SystemTime::now() + Duration::from_secs(2 * 86_400 + 3 * 3600),
+            )
+            .unwrap();
+        let requests = mock.requests();
+        assert_eq!(requests[1], json!({"time": {"get_time": {}}}));
+        assert_eq!(
+            requests[2],
+            json!({"schedule": {"add_rule": {
+                "name": "scheduled state change",
+                "enable": 1,
+                "wday": [0, 0, 0, 0, 0, 0, 1],
+                "stime_opt": 0,
+                "smin": 120,
+                "sact": 1,
+                "repeat": 0,
+                "year": 2024,
+                "month": 3,
+                "day": 2,
+            }}})
+        );
+    }
+
+    #[test]
+    fn toggle_power_with_confirmation() {
+        let relay_state = Arc::new(AtomicUsize::new(0));
+        let state = relay_state.clone();
+        let mock = MockSmartplug::start(move |request| {
+            if let Some(new_state) = request.pointer("/system/set_relay_state/state") {
+                state.store(new_state.as_u64().unwrap() as usize, Ordering::SeqCst);
+                json!({"system": {"set_relay_state": {"err_code": 0}}})
+            } else {
+                json!({"system": {"get_sysinfo": {
+                    "relay_state": state.load(Ordering::SeqCst), "err_code": 0
+                }}})
+            }
+        });
+        let smartplug = mock.hs110();
+
+        assert_eq!(
+            smartplug.toggle_power_with_confirmation().unwrap(),
+            (PowerState::Off, PowerState::On)
+        );
+        assert_eq!(smartplug.toggle_power().unwrap(), PowerState::Off);
+        assert_eq!(relay_state.load(Ordering::SeqCst), 0);
+
+        let ignoring = MockSmartplug::start(|request| {
+            if request.pointer("/system/set_relay_state").is_some() {
+                json!({"system": {"set_relay_state": {"err_code": 0}}})
+            } else {
+                json!({"system": {"get_sysinfo": {"relay_state": 1, "err_code": 0}}})
+            }
+        });
+        assert!(matches!(
+            ignoring.hs110().toggle_power_with_confirmation(),
+            Err(TpLinkHs110Error::StateNotChanged(PowerState::On))
+        ));
+        assert_eq!(ignoring.requests().len(), 3);
+    }
+
+    #[test]
+    fn time_remaining_in_active_mode() {
+        let sysinfo = |active_mode| {
+            json!({"system": {"get_sysinfo": {
+                "active_mode": active_mode, "on_time": 120, "relay_state": 1, "err_code": 0
+            }}})
+        };
+        let countdown_rules = |remain: Option<u32>| {
+            let mut rule = json!({
+                "id": "C2A3B7F3E0B4A4F8D77A6C0E1F9A1B2C", "name": "off", "enable": 1, "delay": 1800,
+                "act": 0
+            });
+            if let Some(remain) = remain {
+                rule["remain"] = remain.into();
+            }
+            json!({"count_down": {"get_rules": {"rule_list": [rule], "err_code": 0}}})
+        };
+        let mock = MockSmartplug::with_responses(vec![
+            sysinfo("count_down"),
+            countdown_rules(None),
+            sysinfo("count_down"),
+            countdown_rules(Some(1500)),
+            sysinfo("schedule"),
+            sysinfo("none"),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert_eq!(
+            smartplug.get_time_remaining_in_active_mode().unwrap(),
+            Some(Duration::from_secs(1680))
+        );
+        assert_eq!(
+            smartplug.get_time_remaining_in_active_mode().unwrap(),
+            Some(Duration::from_secs(1500))
+        );
+        assert_eq!(smartplug.get_time_remaining_in_active_mode().unwrap(), None);
+        assert_eq!(smartplug.active_mode().unwrap(), ActiveMode::None);
+        assert_eq!(mock.requests().len(), 6);
+    }
+
+    #[test]
+    fn health_check() {
+        let mock = MockSmartplug::start(|request| {
+            match request
+                .pointer("/cnCloud")
+                .and_then(|cloud| cloud.as_object())
+            {
+                Some(cloud) if cloud.contains_key("get_info") => json!({"cnCloud": {"get_info": {
+                    "binded": 1, "cld_connection": 1, "fwDlPage": "", "fwNotifyType": 0,
+                    "illegalType": 0, "server": "n-devs.tplinkcloud.com", "stopConnect": 0,
+                    "tcspInfo": "", "tcspStatus": 1, "username": "username@example.com",
+                    "err_code": 0
+                }}}),
+                Some(_) => json!({"cnCloud": {"get_intl_fw_list": {"fw_list": [], "err_code": 0}}}),
+                None if request.pointer("/emeter").is_some() => {
+                    json!({"emeter": {"get_realtime": {"err_code": -1, "err_msg": "module not support"}}})
+                }
+                None => json!({"system": {"get_sysinfo": {"relay_state": 0, "err_code": 0}}}),
+            }
+        });
+
+        let report = mock.hs110().health_check().unwrap();
+        assert!(report.reachable);
+        assert!(report.relay_ok);
+        assert!(!report.emeter_ok);
+        assert!(report.cloud_ok);
+        assert_eq!(report.firmware_current, Some(true));
+        assert!(report.latency_ms.is_some());
+        assert_eq!(report.errors.len(), 1);
+
+        let unreachable = HS110::new("127.0.0.1:1")
+            .unwrap()
+            .with_timeout(Duration::from_secs(1));
+        assert!(!unreachable.is_online());
+        let report = unreachable.health_check().unwrap();
+        assert!(!report.reachable && !report.relay_ok && !report.emeter_ok && !report.cloud_ok);
+        assert_eq!((report.firmware_current, report.latency_ms), (None, None));
+        assert_eq!(report.errors.len(), 5);
+    }
+
+    #[test]
+    fn reboot_and_reset_after() {
+        let mock = MockSmartplug::start(|request| {
+            let module = request.as_object().unwrap().keys().next().unwrap();
+            let command = request[module].as_object().unwrap().keys().next().unwrap();
+            json!({module: {command: {"err_code": 0}}})
+        });
+        let smartplug = mock.hs110();
+
+        smartplug
+            .reboot_after(Duration::from_millis(30_900))
+            .unwrap();
+        smartplug
+            .factory_reset_after(Duration::from_secs(120))
+            .unwrap();
+        assert!(matches!(
+            smartplug.reboot_after(Duration::from_secs(u64::from(u32::MAX) + 1)),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"system": {"reboot": {"delay": 30}}}),
+                json!({"system": {"reset": {"delay": 120}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn assert_power_and_led_state() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "relay_state": 1, "led_off": 1, "err_code": 0
+        }}})]);
+        let smartplug = mock.hs110();
+
+        smartplug.assert_power_state(PowerState::On).unwrap();
+        assert!(matches!(
+            smartplug.assert_power_state(PowerState::Off),
+            Err(TpLinkHs110Error::UnexpectedPowerState {
+                expected: PowerState::Off,
+                actual: PowerState::On,
+            })
+        ));
+        smartplug.assert_led_state(LedState::Off).unwrap();
+        assert!(matches!(
+            smartplug.assert_led_state(LedState::On),
+            Err(TpLinkHs110Error::UnexpectedLedState {
+                expected: LedState::On,
+                actual: LedState::Off,
+            })
+        ));
+    }
+
+    #[test]
+    fn emeter_totals() {
+        let now = SystemTime::now();
+        let mock = MockSmartplug::start(|request| {
+            let day_list = |year, month, days: &[(u8, f64)]| {
+                days.iter()
+                    .map(|(day, energy_wh)| {
+                        json!({"year": year, "month": month, "day": day, "energy_wh": energy_wh})
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let response = match request.pointer("/emeter").or(request.pointer("/time")) {
+                Some(command) if command.get("get_time").is_some() => {
+                    return json!({"time": {"get_time": {
+                        "year": 2024, "month": 1, "mday": 10, "hour": 12, "min": 0, "sec": 0,
+                        "err_code": 0
+                    }}});
+                }
+                Some(command) if command.get("get_monthstat").is_some() => {
+                    json!({"get_monthstat": {
+                        "month_list": [{"year": 2024, "month": 1, "energy": 1.5}], "err_code": 0
+                    }})
+                }
+                Some(command) => {
+                    let params = &command["get_daystat"];
+                    let day_list = match (params["year"].as_u64(), params["month"].as_u64()) {
+                        (Some(2023), Some(11)) => day_list(2023, 11, &[(29, 100.0), (30, 200.0)]),
+                        (Some(2023), Some(12)) => day_list(2023, 12, &[(1, 300.0), (31, 400.0)]),
+                        (Some(2024), Some(1)) => day_list(2024, 1, &[(1, 500.0), (10, 600.0)]),
+                        _ => vec![],
+                    };
+                    json!({"get_daystat": {"day_list": day_list, "err_code": 0}})
+                }
+                None => json!({}),
+            };
+            json!({"emeter": response})
+        });
+        let smartplug = mock.hs110();
+
+        // 2023-11-30 12:00 in smartplug's local time.
+        let since = now - Duration::from_secs(41 * 86_400);
+        assert!((smartplug.emeter_total_since(since).unwrap() - 2.0).abs() < 1e-9);
+        assert!((smartplug.emeter_total_this_month().unwrap() - 1.1).abs() < 1e-9);
+        assert!((smartplug.emeter_total_this_year().unwrap() - 1.5).abs() < 1e-9);
+
+        let daystat_requests = mock
+            .requests()
+            .iter()
+            .filter_map(|request| request.pointer("/emeter/get_daystat").cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            daystat_requests,
+            [
+                json!({"year": 2023, "month": 11}),
+                json!({"year": 2023, "month": 12}),
+                json!({"year": 2024, "month": 1}),
+                json!({"year": 2024, "month": 1}),
+            ]
+        );
+        assert!(matches!(
+            smartplug.emeter_total_since(now + Duration::from_secs(3600)),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn rate_limited() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "relay_state": 1, "err_code": 0
+        }}})]);
+        let smartplug = mock.hs110().rate_limited(20.0).unwrap();
+
+        let started = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+        }
+        // The first request is sent immediately, the rest are 50ms apart.
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(450), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1500), "{elapsed:?}");
+        assert_eq!(mock.requests().len(), 10);
+
+        let smartplug = smartplug.into_inner();
+        let started = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+        }
+        assert!(started.elapsed() < Duration::from_millis(450));
+
+        for requests_per_second in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                mock.hs110().rate_limited(requests_per_second),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn overcurrent_protection() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"emeter": {"get_overcurrent_protection": {
+                "enable": 1, "max_ma": 10000, "err_code": 0
+            }}}),
+            json!({"emeter": {"set_overcurrent_protection": {"err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        let config = smartplug.get_overcurrent_protection().unwrap();
+        assert_eq!(
+            config,
+            OvercurrentConfig {
+                enabled: true,
+                threshold_ma: 10000,
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(config).unwrap(),
+            json!({"enable": 1, "max_ma": 10000})
+        );
+
+        smartplug
+            .set_overcurrent_protection(true, Some(12000))
+            .unwrap();
+        smartplug.set_overcurrent_protection(false, None).unwrap();
+        assert!(matches!(
+            smartplug.set_overcurrent_protection(true, Some(16001)),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(
+            mock.requests()[1..],
+            [
+                json!({"emeter": {"set_overcurrent_protection": {"enable": 1, "max_ma": 12000}}}),
+                json!({"emeter": {"set_overcurrent_protection": {"enable": 0}}}),
+            ]
+        );
+
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"emeter": {"err_code": -2, "err_msg": "member not support"}}),
+        ]);
+        let smartplug = mock.hs110();
+        assert!(matches!(
+            smartplug.get_overcurrent_protection(),
+            Err(TpLinkHs110Error::FeatureNotSupported)
+        ));
+        assert!(matches!(
+            smartplug.set_overcurrent_protection(false, None),
+            Err(TpLinkHs110Error::FeatureNotSupported)
+        ));
+    }
+
+    #[test]
+    fn bulk_schedule_rules() {
+        let rule = |smin| ScheduleRule {
+            id: String::new(),
+            name: format!("rule {smin}"),
+            enable: true,
+            wday: [true; 7],
+            stime_opt: ScheduleTimeOption::Time,
+            smin,
+            soffset: None,
+            sact: PowerState::On,
+            repeat: true,
+            year: None,
+            month: None,
+            day: None,
+        };
+        let rules = [rule(60), rule(120)];
+
+        let bulk = MockSmartplug::start(|request| match request.pointer("/schedule/add_rules") {
+            Some(_) => json!({"schedule": {"add_rules": {"id_list": ["A1", "A2"], "err_code": 0}}}),
+            None => json!({"schedule": {"delete_rules": {"err_code": 0}}}),
+        });
+        assert_eq!(
+            bulk.hs110().bulk_set_schedule_rules(&rules).unwrap(),
+            ["A1", "A2"]
+        );
+        bulk.hs110()
+            .bulk_delete_schedule_rules(&["A1", "A2"])
+            .unwrap();
+        assert_eq!(bulk.requests().len(), 2);
+        assert_eq!(
+            bulk.requests()[1],
+            json!({"schedule": {"delete_rules": {"id_list": ["A1", "A2"]}}})
+        );
+
+        let next_id = AtomicUsize::new(1);
+        let sequential = MockSmartplug::start(move |request| {
+            let schedule = request["schedule"].as_object().unwrap();
+            match schedule.keys().next().map(String::as_str) {
+                Some("add_rule") => json!({"schedule": {"add_rule": {
+                    "id": format!("S{}", next_id.fetch_add(1, Ordering::SeqCst)), "err_code": 0
+                }}}),
+                Some("delete_rule") => json!({"schedule": {"delete_rule": {"err_code": 0}}}),
+                _ => json!({"schedule": {"err_code": -2, "err_msg": "member not support"}}),
+            }
+        });
+        assert_eq!(
+            sequential
+                .hs110()
+                .bulk_set_schedule_rules(&rules[..1])
+                .unwrap(),
+            ["S1"]
+        );
+        assert_eq!(
+            sequential.hs110().bulk_set_schedule_rules(&rules).unwrap(),
+            ["S2", "S3"]
+        );
+        sequential
+            .hs110()
+            .bulk_delete_schedule_rules(&["S1", "S2"])
+            .unwrap();
+        let requests = sequential.requests();
+        assert_eq!(requests.len(), 8);
+        assert_eq!(requests[4]["schedule"]["add_rule"]["smin"], 120);
+        assert_eq!(
+            requests[6..],
+            [
+                json!({"schedule": {"delete_rule": {"id": "S1"}}}),
+                json!({"schedule": {"delete_rule": {"id": "S2"}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn device_icons() {
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"system": {"get_dev_icons": {"icon_list": [
+                {"icon_hash": "6E4B2A1F0C9D8E7F6A5B4C3D2E1F0A9B", "name": "Lamp"},
+                {"icon_hash": "0123456789abcdef0123456789ABCDEF", "name": "Fan"},
+            ], "err_code": 0}}}),
+            json!({"system": {"set_dev_icon": {"err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        let icons = smartplug.get_device_icons().unwrap();
+        assert_eq!(icons.len(), 2);
+        assert_eq!(
+            icons[1],
+            DeviceIcon {
+                icon_hash: "0123456789abcdef0123456789ABCDEF".to_string(),
+                name: "Fan".to_string(),
+            }
+        );
+
+        smartplug.set_device_icon(&icons[1].icon_hash).unwrap();
+        for icon_hash in ["", "0123456789abcdef", "0123456789abcdef0123456789abcdeg"] {
+            assert!(matches!(
+                smartplug.set_device_icon(icon_hash),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+        assert_eq!(
+            mock.requests()[1],
+            json!({"system": {"set_dev_icon": {"hash": "0123456789abcdef0123456789ABCDEF"}}})
+        );
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[test]
+    fn debug_format() {
+        let smartplug = HS110::new("192.168.1.100").unwrap();
+        assert_eq!(
+            format!("{smartplug:?}"),
+            r#"HS110 { addr: "192.168.1.100:9999", timeout: None }"#
+        );
+
+        let smartplug = smartplug
+            .with_timeout(Duration::from_secs(3))
+            .with_connect_timeout(Duration::from_secs(1));
+        assert_eq!(
+            format!("{smartplug:?}"),
+            r#"HS110 { addr: "192.168.1.100:9999", timeout: Some("3s") }"#
+        );
+
+        #[cfg(feature = "humantime")]
+        assert_eq!(
+            format!("{:?}", smartplug.with_timeout(Duration::from_millis(1500))),
+            r#"HS110 { addr: "192.168.1.100:9999", timeout: Some("1s 500ms") }"#
+        );
+    }
+
+    #[test]
+    fn measure_power_stability() {
+        let reading = |power_mw: u32| {
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230000, "current_ma": power_mw / 230, "power_mw": power_mw,
+                "total_wh": 1000, "err_code": 0
+            }}})
+        };
+        let mock =
+            MockSmartplug::with_responses(vec![reading(10_000), reading(20_000), reading(30_000)]);
+
+        let stability = mock
+            .hs110()
+            .measure_power_stability(Duration::from_millis(1500))
+            .unwrap();
+        assert_eq!(stability.samples, 3);
+        assert!((stability.mean_w - 20.0).abs() < 1e-9);
+        assert!((stability.std_dev_w - (200.0f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert!((stability.cv_pct - 40.824_829).abs() < 1e-6);
+        assert_eq!(mock.requests().len(), 3);
+
+        let failing = MockSmartplug::with_responses(vec![
+            reading(10_000),
+            json!({"emeter": {"err_code": -1, "err_msg": "module not support"}}),
+        ]);
+        assert!(matches!(
+            failing
+                .hs110()
+                .measure_power_stability(Duration::from_millis(1500)),
+            Err(TpLinkHs110Error::InsufficientSamples {
+                got: 1,
+                required: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn tcp_socket_options() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let smartplug = HS110::new(&listener.local_addr().unwrap().to_string()).unwrap();
+
+        let stream = smartplug.connect().unwrap();
+        assert!(stream.nodelay().unwrap());
+        assert!(!socket2::SockRef::from(&stream).keepalive().unwrap());
+
+        let smartplug = smartplug
+            .with_tcp_nodelay(false)
+            .with_tcp_keepalive(Duration::from_secs(30));
+        let stream = smartplug.connect().unwrap();
+        assert!(!stream.nodelay().unwrap());
+        assert!(socket2::SockRef::from(&stream).keepalive().unwrap());
+    }
+
+    #[test]
+    fn schedule_next_event() {
+        let rule = |id: &str, enable, wday: [u8; 7], smin, sact| {
+            json!({
+                "id": id, "name": id, "enable": enable, "wday": wday, "stime_opt": 0,
+                "smin": smin, "sact": sact, "repeat": 1
+            })
+        };
+        let rules = json!([
+            rule("DAILY", 1, [1; 7], 23 * 60, 1),
+            rule("THURSDAY", 1, [0, 0, 0, 0, 1, 0, 0], 30, 0),
+            rule("DISABLED", 0, [1; 7], 23 * 60 + 30, 1),
+            {
+                "id": "ONCE", "name": "once", "enable": 1, "wday": [0, 0, 0, 1, 0, 0, 0],
+                "stime_opt": 0, "smin": 22 * 60, "sact": 1, "repeat": 0, "year": 2024,
+                "month": 2, "day": 28
+            },
+            {
+                "id": "SUNSET", "name": "sunset", "enable": 1, "wday": [1, 1, 1, 1, 1, 1, 1],
+                "stime_opt": 2, "smin": 0, "soffset": 0, "sact": 1, "repeat": 1
+            },
+        ]);
+        let time = json!({"time": {"get_time": {
+            "year": 2024, "month": 2, "mday": 28, "hour": 23, "min": 10, "sec": 0, "err_code": 0
+        }}});
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"schedule": {"get_rules": {"rule_list": rules, "enable": 1, "err_code": 0}}}),
+            time.clone(),
+            json!({"schedule": {"get_rules": {"rule_list": [], "enable": 1, "err_code": 0}}}),
+            time,
+        ]);
+        let smartplug = mock.hs110();
+
+        // 2024-02-28 is Wednesday, so the Thursday rule fires 1h 20m later after midnight.
+        let before = SystemTime::now();
+        let event = smartplug.get_schedule_next_event().unwrap().unwrap();
+        assert_eq!(event.rule_id, "THURSDAY");
+        assert_eq!(event.action, PowerState::Off);
+        let fires_in = event.fires_at.duration_since(before).unwrap();
+        assert!(fires_in >= Duration::from_secs(80 * 60), "{fires_in:?}");
+        assert!(fires_in < Duration::from_secs(80 * 60 + 5), "{fires_in:?}");
+
+        assert_eq!(smartplug.get_schedule_next_event().unwrap(), None);
+    }
+
+    #[test]
+    fn measure_energy_in_window() {
+        let reading = |total_wh: u32| {
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230000, "current_ma": 435, "power_mw": 100_000,
+                "total_wh": total_wh, "err_code": 0
+            }}})
+        };
+        let mock = MockSmartplug::with_responses(vec![reading(1000), reading(1017), reading(1020)]);
+        let smartplug = mock.hs110();
+
+        let started = Instant::now();
+        let energy_wh = smartplug
+            .measure_energy_in_window(Duration::from_millis(200))
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!((energy_wh - 17.0).abs() < 1e-9);
+
+        let energy_wh = smartplug.measure_energy_in_window_nb(1.0).unwrap();
+        assert!((energy_wh - 20.0).abs() < 1e-9);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[test]
+    fn connect_only() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "relay_state": 1, "err_code": 0
+        }}})]);
+        let smartplug = mock.hs110();
+
+        smartplug.connect_only().unwrap();
+        assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+        assert_eq!(smartplug.clone().power_state().unwrap(), PowerState::On);
+        assert_eq!(mock.connections(), 1);
+
+        smartplug.disconnect().unwrap();
+        smartplug.disconnect().unwrap();
+        assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+        assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+        assert_eq!(mock.connections(), 3);
+        assert_eq!(mock.requests().len(), 4);
+    }
+
+    #[test]
+    fn connect_only_reconnects_once_connection_is_closed() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let smartplug = HS110::new(&listener.local_addr().unwrap().to_string())
+            .unwrap()
+            .with_timeout(Duration::from_secs(3));
+
+        smartplug.connect_only().unwrap();
+        // Accept and immediately close the pre-established connection, then serve a new one.
+        let (closed, _) = listener.accept().unwrap();
+        drop(closed);
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0u8; 4];
+            stream.read_exact(&mut header).unwrap();
+            let mut payload = vec![0u8; u32::from_be_bytes(header) as usize];
+            stream.read_exact(&mut payload).unwrap();
+            stream
+                .write_all(&HS110::encrypt(
+                    json!({"system": {"get_sysinfo": {"relay_state": 0, "err_code": 0}}})
+                        .to_string(),
+                ))
+                .unwrap();
+        });
+
+        assert_eq!(smartplug.power_state().unwrap(), PowerState::Off);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn connect_only_doesnt_repeat_unanswered_request() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let smartplug = HS110::new(&listener.local_addr().unwrap().to_string())
+            .unwrap()
+            .with_timeout(Duration::from_millis(200));
+
+        smartplug.connect_only().unwrap();
+        // Receive a request over the pre-established connection, but never answer it.
+        let (mut stream, _) = listener.accept().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut header = [0u8; 4];
+            stream.read_exact(&mut header).unwrap();
+            std::thread::sleep(Duration::from_millis(500));
+        });
+
+        assert!(smartplug.set_power_state(PowerState::On).is_err());
+        server.join().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        assert_eq!(
+            listener.accept().unwrap_err().kind(),
+            std::io::ErrorKind::WouldBlock
+        );
+    }
+
+    #[test]
+    fn presets() {
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/schedule/get_rules").is_some() {
+                let rule = |id: &str, name: &str| {
+                    json!({"id": id, "name": name, "enable": 1, "wday": [1, 1, 1, 1, 1, 1, 1],
+                           "stime_opt": 0, "smin": 1020, "sact": 0, "repeat": 1})
+                };
+                return json!({"schedule": {"get_rules": {"enable": 0, "rule_list": [
+                    rule("E1", "Energy saving: peak hours start"),
+                    rule("S1", "Evening off"),
+                    rule("E2", "Energy saving: peak hours end"),
+                ], "err_code": 0}}});
+            }
+            let (module, command) = request.as_object().unwrap().iter().next().unwrap();
+            let command = command.as_object().unwrap().keys().next().unwrap();
+            json!({module: {command: {"id": "R1", "err_code": 0}}})
+        });
+        let smartplug = mock.hs110();
+
+        smartplug.apply_preset(DevicePreset::NightMode).unwrap();
+        assert_eq!(
+            mock.requests(),
+            [json!({"system": {"set_led_off": {"off": 1}}})]
+        );
+
+        smartplug.apply_preset(DevicePreset::AlwaysOn).unwrap();
+        assert_eq!(
+            mock.requests()[1..],
+            [
+                json!({"system": {"set_relay_state": {"state": 1}}}),
+                json!({"schedule": {"set_overall_enable": {"enable": 0}}}),
+                json!({"count_down": {"set_overall_enable": {"enable": 0}}}),
+            ]
+        );
+
+        smartplug.apply_preset(DevicePreset::EnergySaving).unwrap();
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 11);
+        assert_eq!(requests[4], json!({"system": {"set_led_off": {"off": 1}}}));
+        assert_eq!(requests[5], json!({"schedule": {"get_rules": {}}}));
+        // Rules of the previously applied preset are deleted, other rules are kept.
+        assert_eq!(
+            requests[6..8],
+            [
+                json!({"schedule": {"delete_rule": {"id": "E1"}}}),
+                json!({"schedule": {"delete_rule": {"id": "E2"}}}),
+            ]
+        );
+        for (request, smin, sact) in [(&requests[8], 1020, 0), (&requests[9], 1260, 1)] {
+            let rule = &request["schedule"]["add_rule"];
+            assert_eq!(rule["smin"], smin);
+            assert_eq!(rule["sact"], sact);
+            assert_eq!(rule["repeat"], 1);
+            assert_eq!(rule["wday"], json!([1, 1, 1, 1, 1, 1, 1]));
+        }
+        // Schedules disabled by `AlwaysOn` are enabled again.
+        assert_eq!(
+            requests[10],
+            json!({"schedule": {"set_overall_enable": {"enable": 1}}})
+        );
+
+        let custom =
+            DevicePreset::Custom(Box::new(|smartplug| smartplug.set_led_state(LedState::On)));
+        assert_eq!(format!("{custom:?}"), "Custom(..)");
+        smartplug.apply_preset(custom).unwrap();
+        assert_eq!(
+            mock.requests()[11],
+            json!({"system": {"set_led_off": {"off": 0}}})
+        );
     }
-}
 
-/// Smartplug hardware version (hardware revision).
-#[derive(Debug)]
-pub enum HwVersion {
-    Version1,
-    Version2,
-    Unsupported(String),
-}
+    #[test]
+    fn all_rules_summary() {
+        let schedule = json!({"get_rules": {"enable": 1, "version": 2, "rule_list": [
+            {"id": "S1", "name": "Evening off", "enable": 1, "wday": [1, 1, 1, 1, 1, 1, 1],
+             "stime_opt": 0, "smin": 1350, "sact": 0, "repeat": 1},
+            {"id": "L1", "name": "LED off", "enable": 1, "enable_type": 1,
+             "wday": [1, 1, 1, 1, 1, 1, 1], "stime_opt": 0, "smin": 1380, "sact": 0, "repeat": 1}
+        ], "err_code": 0}});
+        let count_down = json!({"get_rules": {"rule_list": [
+            {"id": "C1", "name": "shutdown", "enable": 1, "delay": 1800, "act": 0, "remain": 1795}
+        ], "err_code": 0}});
+        let anti_theft = json!({"get_rules": {"rule_list": [
+            {"id": "A1", "name": "Away", "enable": 1, "wday": [0, 1, 1, 1, 1, 1, 0],
+             "stime_opt": 0, "smin": 1080, "etime_opt": 0, "emin": 1380, "frequency": 5,
+             "repeat": 1}
+        ], "err_code": 0}});
+
+        let batch = {
+            let response = json!({
+                "schedule": schedule.clone(),
+                "count_down": count_down.clone(),
+                "anti_theft": anti_theft.clone(),
+            });
+            MockSmartplug::start(move |_| response.clone())
+        };
+        let summary = batch.hs110().all_rules_summary().unwrap();
+        assert_eq!(batch.requests().len(), 1);
+        assert_eq!(
+            batch.requests()[0],
+            json!({
+                "schedule": {"get_rules": {}},
+                "count_down": {"get_rules": {}},
+                "anti_theft": {"get_rules": {}},
+            })
+        );
+        assert_eq!(summary.schedule_rules.len(), 1);
+        assert_eq!(summary.schedule_rules[0].id, "S1");
+        assert_eq!(summary.countdown_rules[0].remain, Some(1795));
+        assert_eq!(
+            summary.antitheft_rules,
+            [AntitheftRule {
+                id: "A1".to_string(),
+                name: "Away".to_string(),
+                enable: true,
+                wday: [false, true, true, true, true, true, false],
+                stime_opt: ScheduleTimeOption::Time,
+                smin: 1080,
+                etime_opt: ScheduleTimeOption::Time,
+                emin: 1380,
+                frequency: 5,
+                repeat: true,
+                year: None,
+                month: None,
+                day: None,
+            }]
+        );
+
+        // Firmware answering only the first module of a batch request.
+        let sequential = MockSmartplug::start(move |request| {
+            let module = request.as_object().unwrap().keys().next().unwrap().clone();
+            let response = match module.as_str() {
+                "schedule" => schedule.clone(),
+                "count_down" => count_down.clone(),
+                _ => anti_theft.clone(),
+            };
+            json!({module: response})
+        });
+        assert_eq!(sequential.hs110().all_rules_summary().unwrap(), summary);
+        assert_eq!(sequential.requests().len(), 4);
+    }
+
+    #[test]
+    fn config_conversions() {
+        let configs = vec![
+            HS110Config {
+                addr: "192.168.1.100".to_string(),
+                name: None,
+                timeout_secs: Some(3),
+            },
+            HS110Config {
+                addr: "[::1]:10000".to_string(),
+                name: None,
+                timeout_secs: None,
+            },
+        ];
+        let smartplugs = configs
+            .clone()
+            .into_iter()
+            .map(HS110::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            smartplugs[0].socket_addr().to_string(),
+            "192.168.1.100:9999"
+        );
+        assert_eq!(smartplugs[0].timeout, Some(Duration::from_secs(3)));
+        assert_eq!(smartplugs[1].port(), 10000);
+        assert_eq!(smartplugs[1].timeout, None);
+
+        assert_eq!(
+            smartplugs
+                .into_iter()
+                .map(HS110Config::from)
+                .collect::<Vec<_>>(),
+            [
+                HS110Config {
+                    addr: "192.168.1.100:9999".to_string(),
+                    name: None,
+                    timeout_secs: Some(3),
+                },
+                HS110Config {
+                    addr: "[::1]:10000".to_string(),
+                    name: None,
+                    timeout_secs: None,
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::from_value::<HS110Config>(json!({"addr": "10.0.0.1"})).unwrap(),
+            HS110Config {
+                addr: "10.0.0.1".to_string(),
+                name: None,
+                timeout_secs: None,
+            }
+        );
+
+        assert!(HS110::try_from("bogus".to_string()).is_err());
+        assert!(HS110::try_from(HS110Config {
+            addr: "bogus".to_string(),
+            name: None,
+            timeout_secs: None,
+        })
+        .is_err());
+        assert_eq!(
+            HS110::try_from("10.0.0.1:9998".to_string()).unwrap().port(),
+            9998
+        );
+    }
+
+    #[test]
+    fn cloud_stop_connect() {
+        let cloudinfo = |stop_connect| {
+            let mut info =
+                serde_json::from_str::<Value>(include_str!("../tests/fixtures/cloudinfo.json"))
+                    .unwrap();
+            info["stopConnect"] = json!(stop_connect);
+            info["err_code"] = json!(0);
+            json!({"cnCloud": {"get_info": info}})
+        };
+        let mock = MockSmartplug::with_responses(vec![
+            cloudinfo(1),
+            cloudinfo(0),
+            json!({"cnCloud": {"stop_connect": {"err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+
+        assert!(smartplug.is_cloud_stop_connect().unwrap());
+        assert!(!smartplug.is_cloud_stop_connect().unwrap());
+        smartplug.set_cloud_stop_connect(true).unwrap();
+        smartplug.set_cloud_stop_connect(false).unwrap();
+        assert_eq!(
+            mock.requests()[2..],
+            [
+                json!({"cnCloud": {"stop_connect": {"stopConnect": 1}}}),
+                json!({"cnCloud": {"stop_connect": {"stopConnect": 0}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn states_as_u8() {
+        assert_eq!(PowerState::from_u8(0).unwrap(), PowerState::Off);
+        assert_eq!(PowerState::from_u8(1).unwrap(), PowerState::On);
+        assert!(matches!(
+            PowerState::from_u8(2),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(PowerState::On.as_u8(), 1);
+        assert_eq!(PowerState::Off.as_u8(), 0);
+        assert_eq!(LedState::On.as_u8(), 1);
+        assert_eq!(LedState::Off.as_u8(), 0);
+
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "relay_state": 1, "led_off": 1, "err_code": 0
+        }}})]);
+        let smartplug = mock.hs110();
+        assert_eq!(smartplug.power_state_as_u8().unwrap(), 1);
+        assert_eq!(smartplug.led_state_as_u8().unwrap(), 0);
+    }
+
+    #[test]
+    fn segmented_responses() {
+        /// Serves a single connection answering each request with a given response split into
+        /// segments at given offsets.
+        fn serve(listener: &std::net::TcpListener, response: &[u8], splits: &[usize]) {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_nodelay(true).unwrap();
+            let mut header = [0u8; 4];
+            while stream.read_exact(&mut header).is_ok() {
+                let mut payload = vec![0u8; u32::from_be_bytes(header) as usize];
+                stream.read_exact(&mut payload).unwrap();
+                let mut offset = 0;
+                for split in splits.iter().copied().chain([response.len()]) {
+                    stream.write_all(&response[offset..split]).unwrap();
+                    stream.flush().unwrap();
+                    std::thread::sleep(Duration::from_millis(5));
+                    offset = split;
+                }
+            }
+        }
+
+        // A response which is exactly `NET_BUFFER_SIZE` bytes long (including a length header).
+        let padding = "x".repeat(NET_BUFFER_SIZE);
+        let mut sysinfo = json!({"system": {"get_sysinfo": {
+            "relay_state": 1, "err_code": 0, "padding": ""
+        }}});
+        let len = sysinfo.to_string().len() + protocol::HEADER_LEN;
+        sysinfo["system"]["get_sysinfo"]["padding"] = json!(padding[..NET_BUFFER_SIZE - len]);
+        let response = HS110::encrypt(sysinfo.to_string());
+        assert_eq!(response.len(), NET_BUFFER_SIZE);
+
+        for splits in [
+            &[][..],
+            &[2, 4],
+            &[1, 3, 1000, 5000],
+            &[100, NET_BUFFER_SIZE - 1],
+        ] {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let smartplug = HS110::new(&listener.local_addr().unwrap().to_string())
+                .unwrap()
+                .with_timeout(Duration::from_secs(3));
+            let response = response.clone();
+            let server = std::thread::spawn(move || serve(&listener, &response, splits));
+
+            // The connection is kept open by the server, so the response has to be recognized
+            // as complete by its length.
+            smartplug.connect_only().unwrap();
+            assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+            assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+            smartplug.disconnect().unwrap();
+            server.join().unwrap();
+        }
+
+        // A smartplug closing the connection before sending a whole response.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let smartplug = HS110::new(&listener.local_addr().unwrap().to_string())
+            .unwrap()
+            .with_timeout(Duration::from_secs(3));
+        let truncated = response[..100].to_vec();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 64];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(&truncated).unwrap();
+        });
+        assert!(matches!(
+            smartplug.power_state(),
+            Err(TpLinkHs110Error::EncryptedPayloadLengthMismatch {
+                payload_len_actual: 96,
+                ..
+            })
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn emeter_calibration() {
+        let mut response = serde_json::from_str::<Value>(include_str!(
+            "../tests/fixtures/emeter_calibration.json"
+        ))
+        .unwrap();
+        response["err_code"] = json!(0);
+        let mock =
+            MockSmartplug::start(
+                move |request| match request.pointer("/emeter/get_vgain_igain") {
+                    Some(_) => json!({"emeter": {"get_vgain_igain": response}}),
+                    None => json!({"emeter": {"set_vgain_igain": {"err_code": 0}}}),
+                },
+            );
+        let smartplug = mock.hs110();
+
+        let calibration = smartplug.get_emeter_calibration().unwrap();
+        assert_eq!(
+            calibration,
+            EmeterCalibration {
+                vgain: 13462,
+                igain: 16835
+            }
+        );
+        smartplug
+            .set_emeter_calibration(&EmeterCalibration {
+                vgain: 13500,
+                ..calibration
+            })
+            .unwrap();
+        assert!(matches!(
+            smartplug.set_emeter_calibration(&EmeterCalibration {
+                vgain: 0,
+                ..calibration
+            }),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"emeter": {"get_vgain_igain": {}}}),
+                json!({"emeter": {"set_vgain_igain": {"vgain": 13500, "igain": 16835}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn cloud_management() {
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/cnCloud/get_info").is_some() {
+                let mut info =
+                    serde_json::from_str::<Value>(include_str!("../tests/fixtures/cloudinfo.json"))
+                        .unwrap();
+                info["err_code"] = json!(0);
+                json!({"cnCloud": {"get_info": info}})
+            } else {
+                let command = request["cnCloud"]
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .next()
+                    .unwrap();
+                json!({"cnCloud": {command: {"err_code": 0}}})
+            }
+        });
+        let smartplug = mock.hs110();
+
+        assert!(smartplug.is_cloud_bound().unwrap());
+        assert_eq!(smartplug.cloud_server().unwrap(), "n-devs.tplinkcloud.com");
+        smartplug.set_cloud_server("cloud.example.com").unwrap();
+        assert!(matches!(
+            smartplug.set_cloud_server(""),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        smartplug.unbind_cloud().unwrap();
+        assert_eq!(
+            mock.requests()[2..],
+            [
+                json!({"cnCloud": {"set_server_url": {"server": "cloud.example.com"}}}),
+                json!({"cnCloud": {"unbind": {}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn discover() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = device.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut rx_buf = [0u8; 1024];
+            let (nread, source) = device.recv_from(&mut rx_buf).unwrap();
+            let request =
+                serde_json::from_str::<Value>(&protocol::decrypt_datagram(&rx_buf[..nread]))
+                    .unwrap();
+            assert_eq!(request, json!({"system": {"get_sysinfo": {}}}));
+
+            for response in [
+                json!({"system": {"get_sysinfo": {
+                    "deviceId": "8006ABCD", "model": "HS110(EU)", "alias": "Kitchen", "err_code": 0
+                }}}),
+                // A duplicate and a response of something else.
+                json!({"system": {"get_sysinfo": {
+                    "deviceId": "8006ABCD", "model": "HS110(EU)", "alias": "Kitchen", "err_code": 0
+                }}}),
+                json!({"system": {"get_sysinfo": {"err_code": -1}}}),
+            ] {
+                device
+                    .send_to(&protocol::encrypt_datagram(response.to_string()), source)
+                    .unwrap();
+            }
+        });
+
+        let devices = HS110::discover_at(target, Duration::from_millis(300)).unwrap();
+        server.join().unwrap();
+        assert_eq!(
+            devices,
+            [DiscoveredDevice {
+                addr: "127.0.0.1:9999".parse().unwrap(),
+                device_id: "8006ABCD".to_string(),
+                model: "HS110(EU)".to_string(),
+                alias: "Kitchen".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn led_brightness() {
+        let dimmable =
+            MockSmartplug::start(
+                |request| match request.pointer("/system/get_led_brightness") {
+                    Some(_) => {
+                        json!({"system": {"get_led_brightness": {"brightness": 40, "err_code": 0}}})
+                    }
+                    None => json!({"system": {"set_led_brightness": {"err_code": 0}}}),
+                },
+            );
+        let smartplug = dimmable.hs110();
+        assert_eq!(smartplug.get_led_brightness().unwrap(), 40);
+        smartplug.set_led_brightness(70).unwrap();
+        assert!(matches!(
+            smartplug.set_led_brightness(101),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(
+            dimmable.requests()[1],
+            json!({"system": {"set_led_brightness": {"brightness": 70}}})
+        );
+        assert_eq!(dimmable.requests().len(), 2);
+
+        // Command level and module level "member not support" errors.
+        let binary = MockSmartplug::start(|request| {
+            let system = request["system"].as_object().unwrap();
+            match system.keys().next().map(String::as_str) {
+                Some("get_sysinfo") => {
+                    json!({"system": {"get_sysinfo": {"led_off": 1, "err_code": 0}}})
+                }
+                Some("set_led_off") => json!({"system": {"set_led_off": {"err_code": 0}}}),
+                Some("get_led_brightness") => {
+                    json!({"system": {"err_code": -2, "err_msg": "member not support"}})
+                }
+                _ => json!({"system": {"set_led_brightness": {"err_code": -2}}}),
+            }
+        });
+        let smartplug = binary.hs110();
+        assert_eq!(smartplug.get_led_brightness().unwrap(), 0);
+        smartplug.set_led_brightness(100).unwrap();
+        smartplug.set_led_brightness(0).unwrap();
+        assert!(matches!(
+            smartplug.set_led_brightness(50),
+            Err(TpLinkHs110Error::FeatureNotSupported)
+        ));
+        assert_eq!(
+            binary.requests()[2..],
+            [
+                json!({"system": {"set_led_brightness": {"brightness": 100}}}),
+                json!({"system": {"set_led_off": {"off": 0}}}),
+                json!({"system": {"set_led_brightness": {"brightness": 0}}}),
+                json!({"system": {"set_led_off": {"off": 1}}}),
+                json!({"system": {"set_led_brightness": {"brightness": 50}}}),
+            ]
+        );
+
+        // Other errors aren't masked.
+        let failing = MockSmartplug::with_responses(vec![
+            json!({"system": {"set_led_brightness": {"err_code": -3}}}),
+        ]);
+        assert!(matches!(
+            failing.hs110().set_led_brightness(0),
+            Err(TpLinkHs110Error::SmartplugErrCode(-3))
+        ));
+    }
+
+    #[test]
+    fn connectivity() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mock = MockSmartplug::start(move |request| {
+            match request
+                .as_object()
+                .unwrap()
+                .keys()
+                .next()
+                .map(String::as_str)
+            {
+                // Asia/Kolkata.
+                Some("time") if request.pointer("/time/get_timezone").is_some() => {
+                    json!({"time": {"get_timezone": {"index": 75, "err_code": 0}}})
+                }
+                Some("time") => {
+                    // UTC+05:30 with a clock running a bit late.
+                    let mut time = serde_json::to_value(DeviceTime::from_seconds_since_epoch(
+                        now + 5 * 3600 + 30 * 60 - 20,
+                    ))
+                    .unwrap();
+                    time["err_code"] = json!(0);
+                    json!({"time": {"get_time": time}})
+                }
+                Some("cnCloud") => json!({"cnCloud": {"get_info": {
+                    "binded": 1, "cld_connection": 0, "fwDlPage": "", "fwNotifyType": 0,
+                    "illegalType": 0, "server": "n-devs.tplinkcloud.com", "stopConnect": 0,
+                    "tcspInfo": "", "tcspStatus": 1, "username": "", "err_code": 0
+                }}}),
+                _ => json!({"system": {"get_sysinfo": {
+                    "type": "IOT.SMARTPLUGSWITCH", "deviceId": "8006ABCD", "model": "HS110(EU)",
+                    "alias": "Kitchen", "err_code": 0
+                }}}),
+            }
+        });
+
+        let test = mock.hs110().test_connectivity(false).unwrap();
+        assert!(test.tcp_ok && test.protocol_ok);
+        assert!(!test.device_id_ok && !test.cloud_ok && !test.ntp_ok);
+        assert!(test.errors.is_empty());
+        assert_eq!(mock.requests().len(), 1);
+
+        let test = mock.hs110().test_connectivity(true).unwrap();
+        assert!(
+            test.tcp_ok && test.protocol_ok && test.device_id_ok,
+            "{test:?}"
+        );
+        assert!(!test.cloud_ok);
+        let mut failed = test.errors.keys().map(String::as_str).collect::<Vec<_>>();
+        failed.sort_unstable();
+        if cfg!(feature = "chrono") {
+            assert!(test.ntp_ok, "{test:?}");
+            assert_eq!(failed, ["cloud"]);
+        } else {
+            assert!(!test.ntp_ok);
+            assert_eq!(failed, ["cloud", "ntp"]);
+        }
+
+        let test = HS110::new("127.0.0.1:1")
+            .unwrap()
+            .with_timeout(Duration::from_secs(1))
+            .test_connectivity(true)
+            .unwrap();
+        assert_eq!(
+            (
+                test.tcp_ok,
+                test.protocol_ok,
+                test.device_id_ok,
+                test.cloud_ok,
+                test.ntp_ok
+            ),
+            (false, false, false, false, false)
+        );
+        assert_eq!(test.errors.len(), 5);
+    }
+
+    #[test]
+    fn mac_address() {
+        let expected = MacAddress([0x70, 0x4f, 0x57, 0x57, 0xa1, 0x14]);
+        for mac in [
+            "70:4F:57:57:A1:14",
+            "70:4f:57:57:a1:14",
+            "70-4F-57-57-a1-14",
+            "704F.5757.A114",
+            "704f5757a114",
+        ] {
+            assert_eq!(MacAddress::try_from(mac).unwrap(), expected, "{mac}");
+        }
+        assert_eq!(expected.to_string(), "70:4F:57:57:A1:14");
+        assert_eq!("704f5757a114".parse::<MacAddress>().unwrap(), expected);
+
+        for mac in [
+            "",
+            "70:4F:57:57:A1",
+            "70:4F:57:57:A1:14:00",
+            "70:4F:57:57:A1:1G",
+            "70:4F:57-57:A1:14",
+            "704:F57:57:A1:14:0",
+            "70:4F:57:57:A1:+1",
+            "704F5757A11",
+            "704F.5757.A11",
+            "70.4F.57.57.A1.14",
+        ] {
+            assert!(
+                matches!(
+                    MacAddress::try_from(mac),
+                    Err(TpLinkHs110Error::InvalidParameter(_))
+                ),
+                "{mac}"
+            );
+        }
+
+        assert_eq!(
+            serde_json::to_value(expected).unwrap(),
+            json!("70:4F:57:57:A1:14")
+        );
+        assert_eq!(
+            serde_json::from_value::<MacAddress>(json!("70-4f-57-57-a1-14")).unwrap(),
+            expected
+        );
+        assert!(serde_json::from_value::<MacAddress>(json!("bogus")).is_err());
+        assert_eq!(
+            HashSet::from([expected, MacAddress::try_from("704f5757a114").unwrap()]).len(),
+            1
+        );
+
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"system": {"get_sysinfo": {"mac": "70:4F:57:57:A1:14", "err_code": 0}}}),
+            json!({"system": {"get_sysinfo": {"mic_mac": "704F5757A114", "err_code": 0}}}),
+            json!({"system": {"get_sysinfo": {"err_code": 0}}}),
+        ]);
+        let smartplug = mock.hs110();
+        assert_eq!(smartplug.get_mac_address().unwrap(), expected);
+        assert_eq!(smartplug.get_mac_address().unwrap(), expected);
+        assert!(matches!(
+            smartplug.get_mac_address(),
+            Err(TpLinkHs110Error::KeyIsNotAvailable { key, .. }) if key == "mac"
+        ));
+    }
+
+    #[test]
+    fn wifi_scan() {
+        const SCAN_LATENCY: Duration = Duration::from_millis(500);
+
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/netif/get_scaninfo/refresh") == Some(&json!(1)) {
+                std::thread::sleep(SCAN_LATENCY);
+            }
+            json!({"netif": {"get_scaninfo": {"ap_list": [
+                {"ssid": "MERCUSYS_1A04", "key_type": 3},
+                {"ssid": "Guest", "key_type": 0}
+            ], "err_code": 0}}})
+        });
+        let smartplug = mock.hs110();
+
+        let started = Instant::now();
+        smartplug.trigger_wifi_scan().unwrap();
+        assert!(started.elapsed() < SCAN_LATENCY);
+        let access_points = smartplug.get_cached_ap_list().unwrap();
+        assert!(started.elapsed() < SCAN_LATENCY);
+        assert_eq!(
+            access_points,
+            [
+                AccessPoint {
+                    ssid: "MERCUSYS_1A04".to_string(),
+                    key_type: 3
+                },
+                AccessPoint {
+                    ssid: "Guest".to_string(),
+                    key_type: 0
+                },
+            ]
+        );
+
+        assert!(matches!(
+            smartplug.scan_and_wait(SCAN_LATENCY / 5),
+            Err(TpLinkHs110Error::IO(_))
+        ));
+        assert_eq!(
+            smartplug.scan_and_wait(SCAN_LATENCY * 4).unwrap(),
+            access_points
+        );
+
+        std::thread::sleep(SCAN_LATENCY);
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| *request == &json!({"netif": {"get_scaninfo": {"refresh": 1}}}))
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn mac_address_formats() {
+        let mac = MacAddress([0x70, 0x4f, 0x57, 0x0a, 0xa1, 0x14]);
+        assert_eq!(format!("{mac}"), "70:4F:57:0A:A1:14");
+        assert_eq!(format!("{mac:X}"), "704F570AA114");
+        assert_eq!(format!("{mac:x}"), "704f570aa114");
+        assert_eq!(format!("{mac:x}").parse::<MacAddress>().unwrap(), mac);
+
+        assert_eq!(mac.to_bytes(), &[0x70, 0x4f, 0x57, 0x0a, 0xa1, 0x14]);
+        assert_eq!(mac.into_bytes(), [0x70, 0x4f, 0x57, 0x0a, 0xa1, 0x14]);
+        assert_eq!(mac.oui(), [0x70, 0x4f, 0x57]);
+
+        assert!(mac.is_tplink_oui());
+        assert!("50:C7:BF:00:00:01"
+            .parse::<MacAddress>()
+            .unwrap()
+            .is_tplink_oui());
+        assert!(!"A4:83:E7:00:00:03"
+            .parse::<MacAddress>()
+            .unwrap()
+            .is_tplink_oui());
+        assert!(!MacAddress([0x4f, 0x57, 0x70, 0, 0, 0]).is_tplink_oui());
+    }
+
+    #[test]
+    fn sysinfo_stream() {
+        let sysinfo = |relay_state| {
+            json!({"system": {"get_sysinfo": {
+                "alias": "Bathroom", "model": "HS110(EU)", "deviceId": "8006ABCD",
+                "hw_ver": "1.0", "sw_ver": "1.2.6 Build 200727 Rel.120821",
+                "mac": "70:4F:57:57:A1:14", "relay_state": relay_state, "led_off": 0,
+                "on_time": 120, "rssi": -64, "err_code": 0
+            }}})
+        };
+        let mock = MockSmartplug::with_responses(vec![
+            sysinfo(1),
+            sysinfo(1),
+            json!({"system": {"get_sysinfo": {"err_code": -1}}}),
+            sysinfo(0),
+            sysinfo(1),
+        ]);
+
+        let changes = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let recorded = changes.clone();
+        let interval = Duration::from_millis(20);
+        let started = Instant::now();
+        let results = mock
+            .hs110()
+            .sysinfo_stream(interval)
+            .on_power_change(move |old, new| recorded.borrow_mut().push((old, new)))
+            .take(5)
+            .collect::<Vec<_>>();
+        assert!(started.elapsed() >= interval * 4);
+
+        assert_eq!(results[0].as_ref().unwrap().alias, "Bathroom");
+        assert_eq!(results[0].as_ref().unwrap().mac, "70:4F:57:57:A1:14");
+        assert!(matches!(
+            results[2],
+            Err(TpLinkHs110Error::SmartplugErrCode(-1))
+        ));
+        assert_eq!(
+            results
+                .iter()
+                .filter_map(|sysinfo| Some(sysinfo.as_ref().ok()?.relay_state))
+                .collect::<Vec<_>>(),
+            [
+                PowerState::On,
+                PowerState::On,
+                PowerState::Off,
+                PowerState::On
+            ]
+        );
+        assert_eq!(
+            *changes.borrow(),
+            [
+                (PowerState::On, PowerState::Off),
+                (PowerState::Off, PowerState::On)
+            ]
+        );
+    }
+
+    #[test]
+    fn loopback_test() {
+        let delays = Arc::new(Mutex::new(VecDeque::from([200, 10, 10])));
+        let pending = delays.clone();
+        let mock = MockSmartplug::start(move |_| {
+            if let Some(delay) = pending.lock().unwrap().pop_front() {
+                std::thread::sleep(Duration::from_millis(delay));
+            }
+            json!({"system": {"get_sysinfo": {"err_code": 0}}})
+        });
+        let smartplug = mock.hs110();
+        assert_eq!(smartplug.avg_loopback_latency(), None);
+
+        let latency = smartplug.perform_loopback_test().unwrap();
+        assert!(latency >= Duration::from_millis(200));
+        assert_eq!(smartplug.avg_loopback_latency(), Some(latency));
+
+        for _ in 0..LOOPBACK_HISTORY_LEN {
+            smartplug.clone().perform_loopback_test().unwrap();
+        }
+        // The slow test has been pushed out of the history.
+        assert!(smartplug.avg_loopback_latency().unwrap() < Duration::from_millis(200));
+        assert!(smartplug.avg_loopback_latency().unwrap() > Duration::ZERO);
+        assert_eq!(
+            smartplug.loopback_latencies.lock().unwrap().len(),
+            LOOPBACK_HISTORY_LEN
+        );
+
+        // Invalid responses fail the test.
+        let invalid = MockSmartplug::with_responses(vec![json!({"system": {}})]);
+        assert!(invalid.hs110().perform_loopback_test().is_err());
+    }
+
+    #[test]
+    fn auto_off_on_overload() {
+        let legacy = MockSmartplug::start(|request| {
+            let emeter = request["emeter"].as_object().unwrap();
+            match emeter.keys().next().map(String::as_str) {
+                Some("get_overload_protection") => {
+                    json!({"emeter": {"get_overload_protection": {"enable": 1, "err_code": 0}}})
+                }
+                Some("set_overload_protection") => {
+                    json!({"emeter": {"set_overload_protection": {"err_code": 0}}})
+                }
+                _ => json!({"emeter": {"err_code": -2, "err_msg": "member not support"}}),
+            }
+        });
+        let smartplug = legacy.hs110();
+        assert!(smartplug.get_auto_off_on_overload().unwrap());
+        smartplug.set_auto_off_on_overload(false).unwrap();
+        assert_eq!(
+            legacy.requests(),
+            [
+                json!({"emeter": {"get_over_limit": {}}}),
+                json!({"emeter": {"get_overload_protection": {}}}),
+                json!({"emeter": {"set_over_limit": {"enable": 0}}}),
+                json!({"emeter": {"set_overload_protection": {"enable": 0}}}),
+            ]
+        );
 
-/// Smartplug's power relay state.
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum PowerState {
-    /// Power relay is ON, i.e. smartplug is powering its outlet (connected device).
-    On,
+        let current = MockSmartplug::with_responses(vec![
+            json!({"emeter": {"set_over_limit": {"err_code": 0}}}),
+        ]);
+        current.hs110().set_auto_off_on_overload(true).unwrap();
+        assert_eq!(
+            current.requests(),
+            [json!({"emeter": {"set_over_limit": {"enable": 1}}})]
+        );
 
-    /// Power relay is OFF, i.e. there is no power on smartplug's outlet.
-    Off,
-}
+        let unsupported = MockSmartplug::with_responses(vec![
+            json!({"emeter": {"err_code": -2, "err_msg": "member not support"}}),
+        ]);
+        assert!(matches!(
+            unsupported.hs110().set_auto_off_on_overload(true),
+            Err(TpLinkHs110Error::FeatureNotSupported)
+        ));
 
-impl Display for PowerState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                PowerState::On => "ON",
-                PowerState::Off => "OFF",
-            }
-        )
+        let overloaded = |model: &'static str, current_ma: f64| {
+            MockSmartplug::start(move |request| {
+                let mut response = json!({});
+                if request.get("system").is_some() {
+                    response["system"] = json!({"get_sysinfo": {"model": model, "err_code": 0}});
+                }
+                if request.get("emeter").is_some() {
+                    response["emeter"] = json!({"get_realtime": {
+                        "voltage_mv": 230000.0, "current_ma": current_ma, "power_mw": 0.0,
+                        "total_wh": 0.0, "err_code": 0
+                    }});
+                }
+                response
+            })
+            .hs110()
+            .is_overloaded()
+            .unwrap()
+        };
+        assert!(!overloaded("HS110(EU)", 15_000.0));
+        assert!(overloaded("HS110(UK)", 15_000.0));
+        assert!(overloaded("HS110(EU)", 16_500.0));
+
+        assert_eq!(
+            "HS110(US)".parse::<DeviceModel>().unwrap().max_current_a(),
+            15.0
+        );
+        assert_eq!(
+            "HS105".parse::<DeviceModel>().unwrap().max_current_a(),
+            10.0
+        );
     }
-}
 
-impl Not for PowerState {
-    type Output = PowerState;
+    #[test]
+    fn alias() {
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/system/set_dev_alias").is_some() {
+                return json!({"system": {"set_dev_alias": {"err_code": 0}}});
+            }
+            json!({"system": {"get_sysinfo": {"alias": "Bathroom", "err_code": 0}}})
+        });
+        let smartplug = mock.hs110();
+        assert_eq!(smartplug.get_alias().unwrap(), "Bathroom");
+        #[allow(deprecated)]
+        let hostname = smartplug.hostname().unwrap();
+        assert_eq!(hostname, "Bathroom");
 
-    fn not(self) -> Self::Output {
-        match self {
-            PowerState::On => PowerState::Off,
-            PowerState::Off => PowerState::On,
-        }
+        smartplug.set_alias("Kitchen").unwrap();
+        assert_eq!(
+            mock.requests().last().unwrap(),
+            &json!({"system": {"set_dev_alias": {"alias": "Kitchen"}}})
+        );
     }
-}
 
-impl From<PowerState> for bool {
-    fn from(value: PowerState) -> Self {
-        match value {
-            PowerState::On => true,
-            PowerState::Off => false,
-        }
-    }
-}
+    #[test]
+    fn relay_switch_count() {
+        let sysinfo =
+            serde_json::from_str::<Value>(include_str!("../tests/fixtures/sysinfo.json")).unwrap();
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": sysinfo}})]);
+        let smartplug = mock.hs110();
+        assert_eq!(smartplug.relay_switch_count().unwrap(), 1234);
+        assert!((smartplug.relay_life_remaining_pct(10_000).unwrap() - 87.66).abs() < 1e-9);
+        assert_eq!(smartplug.relay_life_remaining_pct(1000).unwrap(), 0.0);
+        assert!(matches!(
+            smartplug.relay_life_remaining_pct(0),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
 
-impl From<bool> for PowerState {
-    fn from(value: bool) -> Self {
-        match value {
-            true => Self::On,
-            false => Self::Off,
-        }
+        let mut sysinfo = sysinfo;
+        let count = sysinfo.as_object_mut().unwrap().remove("sw_cnt").unwrap();
+        sysinfo["relay_cnt"] = count;
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": sysinfo}})]);
+        assert_eq!(mock.hs110().relay_switch_count().unwrap(), 1234);
+
+        sysinfo.as_object_mut().unwrap().remove("relay_cnt");
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": sysinfo}})]);
+        assert!(matches!(
+            mock.hs110().relay_switch_count(),
+            Err(TpLinkHs110Error::FeatureNotSupported)
+        ));
     }
-}
 
-/// Smartplug LED indicator state.
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum LedState {
-    /// LED light is ON.
-    On,
+    #[test]
+    fn atomic_toggle() {
+        // Firmware which either inverts the relay state given a special state value or treats it
+        // as an ordinary "on".
+        let start = |inverts: bool| {
+            let relay_state = Arc::new(AtomicUsize::new(0));
+            let mock = MockSmartplug::start(move |request| {
+                if let Some(requested) = request.pointer("/system/set_relay_state/state") {
+                    match requested.as_u64().unwrap() as usize {
+                        2 if inverts => relay_state.fetch_xor(1, Ordering::SeqCst),
+                        2 => relay_state.swap(1, Ordering::SeqCst),
+                        requested => relay_state.swap(requested, Ordering::SeqCst),
+                    };
+                    return json!({"system": {"set_relay_state": {"err_code": 0}}});
+                }
+                json!({"system": {"get_sysinfo": {
+                    "relay_state": relay_state.load(Ordering::SeqCst), "err_code": 0
+                }}})
+            });
+            (mock.hs110(), mock)
+        };
+        let atomic_requests = |mock: &MockSmartplug| {
+            mock.requests()
+                .iter()
+                .filter(|request| {
+                    request.pointer("/system/set_relay_state/state") == Some(&json!(2))
+                })
+                .count()
+        };
 
-    /// LED light is OFF.
-    Off,
-}
+        let (smartplug, atomic) = start(true);
+        assert_eq!(smartplug.supports_atomic_toggle(), None);
+        assert!(atomic.requests().is_empty());
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::On);
+        // Switching on doesn't prove the relay is inverted.
+        assert_eq!(smartplug.supports_atomic_toggle(), None);
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::Off);
+        assert_eq!(smartplug.supports_atomic_toggle(), Some(true));
+        let requests = atomic.requests().len();
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::On);
+        assert_eq!(atomic.requests().len(), requests + 2);
+        assert_eq!(atomic_requests(&atomic), 3);
 
-impl Display for LedState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                LedState::On => "ON",
-                LedState::Off => "OFF",
+        let (smartplug, non_inverting) = start(false);
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::On);
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::Off);
+        assert_eq!(smartplug.supports_atomic_toggle(), Some(false));
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::On);
+        assert_eq!(smartplug.power_state().unwrap(), PowerState::On);
+        assert_eq!(atomic_requests(&non_inverting), 2);
+
+        // Firmware rejecting the atomic command, read-modify-write is used instead.
+        let legacy = MockSmartplug::start(|request| {
+            match request.pointer("/system/set_relay_state/state") {
+                Some(state) if *state == json!(2) => {
+                    json!({"system": {"set_relay_state": {"err_code": -3, "err_msg": "invalid argument"}}})
+                }
+                Some(_) => json!({"system": {"set_relay_state": {"err_code": 0}}}),
+                None => json!({"system": {"get_sysinfo": {"relay_state": 1, "err_code": 0}}}),
             }
-        )
+        });
+        let smartplug = legacy.hs110();
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::Off);
+        assert_eq!(smartplug.supports_atomic_toggle(), Some(false));
+        assert_eq!(smartplug.atomic_toggle().unwrap(), PowerState::Off);
+        assert_eq!(atomic_requests(&legacy), 1);
     }
-}
 
-impl Not for LedState {
-    type Output = LedState;
+    #[test]
+    fn device_usage_stats() {
+        let mut response =
+            serde_json::from_str::<Value>(include_str!("../tests/fixtures/device_usage.json"))
+                .unwrap();
+        response["err_code"] = json!(0);
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"schedule": {"get_device_usage": response}}),
+        ]);
 
-    fn not(self) -> Self::Output {
-        match self {
-            LedState::On => LedState::Off,
-            LedState::Off => LedState::On,
-        }
+        let stats = mock.hs110().get_device_usage_stats().unwrap();
+        assert_eq!(stats.time_usage[3], 610);
+        assert!((stats.total_weekly_on_hours() - 55.0).abs() < 1e-9);
+        assert!((stats.avg_daily_power_usage_wh() - 147.5).abs() < 1e-9);
+        assert_eq!(
+            mock.requests(),
+            [json!({"schedule": {"get_device_usage": {}}})]
+        );
     }
-}
 
-impl From<LedState> for bool {
-    fn from(value: LedState) -> Self {
-        match value {
-            LedState::On => true,
-            LedState::Off => false,
+    #[test]
+    fn source_port_range() {
+        let mock = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "alias": "Kitchen", "err_code": 0
+        }}})]);
+        for (start, end) in [(0, 10), (10, 0), (20, 10)] {
+            assert!(matches!(
+                mock.hs110().with_source_port_range(start, end),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
         }
-    }
-}
 
-impl From<bool> for LedState {
-    fn from(value: bool) -> Self {
-        match value {
-            true => Self::On,
-            false => Self::Off,
+        // Occupy a part of the range, so free ports have to be looked up.
+        let taken = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let start = taken.local_addr().unwrap().port();
+        let end = start.saturating_add(16);
+        let smartplug = mock.hs110().with_source_port_range(start, end).unwrap();
+        let streams = (0..3)
+            .map(|_| smartplug.connect().unwrap())
+            .collect::<Vec<_>>();
+        for stream in &streams {
+            let port = stream.local_addr().unwrap().port();
+            assert!((start + 1..=end).contains(&port), "{port}");
         }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::*;
-    use once_cell::sync::Lazy;
-    use serial_test::serial;
+        assert_eq!(smartplug.get_alias().unwrap(), "Kitchen");
 
-    static TEST_TARGET_ADDR: Lazy<String> =
-        Lazy::new(|| std::env::var("TEST_TARGET_ADDR").expect("TEST_TARGET_ADDR env variable"));
+        let single = mock
+            .hs110()
+            .with_local_bind_addr("127.0.0.1:0".parse().unwrap())
+            .with_source_port_range(start, start)
+            .unwrap();
+        assert!(matches!(single.connect(), Err(TpLinkHs110Error::IO(_))));
+    }
 
     #[test]
-    #[serial]
-    fn hostname() {
-        let smartplug = HS110::new(&*TEST_TARGET_ADDR)
-            .unwrap()
-            .with_timeout(Duration::from_secs(3));
-        assert!(smartplug.hostname().is_ok());
-
-        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
-        assert!(smartplug.hostname().is_ok());
+    fn set_names() {
+        let mock = MockSmartplug::start(|request| {
+            let command = request["system"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .next()
+                .unwrap();
+            json!({"system": {command: {"err_code": 0}}})
+        });
+        let smartplug = mock.hs110();
+        smartplug.set_alias("Bathroom").unwrap();
+        smartplug.set_dev_name("Smart Wi-Fi Plug").unwrap();
+        // A factory device type name could be set back.
+        let sysinfo =
+            serde_json::from_str::<Value>(include_str!("../tests/fixtures/sysinfo.json")).unwrap();
+        let factory = sysinfo["dev_name"].as_str().unwrap();
+        assert!(factory.len() > MAX_NAME_LEN);
+        smartplug.set_dev_name(factory).unwrap();
+        // The limit is in bytes, not characters.
+        let long = "Ванна".repeat(4);
+        assert_eq!(long.chars().count(), 20);
+        for name in ["", "A very long name of a smartplug!", &long, "Line\nbreak"] {
+            assert!(matches!(
+                smartplug.set_alias(name),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+        for name in ["", &"Plug ".repeat(13), "Line\nbreak"] {
+            assert!(matches!(
+                smartplug.set_dev_name(name),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"system": {"set_dev_alias": {"alias": "Bathroom"}}}),
+                json!({"system": {"set_dev_name": {"dev_name": "Smart Wi-Fi Plug"}}}),
+                json!({"system": {"set_dev_name": {"dev_name": factory}}}),
+            ]
+        );
 
+        let rejected = MockSmartplug::with_responses(vec![
+            json!({"system": {"set_dev_name": {"err_code": -3, "err_msg": "invalid argument"}}}),
+        ]);
         assert!(matches!(
-            smartplug.hw_version(),
-            Ok(HwVersion::Version1) | Ok(HwVersion::Version2)
+            rejected.hs110().set_dev_name("Plug"),
+            Err(TpLinkHs110Error::SmartplugErrCode(-3))
         ));
     }
 
     #[test]
-    fn switch_led_on_off() {
-        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
+    fn swap_devices() {
+        /// Fake smartplug keeping its alias and schedule rules, `fail_location` makes it reject
+        /// location changes.
+        fn start(alias: &'static str, latitude_i: i64, fail_location: bool) -> MockSmartplug {
+            let state = Mutex::new((alias.to_string(), Vec::<Value>::new()));
+            MockSmartplug::start(move |request| {
+                let mut state = state.lock().unwrap();
+                let (module, command) = request
+                    .as_object()
+                    .and_then(|modules| modules.iter().next())
+                    .and_then(|(module, commands)| {
+                        Some((module.clone(), commands.as_object()?.keys().next()?.clone()))
+                    })
+                    .unwrap();
+                let params = &request[&module][&command];
+                let mut response = match command.as_str() {
+                    "get_sysinfo" => json!({
+                        "alias": state.0, "latitude_i": latitude_i, "longitude_i": 305_230
+                    }),
+                    "set_dev_alias" => {
+                        state.0 = params["alias"].as_str().unwrap().to_string();
+                        json!({})
+                    }
+                    "set_dev_location" if fail_location => json!({"err_code": -3}),
+                    "get_rules" => json!({"rule_list": state.1}),
+                    "delete_rules" => {
+                        let ids = params["id_list"].as_array().unwrap();
+                        state.1.retain(|rule| !ids.contains(&rule["id"]));
+                        json!({})
+                    }
+                    "add_rule" => {
+                        let mut rule = params.clone();
+                        rule["id"] = json!(format!("{alias}-{}", state.1.len()));
+                        state.1.push(rule);
+                        json!({"id": format!("{alias}-{}", state.1.len() - 1)})
+                    }
+                    _ => json!({}),
+                };
+                if response.get("err_code").is_none() {
+                    response["err_code"] = json!(0);
+                }
+                json!({module: {command: response}})
+            })
+        }
 
-        let original_state = smartplug.led_state().expect("failed to obtain LED state");
+        let kitchen = start("Kitchen", 504_470, false);
+        let bathroom = start("Bathroom", 504_470, false);
+        let rule = ScheduleRule {
+            id: String::new(),
+            name: "Evening".to_string(),
+            enable: true,
+            wday: [true; 7],
+            stime_opt: ScheduleTimeOption::Time,
+            smin: 18 * 60,
+            soffset: None,
+            sact: PowerState::On,
+            repeat: true,
+            year: None,
+            month: None,
+            day: None,
+        };
+        kitchen.hs110().add_schedule_rule(&rule).unwrap();
+        let led_rule = LedScheduleRule {
+            id: String::new(),
+            name: "Night".to_string(),
+            enable: true,
+            wday: [true; 7],
+            stime_opt: ScheduleTimeOption::Time,
+            smin: 23 * 60,
+            soffset: None,
+            action: LedState::Off,
+            repeat: true,
+            year: None,
+            month: None,
+            day: None,
+        };
+        kitchen.hs110().add_led_schedule_rule(&led_rule).unwrap();
 
-        assert!(smartplug.set_led_state(!original_state).is_ok());
+        let plan = kitchen
+            .hs110()
+            .swap_devices_dry_run(&bathroom.hs110())
+            .unwrap();
+        assert_eq!(plan.changed_fields, ["alias", "schedule_rules"]);
         assert_eq!(
-            smartplug.led_state().expect("failed to obtain LED state"),
-            !original_state
+            plan.this.location,
+            Some(Location {
+                latitude: 50.447,
+                longitude: 30.523
+            })
         );
+        assert_eq!(kitchen.hs110().get_alias().unwrap(), "Kitchen");
 
-        assert!(smartplug.set_led_state(original_state).is_ok());
+        kitchen.hs110().swap_devices(&bathroom.hs110()).unwrap();
+        let (kitchen_now, bathroom_now) = (
+            kitchen.hs110().snapshot().unwrap(),
+            bathroom.hs110().snapshot().unwrap(),
+        );
+        assert_eq!(kitchen_now.alias, "Bathroom");
+        assert!(kitchen_now.schedule_rules.is_empty());
+        assert_eq!(bathroom_now.alias, "Kitchen");
+        assert_eq!(bathroom_now.schedule_rules[0].name, "Evening");
+        assert_eq!(bathroom_now.changed_fields(&plan.this), Vec::<&str>::new());
+        // LED schedule rules stay with a smartplug.
         assert_eq!(
-            smartplug.led_state().expect("failed to obtain LED state"),
-            original_state
+            kitchen.hs110().get_led_schedule_rules().unwrap(),
+            [LedScheduleRule {
+                id: "Kitchen-1".to_string(),
+                ..led_rule
+            }]
         );
+        assert!(bathroom
+            .hs110()
+            .get_led_schedule_rules()
+            .unwrap()
+            .is_empty());
+
+        let stubborn = start("Hall", 0, true);
+        let stubborn_addr = stubborn.hs110().socket_addr();
+        match kitchen.hs110().swap_devices(&stubborn.hs110()) {
+            Err(TpLinkHs110Error::PartialRestore(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].0, format!("{stubborn_addr} location"));
+                assert!(matches!(
+                    errors[0].1,
+                    TpLinkHs110Error::SmartplugErrCode(-3)
+                ));
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(stubborn.hs110().get_alias().unwrap(), "Bathroom");
     }
 
     #[test]
-    #[serial]
-    #[ignore = "power-cycles devices connected to the plug"]
-    fn switch_power_on_off() {
-        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
-
-        let original_state = smartplug
-            .power_state()
-            .expect("failed to obtain smartplug power state");
+    fn power_strip_child_plugs() {
+        let sysinfo =
+            serde_json::from_str::<Value>(include_str!("../tests/fixtures/hs300_sysinfo.json"))
+                .unwrap();
+        let mock = MockSmartplug::start(move |request| {
+            if request.pointer("/system/get_sysinfo").is_some() {
+                json!({"system": {"get_sysinfo": sysinfo}})
+            } else {
+                json!({"system": {"set_relay_state": {"err_code": 0}}})
+            }
+        });
 
-        assert!(smartplug.set_power_state(!original_state).is_ok());
+        let children = mock.hs110().get_children().unwrap().unwrap();
         assert_eq!(
-            smartplug
-                .power_state()
-                .expect("failed to obtain smartplug power state"),
-            !original_state
+            children
+                .iter()
+                .map(|child| (child.id.as_str(), child.alias.as_str(), child.state))
+                .collect::<Vec<_>>(),
+            [
+                ("8006AF35494E7DB13DDE9B8F40BF2E7A00", "Desk", PowerState::On),
+                (
+                    "8006AF35494E7DB13DDE9B8F40BF2E7A01",
+                    "Lamp",
+                    PowerState::Off
+                ),
+                (
+                    "8006AF35494E7DB13DDE9B8F40BF2E7A02",
+                    "Monitor",
+                    PowerState::On
+                ),
+            ]
+        );
+        assert_eq!(
+            children[1].parent().socket_addr(),
+            mock.hs110().socket_addr()
         );
 
-        assert!(smartplug.set_power_state(original_state).is_ok());
+        children[1].set_power_state(PowerState::On).unwrap();
         assert_eq!(
-            smartplug
-                .power_state()
-                .expect("failed to obtain smartplug power state"),
-            original_state
+            mock.requests().last().unwrap(),
+            &json!({
+                "context": {"child_ids": ["8006AF35494E7DB13DDE9B8F40BF2E7A01"]},
+                "system": {"set_relay_state": {"state": 1}}
+            })
         );
+
+        // Energy meters of a power strip are per outlet.
+        let strip = mock.hs110();
+        assert!(matches!(
+            strip.emeter(),
+            Err(TpLinkHs110Error::PowerStripEmeter)
+        ));
+        assert!(matches!(
+            strip.emeter_reading(),
+            Err(TpLinkHs110Error::PowerStripEmeter)
+        ));
+        assert!(matches!(
+            strip.get_daystat(2024, 1),
+            Err(TpLinkHs110Error::PowerStripEmeter)
+        ));
+        assert!(matches!(
+            strip.get_monthstat(2024),
+            Err(TpLinkHs110Error::PowerStripEmeter)
+        ));
+
+        let single = MockSmartplug::with_responses(vec![json!({"system": {"get_sysinfo": {
+            "model": "HS110(EU)", "err_code": 0
+        }}})]);
+        assert!(single.hs110().get_children().unwrap().is_none());
     }
 
     #[test]
-    fn get_cloudinfo() {
-        assert!(HS110::new(&*TEST_TARGET_ADDR).unwrap().cloudinfo().is_ok());
+    fn arbitrary_json() {
+        assert_eq!(
+            nested_request(&["emeter", "get_realtime"], json!({})).unwrap(),
+            json!({"emeter": {"get_realtime": {}}})
+        );
+        assert_eq!(
+            nested_request(
+                &["cnCloud", "get_intl_fw_list", "fw_list"],
+                json!({"id": 1})
+            )
+            .unwrap(),
+            json!({"cnCloud": {"get_intl_fw_list": {"fw_list": {"id": 1}}}})
+        );
+        assert!(matches!(
+            nested_request(&["", "get_realtime"], json!({})),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+
+        let mock = MockSmartplug::with_responses(vec![
+            json!({"emeter": {"get_realtime": {"power_mw": 1500, "err_code": 0}}}),
+            json!({"smartlife.iot.common.emeter": {"get_daystat": {"day": {"err_code": -2}}}}),
+            json!({"emeter": {"err_code": -1, "err_msg": "module not support"}}),
+        ]);
+        let smartplug = mock.hs110();
+        assert_eq!(
+            smartplug
+                .send_arbitrary_json("emeter", "get_realtime", json!({}))
+                .unwrap(),
+            json!({"power_mw": 1500, "err_code": 0})
+        );
+        assert_eq!(
+            smartplug
+                .send_three_level(
+                    "smartlife.iot.common.emeter",
+                    "get_daystat",
+                    "day",
+                    json!({"year": 2024})
+                )
+                .unwrap(),
+            json!({"err_code": -2})
+        );
+        assert!(matches!(
+            smartplug.send_arbitrary_json("emeter", "get_realtime", json!({})),
+            Err(TpLinkHs110Error::SmartplugErrCode(-1))
+        ));
+        assert!(matches!(
+            smartplug.send_arbitrary_json("system", "get_sysinfo", json!({})),
+            Err(TpLinkHs110Error::KeyIsNotAvailable { key, .. }) if key == "system"
+        ));
+        assert!(matches!(
+            smartplug.send_arbitrary_json("emeter", "", json!({})),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+        assert_eq!(
+            mock.requests(),
+            [
+                json!({"emeter": {"get_realtime": {}}}),
+                json!({"smartlife.iot.common.emeter": {"get_daystat": {"day": {"year": 2024}}}}),
+                json!({"emeter": {"get_realtime": {}}}),
+                json!({"system": {"get_sysinfo": {}}}),
+            ]
+        );
     }
 
     #[test]
-    #[serial]
-    fn access_points_list_and_scan() {
-        let smartplug = HS110::new(&*TEST_TARGET_ADDR).unwrap();
+    fn power_stability() {
+        let reading = |power_mw: u32| {
+            json!({"emeter": {"get_realtime": {
+                "voltage_mv": 230000, "current_ma": power_mw / 230, "power_mw": power_mw,
+                "total_wh": 1000, "err_code": 0
+            }}})
+        };
+        let hs110 = |mock: &MockSmartplug| {
+            mock.hs110()
+                .with_power_sampling_interval(Duration::from_millis(10))
+                .unwrap()
+        };
+        let window = Duration::from_millis(30);
 
-        smartplug
-            .ap_list(false)
-            .expect("failed to obtain AP list")
-            .as_array()
-            .expect("json array is expected");
+        let steady = MockSmartplug::with_responses(vec![
+            reading(100_000),
+            reading(101_000),
+            reading(99_000),
+        ]);
+        assert!(hs110(&steady).is_power_stable(window).unwrap());
 
-        assert!(
-            !smartplug
-                .ap_list(true)
-                .expect("failed to obtain AP list")
-                .as_array()
-                .expect("json array is expected")
-                .is_empty(),
-            "list of access points is not expected to be empty"
-        );
+        let starting = MockSmartplug::with_responses(vec![
+            reading(60_000),
+            reading(100_000),
+            reading(140_000),
+        ]);
+        assert!(!hs110(&starting).is_power_stable(window).unwrap());
+        let starting = MockSmartplug::with_responses(vec![
+            reading(60_000),
+            reading(100_000),
+            reading(140_000),
+        ]);
+        assert!(hs110(&starting)
+            .with_stability_threshold(50.0)
+            .unwrap()
+            .is_power_stable(window)
+            .unwrap());
+        for pct in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                starting.hs110().with_stability_threshold(pct),
+                Err(TpLinkHs110Error::InvalidParameter(_))
+            ));
+        }
+        assert!(matches!(
+            starting
+                .hs110()
+                .with_power_sampling_interval(Duration::ZERO),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+
+        let idle = MockSmartplug::with_responses(vec![reading(0)]);
+        assert!(hs110(&idle).is_power_stable(window).unwrap());
+
+        // The first window is unsettled, the second one is stable.
+        let settling = MockSmartplug::with_responses(vec![
+            reading(200_000),
+            reading(50_000),
+            reading(150_000),
+            reading(100_000),
+            reading(100_000),
+        ]);
+        hs110(&settling)
+            .wait_for_stable_power(Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(settling.requests().len(), 8);
+
+        let flapping = MockSmartplug::start({
+            let next = std::sync::atomic::AtomicU32::new(0);
+            move |_| {
+                let index = next.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                reading(if index % 2 == 0 { 10_000 } else { 100_000 })
+            }
+        });
+        assert!(matches!(
+            hs110(&flapping).wait_for_stable_power(Duration::from_millis(100)),
+            Err(TpLinkHs110Error::WaitTimeout(_))
+        ));
     }
 
     #[test]
-    #[serial]
-    #[ignore = "power-cycles devices connected to the plug"]
-    fn reboot() {
-        let hs110 = HS110::new(&*TEST_TARGET_ADDR).unwrap();
-        assert!(hs110.reboot(None).is_ok());
+    fn cloud_info_with_missing_fields() {
+        let mock = MockSmartplug::with_responses(vec![json!({"cnCloud": {"get_info": {
+            "binded": 0, "cld_connection": 0, "server": "n-devs.tplinkcloud.com", "err_code": 0
+        }}})]);
 
-        let hs110 = hs110.with_timeout(Duration::from_secs(1));
-        assert!(
-            hs110.reboot(Some(1)).is_err(),
-            "device is expected to be unreachable right after reboot command"
+        assert_eq!(
+            mock.hs110().cloud_info_typed().unwrap(),
+            CloudInfo {
+                server: "n-devs.tplinkcloud.com".to_string(),
+                ..CloudInfo::default()
+            }
         );
+    }
 
-        // Wait till the device is back online after reboot.
-        let hs110 = hs110.with_timeout(Duration::from_secs(10));
-        for _ in 0..20 {
-            if hs110.hostname().is_ok() {
-                return;
-            }
+    #[test]
+    fn trigger_wifi_scan_goes_through_request_path() {
+        let mock = MockSmartplug::with_responses(vec![json!({"netif": {"get_scaninfo": {
+            "ap_list": [], "err_code": 0
+        }}})]);
+
+        // A pre-established connection is used.
+        let smartplug = mock.hs110();
+        smartplug.connect_only().unwrap();
+        smartplug.trigger_wifi_scan().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(3);
+        while mock.requests().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
         }
-        panic!("device didn't back online after reboot");
+        assert_eq!(mock.requests().len(), 1);
+        assert_eq!(mock.connections(), 1);
+
+        // The request rate limit is respected.
+        let limited = mock.hs110().rate_limited(10.0).unwrap();
+        let started = Instant::now();
+        limited.trigger_wifi_scan().unwrap();
+        limited.trigger_wifi_scan().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        // Errors are remembered.
+        let unreachable = HS110::new("127.0.0.1:1").unwrap();
+        assert!(unreachable.trigger_wifi_scan().is_err());
+        assert!(matches!(
+            unreachable.last_error(),
+            Some(TpLinkHs110Error::IO(_))
+        ));
     }
 }
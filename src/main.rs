@@ -1,21 +1,36 @@
-use clap::{arg, Command};
-use serde_json::to_string_pretty;
-use tplink_hs110::{error::TpLinkHs110Error, HS110};
+use clap::{arg, Command, ValueEnum};
+use serde::Serialize;
+use std::{fmt::Write as _, io::Write, path::PathBuf, time::Duration};
+use tplink_hs110::{error::TpLinkHs110Error, registry::DeviceRegistry, HS110Config, HS110};
 
 fn main() -> Result<(), TpLinkHs110Error> {
     let matches = cli().get_matches();
 
+    if let Some(("config", sub_matches)) = matches.subcommand() {
+        print!("{}", manage_registry(&matches, sub_matches)?);
+        return Ok(());
+    }
+
     let hostname = matches
         .get_one::<String>("HOST")
         .ok_or(TpLinkHs110Error::HostIsNotProvided)?;
     let port = matches
         .get_one::<u16>("port")
         .ok_or(TpLinkHs110Error::PortIsNotProvided)?;
-    let smartplug = HS110::new(&format!("{hostname}:{port}"))?;
+    let mut smartplug = resolve_host(&matches, hostname, *port)?;
+    if let Some(timeout) = matches.get_one::<u64>("timeout-ms") {
+        smartplug = smartplug.with_timeout(Duration::from_millis(*timeout));
+    }
+    if let Some(timeout) = matches.get_one::<u64>("connect-timeout-ms") {
+        smartplug = smartplug.with_connect_timeout(Duration::from_millis(*timeout));
+    }
+    let output_format = *matches
+        .get_one::<OutputFormat>("output-format")
+        .expect("has default value");
 
     match matches.subcommand() {
         Some(("info", _)) => {
-            println!("{}", to_string_pretty(&smartplug.info()?)?)
+            println!("{}", output_format.format(&smartplug.info()?)?);
         }
         Some(("led", sub_matches)) => {
             let switch_on = sub_matches.get_flag("on");
@@ -37,6 +52,34 @@ fn main() -> Result<(), TpLinkHs110Error> {
             println!("LED is {led_state}");
         }
         Some(("power", sub_matches)) => {
+            if sub_matches.get_flag("countdown-remaining") {
+                match smartplug.countdown_remaining()? {
+                    Some(remaining) => {
+                        println!("Countdown fires in {}", format_duration(remaining));
+                    }
+                    None => println!("There is no active countdown"),
+                }
+                return Ok(());
+            }
+
+            if let Some(delay) = sub_matches.get_one::<Duration>("off-after") {
+                smartplug.graceful_shutdown(*delay, true)?;
+                if let Some(message) = sub_matches.get_one::<String>("message") {
+                    println!("{message}");
+                }
+
+                for remaining in (1..=delay.as_secs()).rev() {
+                    print!(
+                        "\rPower goes OFF in {:<12}",
+                        format_duration(Duration::from_secs(remaining))
+                    );
+                    std::io::stdout().flush()?;
+                    std::thread::sleep(Duration::from_secs(1));
+                }
+                println!("\rPower goes OFF now{:12}", "");
+                return Ok(());
+            }
+
             let switch_on = sub_matches.get_flag("on");
             let switch_off = sub_matches.get_flag("off");
 
@@ -56,34 +99,108 @@ fn main() -> Result<(), TpLinkHs110Error> {
             println!("Power is {power_state}");
         }
         Some(("cloudinfo", _)) => {
-            println!("{}", to_string_pretty(&smartplug.cloudinfo()?)?)
+            println!("{}", output_format.format(&smartplug.cloud_info_typed()?)?);
         }
         Some(("wifi", sub_matches)) => match sub_matches.subcommand() {
             Some(("scan", _)) => {
-                println!("{}", to_string_pretty(&smartplug.ap_list(true)?)?);
+                println!("{}", output_format.format(&smartplug.ap_list(true)?)?);
             }
             Some(("list", _)) => {
-                println!("{}", to_string_pretty(&smartplug.ap_list(false)?)?)
+                println!("{}", output_format.format(&smartplug.ap_list(false)?)?);
             }
             _ => {
                 unreachable!()
             }
         },
+        #[cfg(feature = "mqtt")]
+        Some(("emeter", sub_matches)) if sub_matches.contains_id("mqtt-broker") => {
+            publish_emeter_to_mqtt(&smartplug, sub_matches)?;
+        }
+        #[cfg(feature = "influxdb")]
+        Some(("emeter", sub_matches)) if sub_matches.get_flag("influxdb") => {
+            println!("{}", smartplug.influxdb_line_protocol("tplink_hs110", &[])?);
+        }
+        Some(("emeter", sub_matches)) if sub_matches.contains_id("cut-if-above") => {
+            let max_watts = sub_matches
+                .get_one::<f64>("cut-if-above")
+                .expect("checked above");
+            if smartplug.power_off_if_overloaded(*max_watts)? {
+                println!("Power draw exceeds {max_watts} W, power is switched OFF");
+                std::process::exit(2);
+            }
+            println!("Power draw is within {max_watts} W");
+        }
+        Some(("emeter", sub_matches))
+            if ["daily-cost", "monthly-cost", "annual-cost"]
+                .iter()
+                .any(|id| sub_matches.contains_id(id)) =>
+        {
+            let reading = smartplug.emeter_reading()?;
+            println!("Current power: {} W", reading.power_w);
+            if let Some(rate) = sub_matches.get_one::<f64>("daily-cost") {
+                println!(
+                    "Estimated daily cost: {:.2}",
+                    reading.estimated_daily_cost(*rate)
+                );
+            }
+            if let Some(rate) = sub_matches.get_one::<f64>("monthly-cost") {
+                println!(
+                    "Estimated monthly cost: {:.2}",
+                    reading.estimated_monthly_cost(*rate)
+                );
+            }
+            if let Some(rate) = sub_matches.get_one::<f64>("annual-cost") {
+                println!(
+                    "Estimated annual cost: {:.2}",
+                    reading.estimated_annual_cost(*rate)
+                );
+            }
+        }
         Some(("emeter", _)) => {
-            println!("{}", to_string_pretty(&smartplug.emeter()?)?)
+            println!("{}", output_format.format(&smartplug.emeter()?)?);
         }
         Some(("reboot", sub_matches)) => {
-            let delay = sub_matches.get_one::<u32>("delay").copied();
+            let delay = sub_matches
+                .get_one::<Duration>("delay")
+                .copied()
+                .unwrap_or_default();
 
-            smartplug.reboot(delay)?;
+            smartplug.reboot_after(delay)?;
             println!("Operation completed successfully");
         }
         Some(("factory-reset", sub_matches)) => {
-            let delay = sub_matches.get_one::<u32>("delay").copied();
+            let delay = sub_matches
+                .get_one::<Duration>("delay")
+                .copied()
+                .unwrap_or_default();
 
-            smartplug.factory_reset(delay)?;
+            smartplug.factory_reset_after(delay)?;
             println!("Operation completed successfully");
         }
+        Some(("ping", sub_matches)) if sub_matches.get_flag("histogram") => {
+            let samples = sub_matches
+                .get_one::<u32>("samples")
+                .expect("has default value");
+            println!("{}", smartplug.measure_latency_histogram(*samples)?);
+        }
+        Some(("ping", _)) => {
+            let latency = smartplug.ping()?;
+            println!("Round-trip time: {:.1} ms", latency.as_secs_f64() * 1000.0);
+        }
+        Some(("diagnose", sub_matches)) => {
+            let report = if sub_matches.get_flag("connectivity") {
+                output_format.format(&smartplug.test_connectivity(true)?)?
+            } else {
+                output_format.format(&smartplug.diagnose()?)?
+            };
+            match sub_matches.get_one::<String>("output") {
+                Some(path) => {
+                    std::fs::write(path, report + "\n")?;
+                    println!("Diagnostic report is written to {path}");
+                }
+                None => println!("{report}"),
+            }
+        }
         _ => {
             unreachable!()
         }
@@ -92,18 +209,227 @@ fn main() -> Result<(), TpLinkHs110Error> {
     Ok(())
 }
 
+/// Creates a smartplug instance for HOST given either as an address or as a name of a smartplug
+/// kept in the registry (a port of a named smartplug comes from the registry as well).
+fn resolve_host(
+    matches: &clap::ArgMatches,
+    hostname: &str,
+    port: u16,
+) -> Result<HS110, TpLinkHs110Error> {
+    match HS110::new(&format!("{hostname}:{port}")) {
+        Ok(smartplug) => Ok(smartplug),
+        Err(err) => match DeviceRegistry::load_or_default(&registry_path(matches)?)?.find(hostname)
+        {
+            Some(config) => HS110::from_config(config),
+            None => Err(err),
+        },
+    }
+}
+
+/// Returns a location of the registry file, either given on the command line or the default one.
+fn registry_path(matches: &clap::ArgMatches) -> Result<PathBuf, TpLinkHs110Error> {
+    matches
+        .get_one::<PathBuf>("registry")
+        .cloned()
+        .or_else(DeviceRegistry::default_path)
+        .ok_or_else(|| {
+            TpLinkHs110Error::InvalidParameter(
+                "unable to locate a config directory, use --registry".to_string(),
+            )
+        })
+}
+
+/// Handles `config` subcommands, returns an output to print.
+fn manage_registry(
+    matches: &clap::ArgMatches,
+    sub_matches: &clap::ArgMatches,
+) -> Result<String, TpLinkHs110Error> {
+    let path = registry_path(matches)?;
+    let mut registry = DeviceRegistry::load_or_default(&path)?;
+
+    let output = match sub_matches.subcommand() {
+        Some(("add", add_matches)) => {
+            let addr = add_matches
+                .get_one::<String>("ADDR")
+                .expect("required by clap");
+            // Validate an address before it gets stored.
+            HS110::new(addr)?;
+            let name = add_matches.get_one::<String>("name").cloned();
+            let output = format!("Added {}\n", name.as_deref().unwrap_or(addr));
+            registry.add(HS110Config {
+                addr: addr.clone(),
+                name,
+                timeout_secs: None,
+            });
+            output
+        }
+        Some(("list", _)) => {
+            let width = registry
+                .configs()
+                .iter()
+                .filter_map(|config| config.name.as_ref().map(String::len))
+                .max()
+                .unwrap_or(1);
+            let mut list = String::new();
+            for config in registry.configs() {
+                let name = config.name.as_deref().unwrap_or("-");
+                writeln!(list, "{name:<width$}  {}", config.addr)
+                    .expect("writing to a string doesn't fail");
+            }
+            return Ok(list);
+        }
+        Some(("remove", remove_matches)) => {
+            let name = remove_matches
+                .get_one::<String>("NAME")
+                .expect("required by clap");
+            if !registry.remove(name) {
+                return Err(TpLinkHs110Error::InvalidParameter(format!(
+                    "{name} is not in the registry"
+                )));
+            }
+            format!("Removed {name}\n")
+        }
+        Some(("import-discovery", _)) => {
+            let timeout = Duration::from_millis(
+                matches
+                    .get_one::<u64>("timeout-ms")
+                    .copied()
+                    .unwrap_or(3000),
+            );
+            let discovered = HS110::discover(timeout)?;
+            let known = registry.configs().len();
+            registry.merge_discovered(&discovered);
+            format!(
+                "Discovered {} smartplugs, {} of them added\n",
+                discovered.len(),
+                registry.configs().len() - known
+            )
+        }
+        _ => {
+            unreachable!()
+        }
+    };
+
+    registry.save(&path)?;
+    Ok(output)
+}
+
+/// Format of JSON output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    // Plain comments (rather than doc comments) keep possible values in a short `--help` form.
+    // Pretty printed JSON.
+    Pretty,
+
+    // Compact JSON (a single line).
+    Json,
+}
+
+impl OutputFormat {
+    /// Represents a value as JSON of this format.
+    fn format<T>(self, value: &T) -> serde_json::Result<String>
+    where
+        T: Serialize,
+    {
+        match self {
+            OutputFormat::Pretty => serde_json::to_string_pretty(value),
+            OutputFormat::Json => serde_json::to_string(value),
+        }
+    }
+}
+
+/// Formats a duration in a human-readable form like `1h 2m 3s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, s) => format!("{h}h {m}m {s}s"),
+    }
+}
+
+/// Parses a duration given as a number of seconds with an optional `s`, `m` or `h` unit suffix,
+/// e.g. `90`, `90s`, `2m` or `1h`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, 's')) => (&value[..index], 1),
+        Some((index, 'm')) => (&value[..index], 60),
+        Some((index, 'h')) => (&value[..index], 3600),
+        _ => (value, 1),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .map(Duration::from_secs)
+        .ok_or_else(|| {
+            format!("invalid duration `{value}`, expected e.g. `90`, `90s`, `2m` or `1h`")
+        })
+}
+
+/// Parses a delay given either as a number of seconds or as a human-readable duration like
+/// `1m 30s` (see `humantime` crate).
+#[cfg(feature = "humantime")]
+fn parse_delay(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(secs) => Ok(Duration::from_secs(secs)),
+        Err(_) => humantime::parse_duration(value)
+            .map_err(|err| format!("invalid duration `{value}`: {err}")),
+    }
+}
+
+/// Parses a delay, see [`parse_duration`] (human-readable durations like `1m 30s` require
+/// `humantime` feature).
+#[cfg(not(feature = "humantime"))]
+fn parse_delay(value: &str) -> Result<Duration, String> {
+    parse_duration(value)
+}
+
 fn cli() -> Command {
-    Command::new("tplink-hs110")
+    let mut command = Command::new("tplink-hs110")
         .about("TP-Link Kasa HS110 client")
         .arg_required_else_help(true)
-        .arg(arg!(<HOST> "Hostname or an IP address of the smartplug"))
+        .arg(
+            arg!(<HOST> "Hostname or an IP address of the smartplug, or its name in the registry (see `config`)")
+                .env("TPLINK_HOST"),
+        )
         .arg(
             arg!(--port <NUMBER> "TCP port number")
                 .short('p')
                 .value_parser(clap::value_parser!(u16))
                 .num_args(1)
+                .env("TPLINK_PORT")
                 .default_value("9999"),
         )
+        .arg(
+            arg!(--"timeout-ms" <MS> "Network communication timeout in milliseconds")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .num_args(1)
+                .env("TPLINK_TIMEOUT_MS")
+                .global(true),
+        )
+        .arg(
+            arg!(--"connect-timeout-ms" <MS> "Connection timeout in milliseconds (defaults to --timeout-ms)")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .num_args(1)
+                .global(true),
+        )
+        .arg(
+            arg!(--"output-format" <FORMAT> "Format of JSON output")
+                .value_parser(clap::value_parser!(OutputFormat))
+                .num_args(1)
+                .env("TPLINK_OUTPUT_FORMAT")
+                .default_value("pretty")
+                .global(true),
+        )
+        .arg(
+            arg!(--registry <FILE> "Registry of named smartplugs (defaults to devices.json in a user config directory)")
+                .value_parser(clap::value_parser!(PathBuf))
+                .num_args(1)
+                .env("TPLINK_REGISTRY")
+                .global(true),
+        )
         .subcommand_required(true)
         .allow_external_subcommands(true)
         .subcommand(Command::new("info").about("Get smartplug system information"))
@@ -137,6 +463,20 @@ fn cli() -> Command {
                         .short('0')
                         .num_args(0)
                         .conflicts_with("on"),
+                )
+                .arg(
+                    arg!(--"countdown-remaining" "Show time left until an active countdown fires")
+                        .num_args(0)
+                        .conflicts_with_all(["on", "off"]),
+                )
+                .arg(
+                    arg!(--"off-after" <DURATION> "Turn power off after a delay (e.g. 90s, 2m, 1h)")
+                        .value_parser(parse_duration)
+                        .conflicts_with_all(["on", "off", "countdown-remaining"]),
+                )
+                .arg(
+                    arg!(--message <MESSAGE> "Message to print when power off is scheduled")
+                        .requires("off-after"),
                 ),
         )
         .subcommand(Command::new("cloudinfo").about("Get cloud information"))
@@ -157,9 +497,9 @@ fn cli() -> Command {
             Command::new("reboot")
                 .about("Reboot a smart plug (causes power interruption for connected devices)")
                 .arg(
-                    arg!(--delay <NUMBER> "Delay a reboot by NUMBER of seconds")
+                    arg!(--delay <DURATION> "Delay a reboot (e.g. 30, 30s or 2m)")
                         .short('d')
-                        .value_parser(clap::value_parser!(u32))
+                        .value_parser(parse_delay)
                         .num_args(1),
                 ),
         )
@@ -167,13 +507,346 @@ fn cli() -> Command {
             Command::new("factory-reset")
                 .about("Reset device to factory settings")
                 .arg(
-                    arg!(--delay <NUMBER> "Delay a factory-reset by NUMBER of seconds")
+                    arg!(--delay <DURATION> "Delay a factory-reset (e.g. 30, 30s or 2m)")
                         .short('d')
-                        .value_parser(clap::value_parser!(u32))
+                        .value_parser(parse_delay)
                         .num_args(1),
                 ),
         )
+        .subcommand(emeter_command())
+        .subcommand(
+            Command::new("ping")
+                .about("Measure request round-trip time")
+                .arg(arg!(--histogram "Measure a distribution of round-trip times").num_args(0))
+                .arg(
+                    arg!(--samples <NUMBER> "Number of requests to measure the distribution over")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("20")
+                        .requires("histogram"),
+                ),
+        )
+        .subcommand(
+            Command::new("diagnose")
+                .about("Collect all the device state into a diagnostic report")
+                .arg(
+                    arg!(--connectivity "Test connectivity layer by layer instead (TCP, protocol, device identity, cloud, time sync)")
+                        .num_args(0),
+                )
+                .arg(
+                    arg!(--output <FILE> "Write the report to FILE instead of printing it")
+                        .short('o'),
+                ),
+        )
         .subcommand(
-            Command::new("emeter").about("Get energy meter readings (voltage, current, power)"),
+            Command::new("config")
+                .about("Manage a registry of named smartplugs (HOST is not needed)")
+                .arg_required_else_help(true)
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a smartplug (or update a known one)")
+                        .arg(arg!(<ADDR> "IP address of the smartplug"))
+                        .arg(arg!(--name <NAME> "Name to use instead of HOST").short('n')),
+                )
+                .subcommand(Command::new("list").about("List known smartplugs"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a smartplug")
+                        .arg(arg!(<NAME> "Name or address of the smartplug")),
+                )
+                .subcommand(
+                    Command::new("import-discovery")
+                        .about("Discover smartplugs in a local network and add new ones (named after their aliases)"),
+                ),
+        );
+
+    // HOST isn't needed by `config` subcommand, but usage of the other subcommands is kept as it
+    // is while HOST is required (clap omits HOST from usage once subcommands negate it).
+    command.build();
+    for subcommand in command
+        .get_subcommands_mut()
+        .filter(|subcommand| subcommand.get_name() != "config")
+    {
+        let usage = subcommand.render_usage().to_string();
+        *subcommand = std::mem::take(subcommand)
+            .override_usage(usage.trim_start_matches("Usage: ").to_string());
+    }
+    command.subcommand_negates_reqs(true)
+}
+
+fn emeter_command() -> Command {
+    let command = Command::new("emeter")
+        .about("Get energy meter readings (voltage, current, power)")
+        .arg(
+            arg!(--"daily-cost" <RATE> "Estimate daily cost at RATE per kWh based on current load")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--"monthly-cost" <RATE> "Estimate monthly cost at RATE per kWh based on current load")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--"annual-cost" <RATE> "Estimate annual cost at RATE per kWh based on current load")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--"cut-if-above" <WATTS> "Switch power off (and exit with code 2) if power draw exceeds WATTS")
+                .value_parser(clap::value_parser!(f64)),
+        );
+
+    #[cfg(feature = "influxdb")]
+    let command = command
+        .arg(arg!(--influxdb "Print readings as a line of InfluxDB line protocol").num_args(0));
+
+    #[cfg(feature = "mqtt")]
+    let command = command
+        .arg(
+            arg!(--"mqtt-broker" <URL> "Periodically publish readings to an MQTT broker (e.g. tcp://localhost:1883)")
+                .num_args(1)
+                .requires("mqtt-topic"),
+        )
+        .arg(
+            arg!(--"mqtt-topic" <TOPIC> "Base MQTT topic to publish readings under (e.g. home/plug1)")
+                .num_args(1)
+                .requires("mqtt-broker"),
         )
+        .arg(
+            arg!(--"mqtt-interval" <SECONDS> "Interval between MQTT publications")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .num_args(1)
+                .default_value("10")
+                .requires("mqtt-broker"),
+        );
+
+    command
+}
+
+/// Polls energy meter readings and publishes them to an MQTT broker until interrupted.
+#[cfg(feature = "mqtt")]
+fn publish_emeter_to_mqtt(
+    smartplug: &HS110,
+    sub_matches: &clap::ArgMatches,
+) -> Result<(), TpLinkHs110Error> {
+    let broker = sub_matches
+        .get_one::<String>("mqtt-broker")
+        .expect("required by clap");
+    let topic = sub_matches
+        .get_one::<String>("mqtt-topic")
+        .expect("required by clap");
+    let interval = Duration::from_secs(
+        *sub_matches
+            .get_one::<u64>("mqtt-interval")
+            .expect("has default value"),
+    );
+
+    let address = broker
+        .trim_start_matches("tcp://")
+        .trim_start_matches("mqtt://");
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse().map_err(|_| {
+                TpLinkHs110Error::InvalidParameter(format!("invalid MQTT broker port: {port}"))
+            })?,
+        ),
+        None => (address, 1883),
+    };
+
+    let options =
+        rumqttc::MqttOptions::new(format!("tplink-hs110-{}", std::process::id()), host, port);
+    let (client, mut connection) = rumqttc::Client::new(options, 16);
+    std::thread::spawn(move || {
+        for notification in connection.iter() {
+            if let Err(err) = notification {
+                eprintln!("MQTT connection error: {err}");
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+    });
+
+    loop {
+        match smartplug.publish_emeter_to_mqtt(&client, topic) {
+            Ok(()) => println!("Published energy meter readings to {topic}"),
+            Err(err) => eprintln!("Failed to publish energy meter readings: {err}"),
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    const VARIABLES: [&str; 5] = [
+        "TPLINK_HOST",
+        "TPLINK_PORT",
+        "TPLINK_TIMEOUT_MS",
+        "TPLINK_OUTPUT_FORMAT",
+        "TPLINK_REGISTRY",
+    ];
+
+    /// Parses command line arguments with given environment variables set.
+    fn matches_with_env(args: &[&str], env: &[(&str, &str)]) -> clap::ArgMatches {
+        for variable in VARIABLES {
+            std::env::remove_var(variable);
+        }
+        for (variable, value) in env {
+            std::env::set_var(variable, value);
+        }
+        let matches = cli().try_get_matches_from(args);
+        for variable in VARIABLES {
+            std::env::remove_var(variable);
+        }
+
+        matches.unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn environment_variables() {
+        let env = [
+            ("TPLINK_HOST", "192.168.0.155"),
+            ("TPLINK_PORT", "10000"),
+            ("TPLINK_TIMEOUT_MS", "1500"),
+            ("TPLINK_OUTPUT_FORMAT", "json"),
+        ];
+
+        let matches = matches_with_env(&["tplink-hs110", "info"], &env);
+        assert_eq!(matches.get_one::<String>("HOST").unwrap(), "192.168.0.155");
+        assert_eq!(matches.get_one::<u16>("port"), Some(&10000));
+        assert_eq!(matches.get_one::<u64>("timeout-ms"), Some(&1500));
+        assert_eq!(
+            matches.get_one::<OutputFormat>("output-format"),
+            Some(&OutputFormat::Json)
+        );
+
+        let matches = matches_with_env(
+            &[
+                "tplink-hs110",
+                "10.0.0.1",
+                "--port",
+                "9998",
+                "info",
+                "--timeout-ms",
+                "100",
+                "--output-format",
+                "pretty",
+            ],
+            &env,
+        );
+        assert_eq!(matches.get_one::<String>("HOST").unwrap(), "10.0.0.1");
+        assert_eq!(matches.get_one::<u16>("port"), Some(&9998));
+        assert_eq!(matches.get_one::<u64>("timeout-ms"), Some(&100));
+        assert_eq!(
+            matches.get_one::<OutputFormat>("output-format"),
+            Some(&OutputFormat::Pretty)
+        );
+
+        let matches = matches_with_env(&["tplink-hs110", "10.0.0.1", "info"], &[]);
+        assert_eq!(matches.get_one::<u16>("port"), Some(&9999));
+        assert_eq!(matches.get_one::<u64>("timeout-ms"), None);
+    }
+
+    #[test]
+    fn output_format() {
+        let value = serde_json::json!({"alias": "Bathroom"});
+        assert_eq!(
+            OutputFormat::Json.format(&value).unwrap(),
+            r#"{"alias":"Bathroom"}"#
+        );
+        assert_eq!(
+            OutputFormat::Pretty.format(&value).unwrap(),
+            "{\n  \"alias\": \"Bathroom\"\n}"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn registry() {
+        let dir = std::env::temp_dir().join(format!("tplink-hs110-cli-{}", std::process::id()));
+        let path = dir.join("devices.json");
+        let env = [("TPLINK_REGISTRY", path.to_str().unwrap())];
+        let run = |args: &[&str]| {
+            let matches = matches_with_env(args, &env);
+            match matches.subcommand() {
+                Some(("config", sub_matches)) => manage_registry(&matches, sub_matches),
+                _ => panic!("not a config subcommand: {args:?}"),
+            }
+        };
+
+        assert_eq!(run(&["tplink-hs110", "config", "list"]).unwrap(), "");
+        assert_eq!(
+            run(&[
+                "tplink-hs110",
+                "config",
+                "add",
+                "192.168.1.100",
+                "--name",
+                "bathroom"
+            ])
+            .unwrap(),
+            "Added bathroom\n"
+        );
+        run(&["tplink-hs110", "config", "add", "192.168.1.101:10000"]).unwrap();
+        assert!(run(&["tplink-hs110", "config", "add", "bogus"]).is_err());
+        assert_eq!(
+            run(&["tplink-hs110", "config", "list"]).unwrap(),
+            "bathroom  192.168.1.100\n-         192.168.1.101:10000\n"
+        );
+
+        let matches = matches_with_env(&["tplink-hs110", "bathroom", "power"], &env);
+        let smartplug = resolve_host(&matches, "bathroom", 9999).unwrap();
+        assert_eq!(
+            smartplug.socket_addr(),
+            "192.168.1.100:9999".parse().unwrap()
+        );
+        assert_eq!(
+            resolve_host(&matches, "10.0.0.1", 9998)
+                .unwrap()
+                .socket_addr(),
+            "10.0.0.1:9998".parse().unwrap()
+        );
+        assert!(resolve_host(&matches, "kitchen", 9999).is_err());
+
+        assert_eq!(
+            run(&["tplink-hs110", "config", "remove", "bathroom"]).unwrap(),
+            "Removed bathroom\n"
+        );
+        assert!(run(&["tplink-hs110", "config", "remove", "bathroom"]).is_err());
+        assert!(resolve_host(&matches, "bathroom", 9999).is_err());
+        assert_eq!(
+            run(&[
+                "tplink-hs110",
+                "--registry",
+                path.to_str().unwrap(),
+                "config",
+                "list"
+            ])
+            .unwrap(),
+            "-  192.168.1.101:10000\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn usage() {
+        let usage = |args: &[&str]| {
+            let help = cli().try_get_matches_from(args).unwrap_err().to_string();
+            help.lines()
+                .find_map(|line| line.strip_prefix("Usage: "))
+                .unwrap()
+                .to_string()
+        };
+
+        for name in ["led", "power", "wifi"] {
+            assert!(usage(&["tplink-hs110", "10.0.0.1", name, "--help"])
+                .starts_with(&format!("tplink-hs110 <HOST> {name}")));
+        }
+        assert_eq!(
+            usage(&["tplink-hs110", "config", "--help"]),
+            "tplink-hs110 config [OPTIONS] <COMMAND>"
+        );
+    }
 }
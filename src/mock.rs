@@ -0,0 +1,112 @@
+//! A fake smartplug which is used by unit tests to exercise the library without a real device.
+use crate::HS110;
+use serde_json::Value;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Fake smartplug listening on a loopback interface.
+pub(crate) struct MockSmartplug {
+    /// Address the fake smartplug listens on.
+    addr: SocketAddr,
+
+    /// All requests received by the fake smartplug so far.
+    requests: Arc<Mutex<Vec<Value>>>,
+
+    /// Number of connections accepted by the fake smartplug so far.
+    connections: Arc<AtomicUsize>,
+}
+
+impl MockSmartplug {
+    /// Starts a fake smartplug which answers each received request with a response produced by
+    /// `respond`.
+    pub(crate) fn start<F>(respond: F) -> Self
+    where
+        F: Fn(&Value) -> Value + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind a mock listener");
+        let addr = listener
+            .local_addr()
+            .expect("failed to obtain mock address");
+        let requests = Arc::new(Mutex::new(vec![]));
+        let respond = Arc::new(respond);
+
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let received = requests.clone();
+        let accepted = connections.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    break;
+                };
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let received = received.clone();
+                let respond = respond.clone();
+                thread::spawn(move || {
+                    while let Some(request) = Self::read_request(&mut stream) {
+                        let response = respond(&request);
+                        received.lock().unwrap().push(request);
+                        if stream
+                            .write_all(&HS110::encrypt(response.to_string()))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        Self {
+            addr,
+            requests,
+            connections,
+        }
+    }
+
+    /// Starts a fake smartplug which answers requests with given responses in order. The last
+    /// response is repeated once the list is exhausted.
+    pub(crate) fn with_responses(responses: Vec<Value>) -> Self {
+        let next = AtomicUsize::new(0);
+        Self::start(move |_| {
+            let index = next.fetch_add(1, Ordering::SeqCst);
+            responses[index.min(responses.len() - 1)].clone()
+        })
+    }
+
+    /// Reads and decrypts a single request. Returns `None` once a client closes the connection.
+    fn read_request(stream: &mut TcpStream) -> Option<Value> {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).ok()?;
+        let mut payload = vec![0u8; u32::from_be_bytes(header) as usize];
+        stream.read_exact(&mut payload).ok()?;
+
+        let request = HS110::decrypt(&[&header[..], &payload[..]].concat()).ok()?;
+        serde_json::from_str(&request).ok()
+    }
+
+    /// Returns an `HS110` instance pointing to the fake smartplug.
+    pub(crate) fn hs110(&self) -> HS110 {
+        HS110::new(&self.addr.to_string())
+            .unwrap()
+            .with_timeout(Duration::from_secs(3))
+    }
+
+    /// Returns number of connections accepted by the fake smartplug so far.
+    pub(crate) fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Returns all requests received by the fake smartplug so far.
+    pub(crate) fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+}
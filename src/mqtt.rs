@@ -0,0 +1,184 @@
+//! Publishing energy meter readings to an MQTT broker (requires `mqtt` feature).
+//!
+//! Readings are published under a given base topic using the following structure:
+//! ```text
+//! {base_topic}/voltage  - voltage in Volts
+//! {base_topic}/current  - current in Amperes
+//! {base_topic}/power    - power in Watts
+//! {base_topic}/total    - total consumed energy in kWh
+//! {base_topic}/state    - all the readings above as a JSON object
+//! ```
+use crate::HS110;
+use rumqttc::{Client, QoS};
+use serde_json::Value;
+
+impl HS110 {
+    /// Attempts to read the energy meter and publish the readings to an MQTT broker under
+    /// `base_topic` (see [module documentation](crate::mqtt) for topic structure).
+    ///
+    /// Note that `client` only queues messages, its connection should be driven (iterated)
+    /// elsewhere for messages to be actually delivered.
+    pub fn publish_emeter_to_mqtt(
+        &self,
+        client: &Client,
+        base_topic: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for (topic, payload) in emeter_mqtt_messages(base_topic, &self.emeter()?) {
+            client.publish(topic, QoS::AtLeastOnce, false, payload)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds `(topic, payload)` pairs to be published for given energy meter readings.
+fn emeter_mqtt_messages(base_topic: &str, emeter: &Value) -> Vec<(String, String)> {
+    let base_topic = base_topic.trim_end_matches('/');
+    let mut state = serde_json::Map::new();
+    let mut messages = vec![];
+
+    for field in ["voltage", "current", "power", "total"] {
+        let value = emeter.get(field).cloned().unwrap_or(Value::Null);
+        messages.push((format!("{base_topic}/{field}"), value.to_string()));
+        state.insert(field.to_string(), value);
+    }
+    messages.push((
+        format!("{base_topic}/state"),
+        Value::from(state).to_string(),
+    ));
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSmartplug;
+    use rumqttc::{Event, MqttOptions, Packet};
+    use serde_json::json;
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    /// Reads a single MQTT packet, returns its fixed header type byte and the rest of it.
+    fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).unwrap();
+        let packet_type = byte[0];
+
+        let mut remaining_len = 0;
+        for shift in (0..28).step_by(7) {
+            stream.read_exact(&mut byte).unwrap();
+            remaining_len |= usize::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+
+        let mut body = vec![0u8; remaining_len];
+        stream.read_exact(&mut body).unwrap();
+        (packet_type, body)
+    }
+
+    #[test]
+    fn emeter_messages() {
+        let emeter = json!({
+            "current": 0.027824,
+            "current_ma": 27.824,
+            "err_code": 0,
+            "power": 0.770242,
+            "power_mw": 770.242,
+            "total": 625.833,
+            "total_wh": 625833.0,
+            "voltage": 228.603726,
+            "voltage_mv": 228603.726,
+        });
+
+        let messages = emeter_mqtt_messages("home/plug1/", &emeter);
+        assert_eq!(
+            messages[..4],
+            [
+                ("home/plug1/voltage".into(), "228.603726".into()),
+                ("home/plug1/current".into(), "0.027824".into()),
+                ("home/plug1/power".into(), "0.770242".into()),
+                ("home/plug1/total".into(), "625.833".into()),
+            ]
+        );
+        assert_eq!(messages[4].0, "home/plug1/state");
+        assert_eq!(
+            serde_json::from_str::<Value>(&messages[4].1).unwrap(),
+            json!({"voltage": 228.603726, "current": 0.027824, "power": 0.770242, "total": 625.833})
+        );
+    }
+
+    #[test]
+    fn publish_to_broker() {
+        let smartplug = MockSmartplug::with_responses(vec![json!({"emeter": {"get_realtime": {
+            "voltage": 230.5, "current": 0.5, "power": 115.25, "total": 12.5, "err_code": 0
+        }}})]);
+
+        // A fake broker accepting a single client and acknowledging its messages.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let broker = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (packet_type, connect) = read_packet(&mut stream);
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+
+            let mut published = vec![];
+            for _ in 0..5 {
+                let (packet_type, publish) = read_packet(&mut stream);
+                assert_eq!(packet_type, 0x32, "QoS 1 PUBLISH is expected");
+                let topic_len = usize::from(u16::from_be_bytes([publish[0], publish[1]]));
+                let (topic, rest) = publish[2..].split_at(topic_len);
+                let (packet_id, payload) = rest.split_at(2);
+                stream
+                    .write_all(&[0x40, 0x02, packet_id[0], packet_id[1]])
+                    .unwrap();
+                published.push((
+                    String::from_utf8(topic.to_vec()).unwrap(),
+                    String::from_utf8(payload.to_vec()).unwrap(),
+                ));
+            }
+
+            (packet_type, connect, published)
+        });
+
+        let (client, mut connection) =
+            Client::new(MqttOptions::new("hs110-test", "127.0.0.1", port), 10);
+        smartplug
+            .hs110()
+            .publish_emeter_to_mqtt(&client, "home/plug1")
+            .unwrap();
+        let mut acknowledged = 0;
+        for event in connection.iter() {
+            if let Event::Incoming(Packet::PubAck(_)) = event.unwrap() {
+                acknowledged += 1;
+                if acknowledged == 5 {
+                    break;
+                }
+            }
+        }
+
+        let (packet_type, connect, published) = broker.join().unwrap();
+        assert_eq!(packet_type, 0x10, "CONNECT is expected first");
+        // Protocol name ("MQTT"), protocol level (3.1.1), flags and keep alive, client id.
+        assert_eq!(connect[..7], [0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04]);
+        assert_eq!(connect[10..], *b"\x00\x0ahs110-test");
+        assert_eq!(
+            published,
+            [
+                ("home/plug1/voltage".into(), "230.5".into()),
+                ("home/plug1/current".into(), "0.5".into()),
+                ("home/plug1/power".into(), "115.25".into()),
+                ("home/plug1/total".into(), "12.5".into()),
+                (
+                    "home/plug1/state".into(),
+                    r#"{"current":0.5,"power":115.25,"total":12.5,"voltage":230.5}"#.into()
+                ),
+            ]
+        );
+    }
+}
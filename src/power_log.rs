@@ -0,0 +1,177 @@
+//! Logging energy meter readings into a CSV file in a background thread.
+use crate::{error::TpLinkHs110Error, EmeterReading, HS110};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Header of a power log, see [`HS110::start_power_log`].
+pub const POWER_LOG_HEADER: &str = "timestamp,voltage_v,current_a,power_w,total_kwh\n";
+
+impl HS110 {
+    /// Starts polling the energy meter every `interval` in a background thread and appending
+    /// readings to a CSV file at `path` (see [`POWER_LOG_HEADER`] for columns, `timestamp` is
+    /// a number of seconds since Unix epoch). The header is written only if the file doesn't
+    /// exist yet (or is empty), so logging could be resumed into the same file.
+    ///
+    /// The file is opened in append mode and every row is written with a single write which is
+    /// synced to a disk right away, so the file doesn't end up with a partially written row
+    /// (a copy written to a temporary file which then replaces the log would have to contain
+    /// the whole log, so every row would cost more as the log grows).
+    /// Failed polls are logged and skipped. Logging continues until [`PowerLogHandle::stop`] is
+    /// called or the handle is dropped.
+    pub fn start_power_log(
+        &self,
+        path: &Path,
+        interval: Duration,
+    ) -> Result<PowerLogHandle, TpLinkHs110Error> {
+        let path = path.to_path_buf();
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let bytes_written = Arc::new(AtomicU64::new(0));
+        if file.metadata()?.len() == 0 {
+            append_row(&mut file, POWER_LOG_HEADER)?;
+            bytes_written.fetch_add(POWER_LOG_HEADER.len() as u64, Ordering::SeqCst);
+        }
+
+        let (stop, stopped) = mpsc::channel();
+        let plug = self.clone();
+        let written = bytes_written.clone();
+        let thread = thread::spawn(move || loop {
+            match plug.emeter_reading() {
+                Ok(reading) => {
+                    let row = csv_row(SystemTime::now(), &reading);
+                    match append_row(&mut file, &row) {
+                        Ok(()) => {
+                            written.fetch_add(row.len() as u64, Ordering::SeqCst);
+                        }
+                        Err(err) => log::warn!("failed to write {}: {err}", path.display()),
+                    }
+                }
+                Err(err) => log::warn!("failed to read energy meter: {err}"),
+            }
+
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        Ok(PowerLogHandle {
+            stop,
+            thread,
+            bytes_written,
+        })
+    }
+}
+
+/// Handle of a running power log, see [`HS110::start_power_log`].
+#[derive(Debug)]
+pub struct PowerLogHandle {
+    /// Channel used to signal the logging thread to stop.
+    stop: Sender<()>,
+
+    /// Logging thread.
+    thread: JoinHandle<()>,
+
+    /// Number of bytes appended to the log so far.
+    bytes_written: Arc<AtomicU64>,
+}
+
+impl PowerLogHandle {
+    /// Stops logging and waits for the logging thread to finish (an ongoing poll is completed
+    /// and its row is written).
+    pub fn stop(self) {
+        // The thread might have already gone if it has panicked, there is nothing to stop then.
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+
+    /// Returns a number of bytes (including the header, if it was written) appended to the log
+    /// so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::SeqCst)
+    }
+}
+
+/// Formats a single power log row.
+fn csv_row(time: SystemTime, reading: &EmeterReading) -> String {
+    format!(
+        "{},{},{},{},{}\n",
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        reading.voltage_v,
+        reading.current_a,
+        reading.power_w,
+        reading.total_kwh
+    )
+}
+
+/// Appends `text` to a file opened in append mode with a single write and syncs it to a disk.
+fn append_row(file: &mut File, text: &str) -> Result<(), TpLinkHs110Error> {
+    file.write_all(text.as_bytes())?;
+    file.sync_data()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSmartplug;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn csv_output() {
+        let mock = MockSmartplug::with_responses(vec![json!({"emeter": {"get_realtime": {
+            "voltage_mv": 230500, "current_ma": 150, "power_mw": 34500, "total_wh": 1250,
+            "err_code": 0
+        }}})]);
+        let dir =
+            std::env::temp_dir().join(format!("tplink-hs110-power-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("power.csv");
+        let _ = fs::remove_file(&path);
+
+        let log = mock
+            .hs110()
+            .start_power_log(&path, Duration::from_millis(20))
+            .unwrap();
+        thread::sleep(Duration::from_millis(150));
+        let bytes_written = log.bytes_written();
+        log.stop();
+
+        let csv = fs::read_to_string(&path).unwrap();
+        assert!(csv.len() as u64 >= bytes_written);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(POWER_LOG_HEADER.trim_end()));
+        let rows = lines.collect::<Vec<_>>();
+        assert!(rows.len() >= 2, "{csv}");
+        for row in &rows {
+            let (timestamp, reading) = row.split_once(',').unwrap();
+            assert!(timestamp.parse::<u64>().unwrap() > 0);
+            assert_eq!(reading, "230.5,0.15,34.5,1.25");
+        }
+
+        // Resumed logging doesn't repeat the header.
+        let log = mock
+            .hs110()
+            .start_power_log(&path, Duration::from_secs(10))
+            .unwrap();
+        log.stop();
+        let resumed = fs::read_to_string(&path).unwrap();
+        assert_eq!(resumed.matches("timestamp").count(), 1);
+        assert_eq!(resumed.lines().count(), rows.len() + 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+//! Exposing smartplug readings in Prometheus text exposition format (requires `prometheus`
+//! feature).
+use crate::{error::TpLinkHs110Error, ExtractHierarchical, HS110};
+use std::fmt::Write;
+
+impl HS110 {
+    /// Attempts to read the energy meter and relay state and represent them in Prometheus text
+    /// exposition format. Given `labels` (like `[("alias", "kitchen")]`) are attached to every
+    /// metric.
+    ///
+    /// Resulting text looks similar to this:
+    /// ```text
+    /// # HELP tplink_hs110_power_watts Current power consumption in Watts.
+    /// # TYPE tplink_hs110_power_watts gauge
+    /// tplink_hs110_power_watts{alias="kitchen"} 0.770242
+    /// ...
+    /// ```
+    pub fn prometheus_metrics_text(
+        &self,
+        labels: &[(&str, &str)],
+    ) -> Result<String, TpLinkHs110Error> {
+        let emeter = self.emeter()?;
+        let emeter_field = |field| {
+            emeter
+                .extract_hierarchical(&[field])?
+                .as_f64()
+                .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)
+        };
+        let relay_state = self
+            .info()?
+            .extract_hierarchical(&["system", "get_sysinfo", "relay_state"])?
+            .as_f64()
+            .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?;
+
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{{{}}}",
+                labels
+                    .iter()
+                    .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        };
+
+        let mut text = String::new();
+        #[rustfmt::skip]
+        let metrics = [
+            ("power_watts",      "gauge",   "Current power consumption in Watts.",  emeter_field("power")?),
+            ("voltage_volts",    "gauge",   "Current voltage in Volts.",            emeter_field("voltage")?),
+            ("current_amperes",  "gauge",   "Current electric current in Amperes.", emeter_field("current")?),
+            ("energy_kwh_total", "counter", "Total consumed energy in kWh.",        emeter_field("total")?),
+            ("relay_state",      "gauge",   "Power relay state (1 = ON, 0 = OFF).", relay_state),
+        ];
+        for (name, kind, help, value) in metrics {
+            writeln!(text, "# HELP tplink_hs110_{name} {help}").unwrap();
+            writeln!(text, "# TYPE tplink_hs110_{name} {kind}").unwrap();
+            writeln!(text, "tplink_hs110_{name}{labels} {value}").unwrap();
+        }
+
+        Ok(text)
+    }
+}
+
+/// Escapes a label value according to Prometheus text exposition format.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::MockSmartplug;
+    use serde_json::json;
+
+    #[test]
+    fn metrics_text() {
+        let mock = MockSmartplug::start(|request| match request.get("emeter") {
+            Some(_) => json!({"emeter": {"get_realtime": {
+                "current_ma": 27, "power_mw": 770, "total_wh": 625833, "voltage_mv": 228603, "err_code": 0
+            }}}),
+            None => json!({"system": {"get_sysinfo": {"relay_state": 1, "err_code": 0}}}),
+        });
+
+        let text = mock
+            .hs110()
+            .prometheus_metrics_text(&[("alias", "kitchen \"main\""), ("location", "cabinet")])
+            .unwrap();
+
+        let samples = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            samples,
+            [
+                r#"tplink_hs110_power_watts{alias="kitchen \"main\"",location="cabinet"} 0.77"#,
+                r#"tplink_hs110_voltage_volts{alias="kitchen \"main\"",location="cabinet"} 228.603"#,
+                r#"tplink_hs110_current_amperes{alias="kitchen \"main\"",location="cabinet"} 0.027"#,
+                r#"tplink_hs110_energy_kwh_total{alias="kitchen \"main\"",location="cabinet"} 625.833"#,
+                r#"tplink_hs110_relay_state{alias="kitchen \"main\"",location="cabinet"} 1"#,
+            ]
+        );
+        assert!(text.contains("# TYPE tplink_hs110_energy_kwh_total counter\n"));
+        assert!(text.contains("# TYPE tplink_hs110_power_watts gauge\n"));
+    }
+}
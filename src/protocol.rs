@@ -0,0 +1,137 @@
+//! TP-Link smart home protocol "encryption" (an autokey XOR cipher).
+//!
+//! Each byte is XOR-ed with a key, which starts as [`INITIAL_KEY`] and then becomes the previous
+//! encrypted byte. Messages sent over TCP are prefixed with a 4-byte big-endian payload length
+//! header, datagrams sent over UDP are not.
+use crate::error::TpLinkHs110Error;
+use std::mem::size_of;
+
+/// Initial key of the cipher.
+pub const INITIAL_KEY: u8 = 171;
+
+/// Length of a payload length header of messages sent over TCP.
+pub const HEADER_LEN: usize = size_of::<u32>();
+
+/// "Encrypts" a given string (which is usually a command represented as a JSON) and prefixes it
+/// with a length header.
+///
+/// This way of encryption/scrambling is necessary before sending a command to a smartplug.
+pub fn encrypt<S>(payload: S) -> Vec<u8>
+where
+    S: AsRef<str>,
+{
+    let mut buf = payload.as_ref().as_bytes().to_vec();
+    encrypt_in_place(&mut buf);
+    buf
+}
+
+/// Attempts to decrypt/unscramble data (including a length header) received from a smartplug.
+pub fn decrypt(payload: &[u8]) -> Result<String, TpLinkHs110Error> {
+    let mut buf = payload.to_vec();
+    decrypt_in_place(&mut buf)?;
+
+    Ok(buf[HEADER_LEN..].iter().map(|byte| *byte as char).collect())
+}
+
+/// "Encrypts" a plain text message in place and prefixes it with a length header. The buffer is
+/// reallocated only if it lacks capacity for the header.
+pub fn encrypt_in_place(buf: &mut Vec<u8>) {
+    let header = (buf.len() as u32).to_be_bytes();
+    xor_encrypt(buf);
+    buf.splice(0..0, header);
+}
+
+/// Attempts to decrypt/unscramble a message (including a length header) in place. The header is
+/// validated and left intact, i.e. plain text is `buf[HEADER_LEN..]`.
+pub fn decrypt_in_place(buf: &mut [u8]) -> Result<(), TpLinkHs110Error> {
+    if buf.len() < HEADER_LEN {
+        Err(TpLinkHs110Error::ShortEncryptedResponse(buf.len()))?;
+    }
+
+    let payload_len_from_header = u32::from_be_bytes(buf[..HEADER_LEN].try_into()?);
+    let payload_len_actual = buf.len() - HEADER_LEN;
+    if payload_len_actual != payload_len_from_header as usize {
+        Err(TpLinkHs110Error::EncryptedPayloadLengthMismatch {
+            payload_len_actual,
+            payload_len_from_header,
+        })?;
+    }
+
+    xor_decrypt(&mut buf[HEADER_LEN..]);
+
+    Ok(())
+}
+
+/// "Encrypts" a given string for sending over UDP (no length header).
+pub fn encrypt_datagram<S>(payload: S) -> Vec<u8>
+where
+    S: AsRef<str>,
+{
+    let mut buf = payload.as_ref().as_bytes().to_vec();
+    xor_encrypt(&mut buf);
+    buf
+}
+
+/// Decrypts/unscrambles a datagram received over UDP (no length header).
+pub fn decrypt_datagram(payload: &[u8]) -> String {
+    let mut buf = payload.to_vec();
+    xor_decrypt(&mut buf);
+    buf.iter().map(|byte| *byte as char).collect()
+}
+
+/// Applies the cipher to plain text bytes.
+fn xor_encrypt(buf: &mut [u8]) {
+    let mut key = INITIAL_KEY;
+    for byte in buf {
+        key ^= *byte;
+        *byte = key;
+    }
+}
+
+/// Reverts the cipher applied to encrypted bytes.
+fn xor_decrypt(buf: &mut [u8]) {
+    let mut key = INITIAL_KEY;
+    for byte in buf {
+        let encrypted = *byte;
+        *byte ^= key;
+        key = encrypted;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &str = r#"{"system":{"get_sysinfo":{}}}"#;
+
+    #[test]
+    fn in_place() {
+        let mut buf = PLAIN.as_bytes().to_vec();
+        encrypt_in_place(&mut buf);
+        assert_eq!(buf, encrypt(PLAIN));
+        assert_eq!(buf[..HEADER_LEN], (PLAIN.len() as u32).to_be_bytes());
+        assert_eq!(buf[HEADER_LEN], b'{' ^ INITIAL_KEY);
+
+        decrypt_in_place(&mut buf).unwrap();
+        assert_eq!(&buf[HEADER_LEN..], PLAIN.as_bytes());
+
+        assert!(matches!(
+            decrypt_in_place(&mut [0, 0, 0]),
+            Err(TpLinkHs110Error::ShortEncryptedResponse(3))
+        ));
+        assert!(matches!(
+            decrypt_in_place(&mut [0, 0, 0, 2, 0xd0]),
+            Err(TpLinkHs110Error::EncryptedPayloadLengthMismatch {
+                payload_len_actual: 1,
+                payload_len_from_header: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(decrypt(&encrypt(PLAIN)).unwrap(), PLAIN);
+        assert_eq!(decrypt_datagram(&encrypt_datagram(PLAIN)), PLAIN);
+        assert_eq!(encrypt_datagram(PLAIN), encrypt(PLAIN)[HEADER_LEN..]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Persistent list of known smartplugs, e.g. saved after discovery (see [`HS110::discover`]) so
+//! subsequent runs don't need to scan a network again.
+//!
+//! A registry is kept as a JSON array of [`HS110Config`] objects:
+//! ```text
+//! [
+//!   {"addr": "192.168.1.100:9999", "name": "bathroom", "timeout_secs": 3},
+//!   {"addr": "192.168.1.101:9999"}
+//! ]
+//! ```
+use crate::{error::TpLinkHs110Error, DiscoveredDevice, HS110Config, HS110};
+use directories::ProjectDirs;
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// List of known smartplugs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceRegistry {
+    /// Configurations of known smartplugs.
+    configs: Vec<HS110Config>,
+}
+
+impl DeviceRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a default registry location in a per-user config directory, e.g.
+    /// `~/.config/tplink-hs110/devices.json` on Linux. `None` if a home directory can't be found.
+    pub fn default_path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "tplink-hs110").map(|dirs| dirs.config_dir().join("devices.json"))
+    }
+
+    /// Attempts to load a registry from a JSON file.
+    pub fn load(path: &Path) -> Result<Self, TpLinkHs110Error> {
+        Ok(Self {
+            configs: serde_json::from_str(&fs::read_to_string(path)?)?,
+        })
+    }
+
+    /// Attempts to load a registry from a JSON file, an empty registry is returned if there is no
+    /// such file yet.
+    pub fn load_or_default(path: &Path) -> Result<Self, TpLinkHs110Error> {
+        match Self::load(path) {
+            Err(TpLinkHs110Error::IO(err)) if err.kind() == ErrorKind::NotFound => Ok(Self::new()),
+            result => result,
+        }
+    }
+
+    /// Attempts to save a registry to a JSON file (overwriting it), missing parent directories
+    /// are created.
+    pub fn save(&self, path: &Path) -> Result<(), TpLinkHs110Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.configs)? + "\n")?;
+        Ok(())
+    }
+
+    /// Returns configurations of known smartplugs.
+    pub fn configs(&self) -> &[HS110Config] {
+        &self.configs
+    }
+
+    /// Looks up a smartplug by its name or address.
+    pub fn find(&self, name_or_addr: &str) -> Option<&HS110Config> {
+        self.configs
+            .iter()
+            .find(|known| is_referred_by(known, name_or_addr))
+    }
+
+    /// Adds a smartplug, replacing a known one with the same address (if any). A name is moved
+    /// from a known smartplug, if it has the same one (the smartplug stays known unnamed).
+    pub fn add(&mut self, config: HS110Config) {
+        if config.name.is_some() {
+            for known in &mut self.configs {
+                if known.name == config.name {
+                    known.name = None;
+                }
+            }
+        }
+
+        match self
+            .configs
+            .iter_mut()
+            .find(|known| same_addr(&known.addr, &config.addr))
+        {
+            Some(known) => *known = config,
+            None => self.configs.push(config),
+        }
+    }
+
+    /// Removes a smartplug with a given name or address, returns whether it was known.
+    pub fn remove(&mut self, name_or_addr: &str) -> bool {
+        let len = self.configs.len();
+        self.configs
+            .retain(|known| !is_referred_by(known, name_or_addr));
+        self.configs.len() != len
+    }
+
+    /// Creates instances for known smartplugs. Entries with malformed addresses are skipped (with
+    /// a warning).
+    pub fn devices(&self) -> Vec<HS110> {
+        self.configs
+            .iter()
+            .filter_map(|config| {
+                HS110::from_config(config)
+                    .map_err(|err| log::warn!("skipping {:?}: {err}", config.addr))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Adds discovered smartplugs which aren't known yet, settings (e.g. timeouts) of known ones
+    /// are kept. New smartplugs are named after their aliases unless such names are taken
+    /// already.
+    pub fn merge_discovered(&mut self, discovered: &[DiscoveredDevice]) {
+        for device in discovered {
+            let addr = device.addr.to_string();
+            if !self
+                .configs
+                .iter()
+                .any(|known| same_addr(&known.addr, &addr))
+            {
+                let name = (!device.alias.is_empty() && self.find(&device.alias).is_none())
+                    .then(|| device.alias.clone());
+                self.configs.push(HS110Config {
+                    addr,
+                    name,
+                    timeout_secs: None,
+                });
+            }
+        }
+    }
+}
+
+/// Whether a smartplug is referred to by a given name or address.
+fn is_referred_by(config: &HS110Config, name_or_addr: &str) -> bool {
+    config.name.as_deref() == Some(name_or_addr) || same_addr(&config.addr, name_or_addr)
+}
+
+/// Whether two addresses point to the same smartplug, e.g. `192.168.1.100` and
+/// `192.168.1.100:9999` do. Malformed addresses are compared as strings.
+fn same_addr(lhs: &str, rhs: &str) -> bool {
+    match (HS110::new(lhs), HS110::new(rhs)) {
+        (Ok(lhs), Ok(rhs)) => lhs.socket_addr() == rhs.socket_addr(),
+        _ => lhs == rhs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        let dir =
+            std::env::temp_dir().join(format!("tplink-hs110-registry-{}", std::process::id()));
+        let path = dir.join("nested").join("devices.json");
+
+        let mut registry = DeviceRegistry::new();
+        registry.add(HS110Config {
+            addr: "192.168.1.100".to_string(),
+            name: None,
+            timeout_secs: Some(3),
+        });
+        registry.add(HS110Config {
+            addr: "192.168.1.101:9999".to_string(),
+            name: None,
+            timeout_secs: None,
+        });
+        registry.add(HS110Config {
+            addr: "192.168.1.100:9999".to_string(),
+            name: None,
+            timeout_secs: Some(5),
+        });
+        assert_eq!(registry.configs().len(), 2);
+        assert_eq!(registry.configs()[0].timeout_secs, Some(5));
+
+        registry.merge_discovered(&[
+            DiscoveredDevice {
+                addr: "192.168.1.100:9999".parse().unwrap(),
+                device_id: "8006A".to_string(),
+                model: "HS110(EU)".to_string(),
+                alias: "Kitchen".to_string(),
+            },
+            DiscoveredDevice {
+                addr: "192.168.1.102:9999".parse().unwrap(),
+                device_id: "8006B".to_string(),
+                model: "HS100(EU)".to_string(),
+                alias: "Hall".to_string(),
+            },
+        ]);
+        assert_eq!(
+            registry.configs(),
+            [
+                HS110Config {
+                    addr: "192.168.1.100:9999".to_string(),
+                    name: None,
+                    timeout_secs: Some(5),
+                },
+                HS110Config {
+                    addr: "192.168.1.101:9999".to_string(),
+                    name: None,
+                    timeout_secs: None,
+                },
+                HS110Config {
+                    addr: "192.168.1.102:9999".to_string(),
+                    name: Some("Hall".to_string()),
+                    timeout_secs: None,
+                },
+            ]
+        );
+
+        assert!(registry.remove("192.168.1.101"));
+        assert!(!registry.remove("192.168.1.101"));
+        registry.add(HS110Config {
+            addr: "bogus".to_string(),
+            name: None,
+            timeout_secs: None,
+        });
+        assert_eq!(
+            registry
+                .devices()
+                .iter()
+                .map(HS110::socket_addr)
+                .collect::<Vec<_>>(),
+            [
+                "192.168.1.100:9999".parse().unwrap(),
+                "192.168.1.102:9999".parse().unwrap(),
+            ]
+        );
+
+        assert!(matches!(
+            DeviceRegistry::load(&path),
+            Err(TpLinkHs110Error::IO(_))
+        ));
+        assert_eq!(
+            DeviceRegistry::load_or_default(&path).unwrap(),
+            DeviceRegistry::new()
+        );
+        registry.save(&path).unwrap();
+        assert_eq!(DeviceRegistry::load(&path).unwrap(), registry);
+        assert_eq!(DeviceRegistry::load_or_default(&path).unwrap(), registry);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names() {
+        let mut registry = DeviceRegistry::new();
+        registry.add(HS110Config {
+            addr: "192.168.1.100".to_string(),
+            name: Some("bathroom".to_string()),
+            timeout_secs: None,
+        });
+        registry.add(HS110Config {
+            addr: "192.168.1.101".to_string(),
+            name: Some("kitchen".to_string()),
+            timeout_secs: None,
+        });
+        assert_eq!(
+            registry.find("bathroom").map(|config| config.addr.as_str()),
+            Some("192.168.1.100")
+        );
+        assert_eq!(
+            registry
+                .find("192.168.1.101:9999")
+                .and_then(|config| config.name.as_deref()),
+            Some("kitchen")
+        );
+        assert_eq!(registry.find("hall"), None);
+
+        // A name moves to another smartplug.
+        registry.add(HS110Config {
+            addr: "192.168.1.102".to_string(),
+            name: Some("bathroom".to_string()),
+            timeout_secs: None,
+        });
+        assert_eq!(registry.configs().len(), 3);
+        assert_eq!(
+            registry.find("bathroom").map(|config| config.addr.as_str()),
+            Some("192.168.1.102")
+        );
+        assert_eq!(registry.find("192.168.1.100").unwrap().name, None);
+
+        // Taken names aren't given to discovered smartplugs.
+        registry.merge_discovered(&[DiscoveredDevice {
+            addr: "192.168.1.103:9999".parse().unwrap(),
+            device_id: "8006C".to_string(),
+            model: "HS110(EU)".to_string(),
+            alias: "kitchen".to_string(),
+        }]);
+        assert_eq!(registry.find("192.168.1.103").unwrap().name, None);
+
+        assert!(registry.remove("kitchen"));
+        assert!(!registry.remove("kitchen"));
+        assert_eq!(registry.configs().len(), 3);
+    }
+}
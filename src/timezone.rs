@@ -0,0 +1,261 @@
+//! Converting smartplug local time into time zone aware date and time (requires `chrono`
+//! feature).
+use crate::{error::TpLinkHs110Error, DeviceTime, HS110};
+use chrono::{DateTime, Local, NaiveDate, Offset, TimeZone};
+use chrono_tz::Tz;
+use std::time::Duration;
+
+/// IANA time zones by time zone indices used by smartplugs (as seen in the Kasa app).
+const TIMEZONES: [&str; 110] = [
+    "Etc/GMT+12",
+    "Pacific/Samoa",
+    "US/Hawaii",
+    "US/Alaska",
+    "Mexico/BajaNorte",
+    "Etc/GMT+8",
+    "PST8PDT",
+    "US/Arizona",
+    "America/Mazatlan",
+    "MST",
+    "MST7MDT",
+    "Mexico/General",
+    "Etc/GMT+6",
+    "CST6CDT",
+    "America/Monterrey",
+    "Canada/Saskatchewan",
+    "America/Bogota",
+    "Etc/GMT+5",
+    "EST",
+    "America/Indiana/Indianapolis",
+    "America/Caracas",
+    "America/Asuncion",
+    "Etc/GMT+4",
+    "Canada/Atlantic",
+    "America/Cuiaba",
+    "Brazil/West",
+    "America/Santiago",
+    "Canada/Newfoundland",
+    "America/Sao_Paulo",
+    "America/Argentina/Buenos_Aires",
+    "America/Cayenne",
+    "America/Miquelon",
+    "America/Montevideo",
+    "Chile/Continental",
+    "Etc/GMT+2",
+    "Atlantic/Azores",
+    "Atlantic/Cape_Verde",
+    "Africa/Casablanca",
+    "UCT",
+    "GB",
+    "Africa/Monrovia",
+    "Europe/Amsterdam",
+    "Europe/Belgrade",
+    "Europe/Brussels",
+    "Europe/Sarajevo",
+    "Africa/Lagos",
+    "Africa/Windhoek",
+    "Asia/Amman",
+    "Europe/Athens",
+    "Asia/Beirut",
+    "Africa/Cairo",
+    "Asia/Damascus",
+    "EET",
+    "Africa/Harare",
+    "Europe/Helsinki",
+    "Asia/Istanbul",
+    "Asia/Jerusalem",
+    "Europe/Kaliningrad",
+    "Africa/Tripoli",
+    "Asia/Baghdad",
+    "Asia/Kuwait",
+    "Europe/Minsk",
+    "Europe/Moscow",
+    "Africa/Nairobi",
+    "Asia/Tehran",
+    "Asia/Muscat",
+    "Asia/Baku",
+    "Europe/Samara",
+    "Indian/Mauritius",
+    "Asia/Tbilisi",
+    "Asia/Yerevan",
+    "Asia/Kabul",
+    "Asia/Ashgabat",
+    "Asia/Yekaterinburg",
+    "Asia/Karachi",
+    "Asia/Kolkata",
+    "Asia/Colombo",
+    "Asia/Kathmandu",
+    "Asia/Almaty",
+    "Asia/Dhaka",
+    "Asia/Novosibirsk",
+    "Asia/Rangoon",
+    "Asia/Bangkok",
+    "Asia/Krasnoyarsk",
+    "Asia/Chongqing",
+    "Asia/Irkutsk",
+    "Asia/Singapore",
+    "Australia/Perth",
+    "Asia/Taipei",
+    "Asia/Ulaanbaatar",
+    "Asia/Tokyo",
+    "Asia/Seoul",
+    "Asia/Yakutsk",
+    "Australia/Adelaide",
+    "Australia/Darwin",
+    "Australia/Brisbane",
+    "Australia/Canberra",
+    "Pacific/Guam",
+    "Australia/Hobart",
+    "Antarctica/DumontDUrville",
+    "Asia/Magadan",
+    "Asia/Srednekolymsk",
+    "Etc/GMT-11",
+    "Asia/Anadyr",
+    "Pacific/Auckland",
+    "Etc/GMT-12",
+    "Pacific/Fiji",
+    "Etc/GMT-13",
+    "Pacific/Apia",
+    "Etc/GMT-14",
+];
+
+/// Looks up an IANA time zone by a smartplug time zone index.
+pub fn timezone_by_index(index: u8) -> Option<Tz> {
+    TIMEZONES.get(usize::from(index))?.parse().ok()
+}
+
+impl HS110 {
+    /// Attempts to get smartplug's local date and time converted into system local time zone.
+    pub fn local_time(&self) -> Result<DateTime<Local>, TpLinkHs110Error> {
+        to_local(&self.get_time()?, self.get_timezone_index()?)
+    }
+
+    /// Attempts to find out how far off a smartplug clock is compared to a system clock
+    /// (regardless of a direction). A considerable drift usually means a smartplug fails to
+    /// synchronize its clock over NTP.
+    pub fn time_drift(&self) -> Result<Duration, TpLinkHs110Error> {
+        Ok((self.local_time()? - Local::now())
+            .abs()
+            .to_std()
+            .unwrap_or_default())
+    }
+}
+
+/// Finds out an offset (in seconds) from UTC of a time zone with a given smartplug time zone
+/// index at a given moment (in seconds since Unix epoch).
+pub(crate) fn utc_offset(timezone_index: u8, at: i64) -> Result<i64, TpLinkHs110Error> {
+    let utc = DateTime::from_timestamp(at, 0)
+        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+        .naive_utc();
+
+    Ok(timezone(timezone_index)?
+        .offset_from_utc_datetime(&utc)
+        .fix()
+        .local_minus_utc()
+        .into())
+}
+
+/// Looks up an IANA time zone by a smartplug time zone index, reports unknown indices as errors.
+fn timezone(timezone_index: u8) -> Result<Tz, TpLinkHs110Error> {
+    timezone_by_index(timezone_index).ok_or_else(|| {
+        TpLinkHs110Error::InvalidParameter(format!("unknown timezone index {timezone_index}"))
+    })
+}
+
+/// Converts smartplug's local date and time into system local time zone given a smartplug time
+/// zone index.
+fn to_local(time: &DeviceTime, timezone_index: u8) -> Result<DateTime<Local>, TpLinkHs110Error> {
+    let timezone = timezone(timezone_index)?;
+    let naive = NaiveDate::from_ymd_opt(time.year.into(), time.month.into(), time.mday.into())
+        .and_then(|date| date.and_hms_opt(time.hour.into(), time.min.into(), time.sec.into()))
+        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?;
+
+    // Ambiguous local time (during a DST transition) is resolved to the earlier instant.
+    Ok(timezone
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or(TpLinkHs110Error::UnexpectedValueRepresentation)?
+        .with_timezone(&Local))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSmartplug;
+    use chrono::{Datelike, Timelike, Utc};
+    use serde_json::json;
+
+    #[test]
+    fn table() {
+        for index in 0..TIMEZONES.len() {
+            assert!(timezone_by_index(index as u8).is_some(), "{index}");
+        }
+        assert_eq!(timezone_by_index(39), Some(chrono_tz::GB));
+        assert_eq!(timezone_by_index(110), None);
+    }
+
+    #[test]
+    fn conversion() {
+        let time = DeviceTime {
+            year: 2024,
+            month: 7,
+            mday: 1,
+            hour: 12,
+            min: 30,
+            sec: 15,
+        };
+        // London, BST (UTC+1).
+        assert_eq!(
+            to_local(&time, 39).unwrap(),
+            Utc.with_ymd_and_hms(2024, 7, 1, 11, 30, 15).unwrap()
+        );
+        // Kathmandu, UTC+5:45.
+        assert_eq!(
+            to_local(&time, 77).unwrap(),
+            Utc.with_ymd_and_hms(2024, 7, 1, 6, 45, 15).unwrap()
+        );
+        assert!(matches!(
+            to_local(&time, 200),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+
+        let summer = Utc
+            .with_ymd_and_hms(2024, 7, 1, 12, 0, 0)
+            .unwrap()
+            .timestamp();
+        let winter = Utc
+            .with_ymd_and_hms(2024, 1, 1, 12, 0, 0)
+            .unwrap()
+            .timestamp();
+        assert_eq!(utc_offset(39, summer).unwrap(), 3600);
+        assert_eq!(utc_offset(39, winter).unwrap(), 0);
+        assert_eq!(utc_offset(77, winter).unwrap(), 5 * 3600 + 45 * 60);
+        assert!(matches!(
+            utc_offset(200, winter),
+            Err(TpLinkHs110Error::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn local_time_and_drift() {
+        let mock = MockSmartplug::start(|request| {
+            if request.pointer("/time/get_timezone").is_some() {
+                return json!({"time": {"get_timezone": {"index": 38, "err_code": 0}}});
+            }
+            let now = Utc::now() - chrono::Duration::minutes(5);
+            json!({"time": {"get_time": {
+                "year": now.year(), "month": now.month(), "mday": now.day(),
+                "hour": now.hour(), "min": now.minute(), "sec": now.second(),
+                "err_code": 0
+            }}})
+        });
+        let smartplug = mock.hs110();
+
+        let drift = smartplug.time_drift().unwrap();
+        assert!(
+            (Duration::from_secs(299)..Duration::from_secs(305)).contains(&drift),
+            "{drift:?}"
+        );
+        assert!(smartplug.local_time().unwrap() < Local::now());
+    }
+}
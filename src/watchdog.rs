@@ -0,0 +1,281 @@
+//! Power-cycling a protected device (e.g. a router or a NAS) once it stops responding (requires
+//! `watchdog` feature).
+use crate::HS110;
+use std::{
+    fmt,
+    net::{SocketAddr, TcpStream},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Default time a protected device is given to boot after a reboot, see
+/// [`Watchdog::with_boot_grace_period`].
+pub const DEFAULT_BOOT_GRACE_PERIOD: Duration = Duration::from_secs(300);
+
+/// Default number of reboots in a row without a protected device recovering, see
+/// [`Watchdog::with_max_reboots`].
+pub const DEFAULT_MAX_REBOOTS: u32 = 3;
+
+/// Watchdog which reboots a smartplug (i.e. power-cycles a device connected to it) if a target
+/// address doesn't accept TCP connections for a number of consecutive checks.
+pub struct Watchdog {
+    /// Smartplug powering a protected device.
+    plug: HS110,
+
+    /// Address of a protected device (a port is expected to accept TCP connections).
+    target: SocketAddr,
+
+    /// Interval between checks (also used as a connection timeout).
+    check_interval: Duration,
+
+    /// Number of consecutive failed checks which triggers a reboot.
+    fail_threshold: u32,
+
+    /// Time after a reboot during which no checks are made.
+    boot_grace_period: Duration,
+
+    /// Number of reboots in a row without a protected device recovering after which the watchdog
+    /// stops rebooting it.
+    max_reboots: u32,
+
+    /// Optional callback called after a smartplug is rebooted.
+    on_reboot: Option<Box<dyn Fn() + Send>>,
+}
+
+impl Watchdog {
+    /// Creates a watchdog which checks `target` every `check_interval` and reboots `plug` once
+    /// `fail_threshold` consecutive checks fail (a threshold of `0` is treated as `1`).
+    pub fn new(
+        plug: HS110,
+        target: SocketAddr,
+        check_interval: Duration,
+        fail_threshold: u32,
+    ) -> Self {
+        Self {
+            plug,
+            target,
+            check_interval,
+            fail_threshold: fail_threshold.max(1),
+            boot_grace_period: DEFAULT_BOOT_GRACE_PERIOD,
+            max_reboots: DEFAULT_MAX_REBOOTS,
+            on_reboot: None,
+        }
+    }
+
+    /// Sets time a protected device is given to boot after a reboot before checks are resumed
+    /// (defaults to [`DEFAULT_BOOT_GRACE_PERIOD`]). It should cover the longest boot time of
+    /// the device, otherwise a slowly booting device gets power-cycled again.
+    #[must_use]
+    pub fn with_boot_grace_period(mut self, grace_period: Duration) -> Self {
+        self.boot_grace_period = grace_period;
+        self
+    }
+
+    /// Sets a number of reboots in a row after which the watchdog gives up if a protected device
+    /// still doesn't recover (defaults to [`DEFAULT_MAX_REBOOTS`]). Checks go on and rebooting
+    /// is resumed once the device has been reachable again.
+    #[must_use]
+    pub fn with_max_reboots(mut self, max_reboots: u32) -> Self {
+        self.max_reboots = max_reboots;
+        self
+    }
+
+    /// Sets a callback which is called each time a smartplug is rebooted (e.g. to send
+    /// a notification).
+    #[must_use]
+    pub fn with_on_reboot<F>(mut self, callback: F) -> Self
+    where
+        F: Fn() + Send + 'static,
+    {
+        self.on_reboot = Some(Box::new(callback));
+        self
+    }
+
+    /// Starts watching in a background thread. The watchdog runs until [`WatchdogHandle::stop`]
+    /// is called or the handle is dropped.
+    pub fn start(self) -> WatchdogHandle {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut failures = 0;
+            let mut reboots = 0;
+            loop {
+                if TcpStream::connect_timeout(&self.target, self.check_interval).is_ok() {
+                    failures = 0;
+                    reboots = 0;
+                } else {
+                    failures += 1;
+                    log::debug!(
+                        "{} is unreachable ({failures}/{} checks)",
+                        self.target,
+                        self.fail_threshold
+                    );
+                }
+
+                let mut pause = self.check_interval;
+                if failures >= self.fail_threshold && reboots < self.max_reboots {
+                    failures = 0;
+                    match self.plug.reboot_after(Duration::ZERO) {
+                        Ok(()) => {
+                            reboots += 1;
+                            log::warn!("{} is unreachable, smartplug is rebooted", self.target);
+                            if reboots == self.max_reboots {
+                                log::error!(
+                                    "{} hasn't recovered after {reboots} reboots, giving up \
+                                     until it is reachable again",
+                                    self.target
+                                );
+                            }
+                            if let Some(on_reboot) = &self.on_reboot {
+                                on_reboot();
+                            }
+                            pause = self.boot_grace_period;
+                        }
+                        Err(err) => log::warn!("failed to reboot smartplug: {err}"),
+                    }
+                }
+
+                match stopped.recv_timeout(pause) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        WatchdogHandle { stop, thread }
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("plug", &self.plug)
+            .field("target", &self.target)
+            .field("check_interval", &self.check_interval)
+            .field("fail_threshold", &self.fail_threshold)
+            .field("boot_grace_period", &self.boot_grace_period)
+            .field("max_reboots", &self.max_reboots)
+            .field("on_reboot", &self.on_reboot.is_some())
+            .finish()
+    }
+}
+
+/// Handle of a running [`Watchdog`].
+#[derive(Debug)]
+pub struct WatchdogHandle {
+    /// Channel used to signal the watchdog thread to stop.
+    stop: Sender<()>,
+
+    /// Watchdog thread.
+    thread: JoinHandle<()>,
+}
+
+impl WatchdogHandle {
+    /// Stops the watchdog and waits for its thread to finish (an ongoing check is completed).
+    pub fn stop(self) {
+        // The thread might have already gone if it has panicked, there is nothing to stop then.
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSmartplug;
+    use serde_json::json;
+    use std::{
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+    };
+
+    #[test]
+    fn reboots_once_target_is_unreachable() {
+        let mock =
+            MockSmartplug::with_responses(vec![json!({"system": {"reboot": {"err_code": 0}}})]);
+        let reachable = TcpListener::bind("127.0.0.1:0").unwrap();
+        let unreachable = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let healthy = Watchdog::new(
+            mock.hs110(),
+            reachable.local_addr().unwrap(),
+            Duration::from_millis(50),
+            2,
+        )
+        .start();
+        thread::sleep(Duration::from_millis(300));
+        healthy.stop();
+        assert!(mock.requests().is_empty());
+
+        let reboots = Arc::new(AtomicU32::new(0));
+        let counter = reboots.clone();
+        let watchdog = Watchdog::new(mock.hs110(), unreachable, Duration::from_millis(50), 3)
+            .with_on_reboot(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .start();
+        thread::sleep(Duration::from_millis(400));
+        watchdog.stop();
+
+        // The target is given the default grace period to boot, so it's rebooted just once.
+        assert_eq!(
+            mock.requests(),
+            [json!({"system": {"reboot": {"delay": 0}}})]
+        );
+        assert_eq!(reboots.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn slow_booting_target_is_rebooted_once() {
+        let mock =
+            MockSmartplug::with_responses(vec![json!({"system": {"reboot": {"err_code": 0}}})]);
+        let target = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        // The target boots for much longer than 3 failed checks take.
+        let booted = Arc::new(std::sync::Mutex::new(None));
+        let listener = booted.clone();
+        let watchdog = Watchdog::new(mock.hs110(), target, Duration::from_millis(50), 3)
+            .with_boot_grace_period(Duration::from_millis(800))
+            .with_on_reboot(move || {
+                let listener = listener.clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(500));
+                    *listener.lock().unwrap() = Some(TcpListener::bind(target).unwrap());
+                });
+            })
+            .start();
+        thread::sleep(Duration::from_millis(1500));
+        watchdog.stop();
+
+        assert!(booted.lock().unwrap().is_some());
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_reboots() {
+        let mock =
+            MockSmartplug::with_responses(vec![json!({"system": {"reboot": {"err_code": 0}}})]);
+        let unreachable = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let watchdog = Watchdog::new(mock.hs110(), unreachable, Duration::from_millis(20), 2)
+            .with_boot_grace_period(Duration::from_millis(50))
+            .with_max_reboots(2)
+            .start();
+        thread::sleep(Duration::from_millis(600));
+        watchdog.stop();
+
+        assert_eq!(mock.requests().len(), 2);
+    }
+}
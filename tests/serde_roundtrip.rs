@@ -0,0 +1,140 @@
+//! Serialization round trip tests for public types, fixtures mirror JSON returned by devices
+//! (identifiers are randomized).
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::fmt::Debug;
+use tplink_hs110::{
+    AccessPoint, AntitheftRule, CloudInfo, CountdownRule, DayStat, DeviceIcon, DeviceSnapshot,
+    DeviceTime, DeviceUsageStats, EmeterCalibration, EmeterReading, HS110Config, LedScheduleRule,
+    Location, MonthStat, OvercurrentConfig, ScheduleRule, SysInfo,
+};
+
+/// Deserializes a fixture, serializes it back and deserializes again making sure nothing is lost
+/// on the way.
+fn roundtrip<T>(fixture: &Value)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let value = serde_json::from_value::<T>(fixture.clone()).unwrap();
+    let serialized = serde_json::to_value(&value).unwrap();
+    assert_eq!(
+        serde_json::from_value::<T>(serialized.clone()).unwrap(),
+        value
+    );
+
+    assert_eq!(
+        serialized.as_object().unwrap().len(),
+        fixture.as_object().unwrap().len(),
+        "fields mismatch: {serialized} vs {fixture}"
+    );
+    assert_eq!(&serialized, fixture);
+}
+
+/// Same as [`roundtrip`] for each element of a fixture list.
+fn roundtrip_list<T>(fixture: &str)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let fixture = serde_json::from_str::<Value>(fixture).unwrap();
+    for element in fixture.as_array().unwrap() {
+        roundtrip::<T>(element);
+    }
+}
+
+/// Same as [`roundtrip`] for a single object fixture.
+fn roundtrip_object<T>(fixture: &str)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    roundtrip::<T>(&serde_json::from_str(fixture).unwrap());
+}
+
+#[test]
+fn cloud_info() {
+    roundtrip_object::<CloudInfo>(include_str!("fixtures/cloudinfo.json"));
+}
+
+#[test]
+fn schedule_rules() {
+    roundtrip_list::<ScheduleRule>(include_str!("fixtures/schedule_rules.json"));
+}
+
+#[test]
+fn led_schedule_rules() {
+    roundtrip_list::<LedScheduleRule>(include_str!("fixtures/led_schedule_rules.json"));
+}
+
+#[test]
+fn countdown_rules() {
+    roundtrip_list::<CountdownRule>(include_str!("fixtures/countdown_rules.json"));
+}
+
+#[test]
+fn antitheft_rules() {
+    roundtrip_list::<AntitheftRule>(include_str!("fixtures/antitheft_rules.json"));
+}
+
+#[test]
+fn emeter_reading() {
+    roundtrip_object::<EmeterReading>(include_str!("fixtures/emeter_reading.json"));
+}
+
+#[test]
+fn emeter_calibration() {
+    roundtrip_object::<EmeterCalibration>(include_str!("fixtures/emeter_calibration.json"));
+}
+
+#[test]
+fn device_usage_stats() {
+    roundtrip_object::<DeviceUsageStats>(include_str!("fixtures/device_usage.json"));
+}
+
+#[test]
+fn device_time() {
+    roundtrip_object::<DeviceTime>(include_str!("fixtures/device_time.json"));
+}
+
+#[test]
+fn sysinfo() {
+    roundtrip_object::<SysInfo>(include_str!("fixtures/sysinfo_typed.json"));
+}
+
+#[test]
+fn day_stats() {
+    roundtrip_list::<DayStat>(include_str!("fixtures/daystat.json"));
+}
+
+#[test]
+fn month_stats() {
+    roundtrip_list::<MonthStat>(include_str!("fixtures/monthstat.json"));
+}
+
+#[test]
+fn device_icons() {
+    roundtrip_list::<DeviceIcon>(include_str!("fixtures/device_icons.json"));
+}
+
+#[test]
+fn access_points() {
+    roundtrip_list::<AccessPoint>(include_str!("fixtures/access_points.json"));
+}
+
+#[test]
+fn location() {
+    roundtrip_object::<Location>(include_str!("fixtures/location.json"));
+}
+
+#[test]
+fn overcurrent_config() {
+    roundtrip_object::<OvercurrentConfig>(include_str!("fixtures/overcurrent_config.json"));
+}
+
+#[test]
+fn hs110_config() {
+    roundtrip_object::<HS110Config>(include_str!("fixtures/hs110_config.json"));
+}
+
+#[test]
+fn device_snapshot() {
+    roundtrip_object::<DeviceSnapshot>(include_str!("fixtures/device_snapshot.json"));
+}